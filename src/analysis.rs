@@ -0,0 +1,90 @@
+// Background analysis for the board window
+//
+// Runs a MultiPV search on its own thread so that the window keeps drawing
+// while the engine thinks. The result of the latest completed iteration is
+// kept for the window to show, and the search stops when the analysis is
+// dropped. `run` instead searches to a fixed depth on the calling thread and
+// prints each iteration, for the command line.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use crate::position::Position;
+use crate::san::move_to_san;
+use crate::search::{format_score, search, SearchLimits, SearchResult};
+use crate::transposition_table::TranspositionTable;
+
+const HASH_MEGABYTES : usize = 16;
+
+pub struct Analysis {
+    stop : Arc<AtomicBool>,
+    latest : Arc<Mutex<Option<SearchResult>>>,
+    handle : Option<JoinHandle<()>>
+}
+impl Analysis {
+    /// Starts analysing the position for the side to move, keeping the given
+    /// number of ranked lines
+    pub fn start(position: Position, lines: usize, table: Arc<TranspositionTable>) -> Analysis {
+        let stop : Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+        let latest : Arc<Mutex<Option<SearchResult>>> = Arc::new(Mutex::new(None));
+
+        let handle = {
+            let (stop, latest) = (Arc::clone(&stop), Arc::clone(&latest));
+            thread::spawn(move || {
+                let mut limits = SearchLimits::infinite();
+                limits.multi_pv = lines;
+                search(&position, limits, &table, &stop, |result| {
+                    if let Ok(mut latest) = latest.lock() {
+                        *latest = Some(result.clone());
+                    }
+                });
+            })
+        };
+
+        Analysis {
+            stop,
+            latest,
+            handle : Some(handle)
+        }
+    }
+
+    /// The result of the deepest iteration completed so far
+    pub fn latest(&self) -> Option<SearchResult> {
+        self.latest.lock().ok().and_then(|latest| latest.clone())
+    }
+}
+impl Drop for Analysis {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Prints an iteration of a search with its lines in SAN
+fn print_iteration(position: &Position, result: &SearchResult) {
+    for line in result.lines.iter() {
+        let mut position = *position;
+        let mut moves : Vec<String> = vec![];
+        for &mv in line.pv.iter() {
+            moves.push(move_to_san(&position, mv));
+            position.play_move(mv);
+        }
+        println!("depth {:2} score {:>7} nodes {:>10} time {:>6}ms  {}", result.depth, format_score(line.score), result.nodes, result.elapsed.as_millis(), moves.join(" "));
+    }
+}
+
+/// Searches a position to a fixed depth, printing every iteration and then
+/// the best move
+pub fn run(position: &Position, depth: u32) {
+    let table = TranspositionTable::new(HASH_MEGABYTES);
+    let mut limits = SearchLimits::infinite();
+    limits.depth = Some(depth);
+    let result = search(position, limits, &table, &AtomicBool::new(false), |result| print_iteration(position, result));
+    match result.best_move {
+        Some(mv) => println!("best move {}", move_to_san(position, mv)),
+        None => println!("no legal moves")
+    }
+}
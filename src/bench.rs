@@ -0,0 +1,49 @@
+// Benchmark
+//
+// Searches a fixed set of positions, from openings to endgames, to a fixed
+// depth with a fresh transposition table each. The total of the nodes
+// searched only changes when the search does, so it tells whether a change
+// meant to keep the search the same really did, and the speed in nodes per
+// second compares builds and machines.
+
+use std::sync::atomic::AtomicBool;
+use std::time::Instant;
+
+use crate::position::Position;
+use crate::search::{search, SearchLimits};
+use crate::transposition_table::TranspositionTable;
+use crate::STARTING_FEN;
+
+pub const DEFAULT_DEPTH : u32 = 5;
+const HASH_MEGABYTES : usize = 16;
+// Positions searched, from openings to endgames
+const FENS : [&str; 8] = [
+    STARTING_FEN,
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+    "r2q1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/PP3PPP/R2QKB1R w KQ - 0 9",
+    "2r3k1/pp3ppp/4p3/3pP3/3P4/P4N2/1P3PPP/2R3K1 w - - 0 25",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "8/8/4k3/3p4/3P4/4K3/8/8 w - - 0 1",
+    "r1b2rk1/2q1b1pp/p2ppn2/1p6/3QP3/1BN1B3/PPP3PP/R4RK1 w - - 0 1"
+];
+
+/// Searches every position to `depth`, printing the nodes of each, and
+/// returns the total
+pub fn run(depth: u32) -> Result<u64, String> {
+    let start = Instant::now();
+    let mut total_nodes : u64 = 0;
+    for (index, fen) in FENS.iter().enumerate() {
+        let table = TranspositionTable::new(HASH_MEGABYTES);
+        let position = Position::from_fen(fen).map_err(|error| format!("bench position {} is not a position: {}", index + 1, error))?;
+        let mut limits = SearchLimits::infinite();
+        limits.depth = Some(depth);
+        let result = search(&position, limits, &table, &AtomicBool::new(false), |_| {});
+        println!("position {:2}: {:>10} nodes  {}", index + 1, result.nodes, fen);
+        total_nodes += result.nodes;
+    }
+    let millis = start.elapsed().as_millis().max(1);
+    println!();
+    println!("{} nodes {} nps", total_nodes, total_nodes as u128 * 1000 / millis);
+    Ok(total_nodes)
+}
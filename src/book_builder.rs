@@ -0,0 +1,96 @@
+// Opening book building
+//
+// Replays the games of a PGN collection and counts, for every position of
+// the first few plies, how often each move was played and how it scored for
+// the side that played it. Moves played often enough and scoring well enough
+// go into a Polyglot book, weighted by their points: two for a win and one
+// for a draw, as Polyglot itself weights them.
+//
+// Unfinished games tell nothing about how a move scored, so they are left
+// out, as are games that can not be read or have an illegal move.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufWriter, Write};
+
+use crate::pgn::read_games;
+use crate::polyglot::{encode_move, polyglot_key};
+use crate::Colors;
+
+pub const DEFAULT_MAX_PLIES : usize = 20;
+pub const DEFAULT_MIN_GAMES : u32 = 2;
+pub const DEFAULT_MIN_SCORE : f64 = 0.0;
+
+/// Games played and points scored with a move, two for a win and one for a
+/// draw
+#[derive(Default, Clone, Copy)]
+struct MoveStats {
+    games : u32,
+    points : u32
+}
+
+/// Builds a Polyglot book from the games of a PGN file, keeping moves from
+/// the first `max_plies` plies that were played in at least `min_games`
+/// games and scored at least `min_score`, between 0 and 1
+pub fn run(pgn_path: &str, output: &str, max_plies: usize, min_games: u32, min_score: f64) -> Result<(), String> {
+    let text = fs::read_to_string(pgn_path).map_err(|error| format!("can not read {}: {}", pgn_path, error))?;
+
+    let (games, errors) = read_games(&text);
+    let mut positions : HashMap<u64, HashMap<u16, MoveStats>> = HashMap::new();
+    let (mut games_used, mut illegal_games) = (0, 0);
+    for game in games.iter() {
+        let white_score = match game.white_score() {
+            Some(score) => score,
+            None => continue
+        };
+        let replayed = match game.replay(false) {
+            Ok(replayed) => replayed,
+            Err(_) => {
+                illegal_games += 1;
+                continue;
+            }
+        };
+        games_used += 1;
+
+        for (position, &mv) in replayed.positions.iter().zip(replayed.moves.iter()).take(max_plies) {
+            let score = if position.turn() == Colors::White { white_score } else { 1.0 - white_score };
+            let stats = positions.entry(polyglot_key(position)).or_default().entry(encode_move(position.board(), mv)).or_default();
+            stats.games += 1;
+            stats.points += (2.0 * score) as u32;
+        }
+    }
+
+    let mut entries : Vec<(u64, u16, u32)> = vec![];
+    for (key, moves) in positions.iter() {
+        for (&mv, stats) in moves.iter() {
+            let score = stats.points as f64 / (2 * stats.games) as f64;
+            if stats.games >= min_games && score >= min_score && stats.points > 0 {
+                entries.push((*key, mv, stats.points));
+            }
+        }
+    }
+    // Weights are scaled down when the heaviest would not fit
+    let heaviest = entries.iter().map(|&(_, _, weight)| weight).max().unwrap_or(0);
+    let scale = (heaviest as f64 / u16::MAX as f64).max(1.0);
+    entries.sort_by(|a, b| a.0.cmp(&b.0).then(b.2.cmp(&a.2)).then(a.1.cmp(&b.1)));
+
+    let file = fs::File::create(output).map_err(|error| format!("can not create {}: {}", output, error))?;
+    let mut writer = BufWriter::new(file);
+    let write_error = |error: std::io::Error| format!("can not write {}: {}", output, error);
+    for &(key, mv, weight) in entries.iter() {
+        let weight = ((weight as f64 / scale) as u16).max(1);
+        let mut entry : Vec<u8> = Vec::with_capacity(16);
+        entry.extend_from_slice(&key.to_be_bytes());
+        entry.extend_from_slice(&mv.to_be_bytes());
+        entry.extend_from_slice(&weight.to_be_bytes());
+        // The learning field is unused
+        entry.extend_from_slice(&0u32.to_be_bytes());
+        writer.write_all(&entry).map_err(write_error)?;
+    }
+    writer.flush().map_err(write_error)?;
+
+    println!("{} games used, {} that could not be read and {} with an illegal move left out", games_used, errors.len(), illegal_games);
+    let position_count = entries.chunk_by(|a, b| a.0 == b.0).count();
+    println!("{} positions, {} entries written to {}", position_count, entries.len(), output);
+    Ok(())
+}
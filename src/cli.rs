@@ -0,0 +1,205 @@
+// Command line interface
+//
+// The first argument picks what the program does, the board window when
+// there is none. Options come after the positional arguments, as
+// `--name value`, where a value may run over several arguments until the
+// next option so that FEN strings need no quotes. Mistakes in the command
+// line exit with 2 and failures while running with 1, so scripts can tell
+// them apart. Only the arguments are read here; every command is a function
+// of the library.
+
+use std::collections::HashMap;
+use std::process::ExitCode;
+
+use chess_engine::pgn::{PgnGame, ReplayedGame};
+use chess_engine::position::Position;
+use chess_engine::{analysis, bench, book_builder, datagen, mate_search, perft, pgn, syzygy, tablebase, terminal, tuner, uci};
+use chess_engine::STARTING_FEN;
+
+const HELP : &str = "\
+usage: chess_engine [command] [arguments] [options]
+
+commands:
+  gui [--fen F]                      open the board window, the default
+  tui [--fen F] [--unicode] [--no-color]
+                                     play on the terminal
+  uci                                run as a UCI engine
+  perft <depth> [--fen F]            count the leaf nodes of the move tree
+  bench [depth]                      search a set of positions and report the speed
+  analyse --fen F --depth D          search a position and print each iteration
+  mate <FEN> <moves>                 look for a forced mate in at most this many moves
+  pgn-check <file> [--strict]        check that every game of a PGN file reads and replays
+  pgn <file> [game number]           step through a game of a PGN file in the window
+  book build <PGN file> <output> [max plies] [min games] [min score]
+                                     build a Polyglot opening book from games
+  datagen <output> <games> [nodes per move] [threads]
+                                     write training data from self-play games
+  tune <positions file> <output>     tune the evaluation weights
+  tablebase generate <material> <output>
+                                     generate an endgame tablebase, such as KRvK
+  tablebase probe <table file> <FEN> look a position up in a tablebase
+  tablebase syzygy <material> <directory>
+                                     write a generated table as Syzygy files
+  help                               show this text";
+
+/// Positional arguments and options of a command
+struct Arguments {
+    positional : Vec<String>,
+    options : HashMap<String, String>
+}
+impl Arguments {
+    fn parse(arguments: &[String]) -> Arguments {
+        let mut parsed = Arguments { positional: vec![], options: HashMap::new() };
+        let mut option : Option<String> = None;
+        for argument in arguments {
+            if let Some(name) = argument.strip_prefix("--") {
+                parsed.options.insert(name.to_string(), String::new());
+                option = Some(name.to_string());
+            } else if let Some(value) = option.as_ref().and_then(|name| parsed.options.get_mut(name)) {
+                if !value.is_empty() {
+                    value.push(' ');
+                }
+                value.push_str(argument);
+            } else {
+                parsed.positional.push(argument.clone());
+            }
+        }
+        parsed
+    }
+
+    fn option(&self, name: &str) -> Option<&str> {
+        self.options.get(name).map(|value| value.as_str()).filter(|value| !value.is_empty())
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.options.contains_key(name)
+    }
+
+    /// The position given with `--fen`, or the starting position
+    fn position(&self) -> Result<Position, Failure> {
+        let fen = self.option("fen").unwrap_or(STARTING_FEN);
+        Position::from_fen(fen).map_err(|error| Failure::Usage(format!("--fen \"{}\" is not a position: {}", fen, error)))
+    }
+}
+
+/// Why a command failed: the command is unknown, its arguments were wrong,
+/// or running it failed
+enum Failure {
+    UnknownCommand(String),
+    Usage(String),
+    Error(String)
+}
+
+/// Runs the command given on the command line
+pub fn run(arguments: &[String]) -> ExitCode {
+    let command = arguments.first().map(|command| command.as_str()).unwrap_or("gui");
+    let rest = Arguments::parse(arguments.get(1..).unwrap_or(&[]));
+    match run_command(command, &rest) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(Failure::UnknownCommand(command)) => {
+            eprintln!("unknown command {}\n\n{}", command, HELP);
+            ExitCode::from(2)
+        },
+        Err(Failure::Usage(usage)) => {
+            eprintln!("usage: {}", usage);
+            eprintln!("run `chess_engine help` for every command");
+            ExitCode::from(2)
+        },
+        Err(Failure::Error(error)) => {
+            eprintln!("{}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run_command(command: &str, arguments: &Arguments) -> Result<(), Failure> {
+    let positional = |index: usize| arguments.positional.get(index).map(|argument| argument.as_str());
+    let number = |index: usize| positional(index).and_then(|value| value.parse::<u64>().ok());
+    match command {
+        "help" | "--help" | "-h" => println!("{}", HELP),
+        "gui" | "--gui" => {
+            open_window(arguments.position()?, None)?;
+        },
+        "tui" | "--tui" => terminal::run(arguments.position()?, arguments.flag("unicode"), !arguments.flag("no-color")),
+        "uci" => uci::run(),
+        "perft" => {
+            let depth = number(0).filter(|&depth| depth >= 1).ok_or_else(|| Failure::Usage("perft <depth> [--fen F]".to_string()))?;
+            let position = arguments.position()?;
+            perft::run(&position, depth as u32);
+        },
+        "bench" => {
+            bench::run(number(0).map_or(bench::DEFAULT_DEPTH, |depth| depth as u32)).map_err(Failure::Error)?;
+        },
+        "analyse" | "analyze" => {
+            let usage = || Failure::Usage("analyse --fen F --depth D".to_string());
+            let depth = arguments.option("depth").and_then(|depth| depth.parse::<u32>().ok()).ok_or_else(usage)?;
+            arguments.option("fen").ok_or_else(usage)?;
+            let position = arguments.position()?;
+            analysis::run(&position, depth);
+        },
+        "mate" => {
+            let usage = || Failure::Usage("mate <FEN> <moves>".to_string());
+            let (moves, fen) = arguments.positional.split_last().filter(|(_, fen)| !fen.is_empty()).ok_or_else(usage)?;
+            let moves = moves.parse::<u32>().ok().filter(|&moves| moves >= 1).ok_or_else(usage)?;
+            let fen = fen.join(" ");
+            let position = Position::from_fen(&fen).map_err(|error| Failure::Usage(format!("\"{}\" is not a position: {}", fen, error)))?;
+            mate_search::run(&position, moves);
+        },
+        "pgn-check" => {
+            let path = positional(0).ok_or_else(|| Failure::Usage("pgn-check <file> [--strict]".to_string()))?;
+            pgn::check_file(path, arguments.flag("strict")).map_err(Failure::Error)?;
+        },
+        "pgn" => {
+            let path = positional(0).ok_or_else(|| Failure::Usage("pgn <PGN file> [game number]".to_string()))?;
+            let (game, replayed) = pgn::load_game(path, number(1).unwrap_or(1) as usize).map_err(Failure::Error)?;
+            let position = replayed.positions[0];
+            open_window(position, Some((game, replayed)))?;
+        },
+        "book" => match (positional(0), positional(1), positional(2)) {
+            (Some("build"), Some(pgn), Some(output)) => {
+                let value = |index: usize| positional(index).and_then(|value| value.parse::<f64>().ok());
+                let max_plies = value(3).map_or(book_builder::DEFAULT_MAX_PLIES, |plies| plies as usize);
+                let min_games = value(4).map_or(book_builder::DEFAULT_MIN_GAMES, |games| games as u32);
+                let min_score = value(5).unwrap_or(book_builder::DEFAULT_MIN_SCORE);
+                book_builder::run(pgn, output, max_plies, min_games, min_score).map_err(Failure::Error)?;
+            },
+            _ => return Err(Failure::Usage("book build <PGN file> <output file> [max plies] [min games] [min score]".to_string()))
+        },
+        "datagen" => match (positional(0), number(1)) {
+            (Some(output), Some(games)) => {
+                let nodes = number(2).unwrap_or(datagen::DEFAULT_NODES);
+                let threads = number(3).unwrap_or(1) as usize;
+                datagen::run(output, games as usize, nodes, threads).map_err(Failure::Error)?;
+            },
+            _ => return Err(Failure::Usage("datagen <output file> <games> [nodes per move] [threads]".to_string()))
+        },
+        "tune" => match (positional(0), positional(1)) {
+            (Some(positions), Some(output)) => tuner::run(positions, output).map_err(Failure::Error)?,
+            _ => return Err(Failure::Usage("tune <positions file> <output file>".to_string()))
+        },
+        "tablebase" => {
+            // Only a FEN runs on over several arguments, the paths are one each
+            let positionals : Vec<&str> = arguments.positional.iter().map(|argument| argument.as_str()).collect();
+            match positionals.as_slice() {
+                ["generate", material, output] => tablebase::run_generate(material, output),
+                ["probe", path, fen @ ..] if !fen.is_empty() => tablebase::run_probe(path, &fen.join(" ")),
+                ["syzygy", material, directory] => syzygy::write_tables(material, directory),
+                _ => return Err(Failure::Usage("tablebase generate <material such as KRvK> <output file>\n       tablebase probe <table file> <FEN>\n       tablebase syzygy <material> <directory>".to_string()))
+            }.map_err(Failure::Error)?;
+        },
+        _ => return Err(Failure::UnknownCommand(command.to_string()))
+    }
+    Ok(())
+}
+
+/// Opens the board window, which builds without the `gui` feature leave out
+#[cfg(feature = "gui")]
+fn open_window(position: Position, loaded_game: Option<(PgnGame, ReplayedGame)>) -> Result<(), Failure> {
+    crate::gui::initialise_window(position, loaded_game);
+    Ok(())
+}
+
+#[cfg(not(feature = "gui"))]
+fn open_window(_position: Position, _loaded_game: Option<(PgnGame, ReplayedGame)>) -> Result<(), Failure> {
+    Err(Failure::Error("this build has no board window, build with the gui feature for it".to_string()))
+}
@@ -0,0 +1,244 @@
+// Self-play training data generation
+//
+// Plays the engine against itself from randomised openings, searching a
+// fixed number of nodes per move, and writes out the quiet positions of each
+// game with the search score and the final result. Each line is
+//
+//     <FEN> | <score> | <result>
+//
+// with the score in centipawns and the result as 1.0, 0.5 or 0.0, both from
+// White's point of view. This is the text format most network trainers
+// read, and the tuner reads it too.
+//
+// Positions in check, with a capture or promotion as the best move, or with
+// a mate score are left out, as their static evaluation says little about
+// the result. Games are played on several threads at once.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::search::{is_capture, is_mate_score, search, SearchLimits};
+use crate::transposition_table::TranspositionTable;
+use crate::position::Position;
+use crate::{Colors, Move};
+
+pub const DEFAULT_NODES : u64 = 5000;
+// Random moves played from the starting position before the engine takes over
+const RANDOM_PLIES : usize = 8;
+// Openings the engine already thinks are this lopsided are thrown away
+const MAX_OPENING_SCORE : i32 = 400;
+// A game is adjudicated as won once both sides agree on a score this large
+// for this many plies in a row, and drawn once it runs this long
+const WIN_SCORE : i32 = 2000;
+const WIN_PLIES : u32 = 6;
+const MAX_GAME_PLIES : u32 = 400;
+const FIFTY_MOVE_PLIES : u32 = 100;
+const HASH_MEGABYTES : usize = 16;
+
+/// Small xorshift generator for picking opening moves
+struct Random {
+    state : u64
+}
+impl Random {
+    fn new(seed: u64) -> Random {
+        Random {
+            state : seed | 1
+        }
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        (self.state % bound as u64) as usize
+    }
+}
+
+/// Plays random moves from the starting position, returning None when the
+/// game ends before the opening does
+fn random_opening(random: &mut Random) -> Option<Position> {
+    let mut position = Position::default();
+    for _ in 0..RANDOM_PLIES {
+        let moves = position.legal_moves();
+        if moves.is_empty() {
+            return None;
+        }
+        position.play_move(moves[random.below(moves.len())]);
+    }
+    if position.legal_moves().is_empty() {
+        return None;
+    }
+    Some(position)
+}
+
+/// Searches the position at the fixed node count, returning the best move
+/// and its score for the side to move
+fn search_move(position: &Position, nodes: u64, table: &TranspositionTable) -> Option<(Move, i32)> {
+    let mut limits = SearchLimits::infinite();
+    limits.nodes = Some(nodes);
+    let result = search(position, limits, table, &AtomicBool::new(false), |_| {});
+    let score = result.lines.first()?.score;
+    Some((result.best_move?, score))
+}
+
+/// One line of training data, with the score and result for White
+fn format_line(fen: &str, white_score: i32, result: f64) -> String {
+    format!("{} | {} | {:.1}", fen, white_score, result)
+}
+
+/// Plays one game from a random opening, returning its lines of training
+/// data, or None when the opening was unusable
+fn play_game(random: &mut Random, nodes: u64, table: &TranspositionTable) -> Option<Vec<String>> {
+    let mut position = random_opening(random)?;
+    table.clear();
+    if search_move(&position, nodes, table).is_none_or(|(_, score)| score.abs() > MAX_OPENING_SCORE) {
+        return None;
+    }
+
+    // Positions kept so far with their scores for White
+    let mut positions : Vec<(String, i32)> = vec![];
+    let mut history : HashMap<u64, u32> = HashMap::new();
+    // Plies in a row White has been winning, or losing when negative
+    let mut winning_plies : i32 = 0;
+    let mut plies : u32 = 0;
+
+    // Score of the game for White
+    let result : f64 = loop {
+        let (board, turn) = (*position.board(), position.turn());
+        let repetitions = history.entry(position.hash()).or_insert(0);
+        *repetitions += 1;
+        if *repetitions >= 3 || position.halfmove_clock() >= FIFTY_MOVE_PLIES || plies >= MAX_GAME_PLIES || position.is_insufficient_material() {
+            break 0.5;
+        }
+
+        let in_check = position.is_in_check();
+        if position.legal_moves().is_empty() {
+            // Checkmate or stalemate
+            break if !in_check { 0.5 } else if turn == Colors::White { 0.0 } else { 1.0 };
+        }
+        // Only a search cut short before its first iteration finds no score
+        let (mv, score) = search_move(&position, nodes, table)?;
+        let white_score = if turn == Colors::White { score } else { -score };
+
+        // Adjudicate once the game is clearly decided
+        winning_plies = match white_score {
+            score if score >= WIN_SCORE => winning_plies.max(0) + 1,
+            score if score <= -WIN_SCORE => winning_plies.min(0) - 1,
+            _ => 0
+        };
+        if winning_plies.unsigned_abs() >= WIN_PLIES {
+            break if winning_plies > 0 { 1.0 } else { 0.0 };
+        }
+
+        let quiet = !in_check && !is_capture(&board, mv) && mv.promotion.is_none() && !is_mate_score(score);
+        if quiet {
+            positions.push((position.to_fen(), white_score));
+        }
+
+        // Pawn moves and captures can not be undone, so earlier positions
+        // can not come round again
+        if position.resets_halfmove_clock(mv) {
+            history.clear();
+        }
+        position.play_move(mv);
+        plies += 1;
+    };
+
+    Some(positions.into_iter().map(|(fen, score)| format_line(&fen, score, result)).collect())
+}
+
+/// Plays `games` games over `threads` threads at `nodes` nodes per move,
+/// writing the training data to `output`
+pub fn run(output: &str, games: usize, nodes: u64, threads: usize) -> Result<(), String> {
+    let file = File::create(output).map_err(|error| format!("can not create {}: {}", output, error))?;
+    let writer = Mutex::new(BufWriter::new(file));
+    let games_started = AtomicUsize::new(0);
+    let positions_written = AtomicUsize::new(0);
+    let seed = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_nanos() as u64);
+    let start = Instant::now();
+
+    // The first write error of any thread, after which the others stop
+    let written : io::Result<()> = thread::scope(|scope| {
+        let handles : Vec<_> = (0..threads.max(1)).map(|thread_index| {
+            let (writer, games_started, positions_written) = (&writer, &games_started, &positions_written);
+            scope.spawn(move || -> io::Result<()> {
+                let mut random = Random::new(seed ^ (thread_index as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+                let table = TranspositionTable::new(HASH_MEGABYTES);
+                while games_started.fetch_add(1, Ordering::Relaxed) < games {
+                    // Unusable openings are replaced by another game
+                    let lines = loop {
+                        if let Some(lines) = play_game(&mut random, nodes, &table) {
+                            break lines;
+                        }
+                    };
+
+                    // Positions only count once they are written
+                    let written = writer.lock()
+                        .map_err(|_| io::Error::other("a thread panicked while writing"))
+                        .and_then(|mut writer| {
+                            for line in &lines {
+                                writeln!(writer, "{}", line)?;
+                            }
+                            Ok(positions_written.fetch_add(lines.len(), Ordering::Relaxed) + lines.len())
+                        });
+                    match written {
+                        Ok(total) => println!("{} positions, {}s", total, start.elapsed().as_secs()),
+                        Err(error) => {
+                            games_started.store(games, Ordering::Relaxed);
+                            return Err(error);
+                        }
+                    }
+                }
+                Ok(())
+            })
+        }).collect();
+        handles.into_iter().try_for_each(|handle| handle.join().unwrap_or_else(|_| Err(io::Error::other("a thread panicked"))))
+    });
+    written
+        .and_then(|()| writer.into_inner().map_err(|_| io::Error::other("a thread panicked while writing")))
+        .and_then(|mut writer| writer.flush())
+        .map_err(|error| format!("can not write {}: {}", output, error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reads a line back into its position, score and result
+    fn parse_line(line: &str) -> (Position, i32, f64) {
+        let fields : Vec<&str> = line.split(" | ").collect();
+        assert_eq!(fields.len(), 3, "{}", line);
+        let position = Position::from_fen(fields[0]).unwrap();
+        (position, fields[1].parse().unwrap(), fields[2].parse().unwrap())
+    }
+
+    #[test]
+    fn writes_lines_that_read_back() {
+        let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+        let line = format_line(fen, -35, 0.5);
+        assert_eq!(line, format!("{} | -35 | 0.5", fen));
+        let (position, score, result) = parse_line(&line);
+        assert_eq!((position.to_fen().as_str(), score, result), (fen, -35, 0.5));
+    }
+
+    #[test]
+    fn plays_games_into_quiet_labelled_positions() {
+        let table = TranspositionTable::new(1);
+        let mut random = Random::new(7);
+        let lines = (0..20).find_map(|_| play_game(&mut random, 300, &table)).unwrap();
+        assert!(!lines.is_empty());
+        let results : Vec<f64> = lines.iter().map(|line| {
+            let (position, score, result) = parse_line(line);
+            assert!(!position.is_in_check() && !is_mate_score(score), "{}", line);
+            assert!([0.0, 0.5, 1.0].contains(&result), "{}", line);
+            result
+        }).collect();
+        // Every position of a game carries the game's result
+        assert!(results.iter().all(|&result| result == results[0]));
+    }
+}
@@ -0,0 +1,350 @@
+// Endgame knowledge
+//
+// A few endgames are simple enough to know exactly, but hard for a shallow
+// search to play well from the evaluation alone:
+//
+// - King and pawn against king is looked up in a bitbase of every position,
+//   won or drawn, built by retrograde analysis the first time it is needed.
+// - King and queen or rook against king drives the lone king to the edge of
+//   the board and brings the other king up to help.
+// - King, bishop and knight against king drives the lone king into a corner
+//   of the bishop's colour, the only corners it can be mated in.
+// - Bare kings, or a lone minor piece, are drawn.
+//
+// Won endgames score KNOWN_WIN on top of their guidance, well above any
+// normal evaluation but below mate scores. Everything else is left to the
+// normal evaluation.
+
+use std::sync::OnceLock;
+
+use crate::evaluation::piece_value;
+use crate::{get_render_coords, Colors, Pieces, Tile};
+
+pub const KNOWN_WIN : i32 = 10000;
+// Bonuses for the lone king being near the edge or right corner, and for
+// the two kings being close, per tile
+const PUSH_TO_EDGE : i32 = 20;
+const PUSH_TO_CORNER : i32 = 30;
+const PUSH_CLOSE : i32 = 20;
+// Bonus for each rank a won pawn has advanced
+const PAWN_ADVANCE : i32 = 20;
+
+// The bitbase is built for White holding the pawn, on files a to d, with
+// tiles numbered from a1 to h8. Each position is indexed by the side to
+// move, both kings and the pawn, which can not stand on the first or last
+// rank.
+const KPK_SIZE : usize = 2 * 24 * 64 * 64;
+
+// Results while building the bitbase, as bits so the results of every
+// successor of a position can be combined
+const INVALID : u8 = 0;
+const UNKNOWN : u8 = 1;
+const DRAW : u8 = 2;
+const WIN : u8 = 4;
+
+static KPK_BITBASE : OnceLock<Vec<u64>> = OnceLock::new();
+
+#[inline]
+fn file_of(tile: usize) -> usize {
+    tile & 7
+}
+
+#[inline]
+fn rank_of(tile: usize) -> usize {
+    tile >> 3
+}
+
+/// Number of king moves between two tiles
+#[inline]
+fn distance(a: usize, b: usize) -> usize {
+    file_of(a).abs_diff(file_of(b)).max(rank_of(a).abs_diff(rank_of(b)))
+}
+
+/// Tiles a king on the tile can move to
+fn king_moves(tile: usize) -> impl Iterator<Item = usize> {
+    (0..64).filter(move |&other| distance(tile, other) == 1)
+}
+
+/// Whether a white pawn on `pawn` attacks `tile`
+#[inline]
+fn pawn_attacks(pawn: usize, tile: usize) -> bool {
+    rank_of(tile) == rank_of(pawn) + 1 && file_of(tile).abs_diff(file_of(pawn)) == 1
+}
+
+/// Index of a position in the bitbase, with 0 for White to move
+#[inline]
+fn kpk_index(black_to_move: usize, black_king: usize, white_king: usize, pawn: usize) -> usize {
+    white_king | black_king << 6 | black_to_move << 12 | file_of(pawn) << 13 | (6 - rank_of(pawn)) << 15
+}
+
+/// The result of a position that can be told without looking at its
+/// successors, or UNKNOWN
+fn classify_initial(black_to_move: bool, black_king: usize, white_king: usize, pawn: usize) -> u8 {
+    if distance(white_king, black_king) <= 1 || white_king == pawn || black_king == pawn
+        || (!black_to_move && pawn_attacks(pawn, black_king)) {
+        return INVALID;
+    }
+    let queening = pawn + 8;
+    // The pawn promotes and the new queen can not be taken
+    if !black_to_move && rank_of(pawn) == 6 && white_king != queening && black_king != queening
+        && (distance(black_king, queening) > 1 || distance(white_king, queening) == 1) {
+        return WIN;
+    }
+    if black_to_move {
+        // Stalemate, or the pawn can be taken
+        let safe = |tile: usize| distance(tile, white_king) > 1 && !pawn_attacks(pawn, tile);
+        if !king_moves(black_king).any(safe) {
+            return DRAW;
+        }
+        if distance(black_king, pawn) == 1 && distance(white_king, pawn) > 1 {
+            return DRAW;
+        }
+    }
+    UNKNOWN
+}
+
+/// The result of a position from the results of its successors
+fn classify(results: &[u8], black_to_move: bool, black_king: usize, white_king: usize, pawn: usize) -> u8 {
+    let mut successors : u8 = 0;
+    let (good, bad) = if black_to_move {
+        for tile in king_moves(black_king) {
+            successors |= results[kpk_index(0, tile, white_king, pawn)];
+        }
+        (DRAW, WIN)
+    } else {
+        for tile in king_moves(white_king) {
+            successors |= results[kpk_index(1, black_king, tile, pawn)];
+        }
+        // Pawns on the seventh rank promote, which is scored on its own
+        if rank_of(pawn) < 6 {
+            successors |= results[kpk_index(1, black_king, white_king, pawn + 8)];
+        }
+        if rank_of(pawn) == 1 && pawn + 8 != white_king && pawn + 8 != black_king {
+            successors |= results[kpk_index(1, black_king, white_king, pawn + 16)];
+        }
+        (WIN, DRAW)
+    };
+
+    if successors & good != 0 {
+        good
+    } else if successors & UNKNOWN != 0 {
+        UNKNOWN
+    } else {
+        bad
+    }
+}
+
+/// Builds the bitbase by retrograde analysis: positions with a known result
+/// are marked first, then positions whose result follows from their
+/// successors, until nothing more can be decided. What is left is drawn.
+fn build_kpk_bitbase() -> Vec<u64> {
+    let decode = |index: usize| {
+        let white_king = index & 63;
+        let black_king = index >> 6 & 63;
+        let black_to_move = index >> 12 & 1 == 1;
+        let pawn = (index >> 13 & 3) + 8 * (6 - (index >> 15 & 7));
+        (black_to_move, black_king, white_king, pawn)
+    };
+
+    let mut results : Vec<u8> = (0..KPK_SIZE).map(|index| {
+        let (black_to_move, black_king, white_king, pawn) = decode(index);
+        classify_initial(black_to_move, black_king, white_king, pawn)
+    }).collect();
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for index in 0..KPK_SIZE {
+            if results[index] != UNKNOWN {
+                continue;
+            }
+            let (black_to_move, black_king, white_king, pawn) = decode(index);
+            let result = classify(&results, black_to_move, black_king, white_king, pawn);
+            if result != UNKNOWN {
+                results[index] = result;
+                changed = true;
+            }
+        }
+    }
+
+    let mut bitbase : Vec<u64> = vec![0; KPK_SIZE / 64];
+    for (index, &result) in results.iter().enumerate() {
+        if result == WIN {
+            bitbase[index / 64] |= 1 << (index % 64);
+        }
+    }
+    bitbase
+}
+
+/// Whether king and pawn against king is won for the side with the pawn.
+/// Tiles are board indexes.
+fn probe_kpk(strong_king: usize, weak_king: usize, pawn: usize, strong_color: Colors, turn: Colors) -> bool {
+    let bitbase = KPK_BITBASE.get_or_init(build_kpk_bitbase);
+    // Board indexes run from a8, so flip them for White to count from a1
+    let flip = if strong_color == Colors::White { 0o70 } else { 0 };
+    // The bitbase only covers pawns on files a to d, the rest are mirrored
+    let mirror = if file_of(pawn) >= 4 { 7 } else { 0 };
+    let [strong_king, weak_king, pawn] = [strong_king, weak_king, pawn].map(|tile| tile ^ flip ^ mirror);
+    let black_to_move = (turn != strong_color) as usize;
+    let index = kpk_index(black_to_move, weak_king, strong_king, pawn);
+    bitbase[index / 64] & 1 << (index % 64) != 0
+}
+
+/// Distance of a tile from the centre, 0 in the middle to 6 in a corner
+#[inline]
+fn centre_distance(tile: usize) -> i32 {
+    let [x, y] = get_render_coords(tile);
+    (x.max(7 - x) + y.max(7 - y)) as i32 - 8
+}
+
+/// Tiles between the tile and the nearest corner of the given colour
+fn corner_distance(tile: usize, dark_corners: bool) -> i32 {
+    let [x, y] = get_render_coords(tile);
+    // a1 and h8 are dark, a8 and h1 light
+    let corners : [[usize; 2]; 2] = if dark_corners { [[0, 7], [7, 0]] } else { [[0, 0], [7, 7]] };
+    corners.iter()
+        .map(|&[corner_x, corner_y]| (x.abs_diff(corner_x) + y.abs_diff(corner_y)) as i32)
+        .min()
+        .unwrap_or(0)
+        .min(7)
+}
+
+/// Scores the known endgames in centipawns from the point of view of `turn`,
+/// or None when the board is not one of them
+pub fn evaluate_endgame(board: &[Tile; 64], turn: Colors) -> Option<i32> {
+    let mut kings : [Option<usize>; 2] = [None, None];
+    // Every piece other than the kings, with its tile and colour
+    let mut pieces : [(usize, Pieces, Colors); 2] = [(0, Pieces::Queen, Colors::White); 2];
+    let mut piece_count = 0;
+    for (index, tile) in board.iter().enumerate() {
+        let (piece, color) = match (tile.piece, tile.color) {
+            (Some(piece), Some(color)) => (piece, color),
+            _ => continue
+        };
+        if let Pieces::King { .. } = piece {
+            kings[color as usize] = Some(index);
+        } else if piece_count == pieces.len() {
+            return None;
+        } else {
+            pieces[piece_count] = (index, piece, color);
+            piece_count += 1;
+        }
+    }
+    let (white_king, black_king) = (kings[Colors::White as usize]?, kings[Colors::Black as usize]?);
+
+    let (strong_color, score) = match (piece_count, pieces) {
+        (0, _) => return Some(0),
+        (1, [(_, Pieces::Knight | Pieces::Bishop, _), _]) => return Some(0),
+        // Pawns can only be on the back ranks of boards set up by hand
+        (1, [(pawn, Pieces::Pawn { .. }, color), _]) if (1..7).contains(&rank_of(pawn)) => {
+            let (strong_king, weak_king) = if color == Colors::White { (white_king, black_king) } else { (black_king, white_king) };
+            if !probe_kpk(strong_king, weak_king, pawn, color, turn) {
+                return Some(0);
+            }
+            let rank = match color {
+                Colors::White => 7 - (pawn >> 3),
+                Colors::Black => pawn >> 3
+            };
+            (color, KNOWN_WIN + piece_value(Pieces::Pawn { has_moved: true, en_passantable: false }) + PAWN_ADVANCE * rank as i32)
+        },
+        (1, [(_, piece @ (Pieces::Queen | Pieces::Rook { .. }), color), _]) => {
+            let weak_king = if color == Colors::White { black_king } else { white_king };
+            let close = 7 - distance(white_king, black_king) as i32;
+            (color, KNOWN_WIN + piece_value(piece) + PUSH_TO_EDGE * centre_distance(weak_king) + PUSH_CLOSE * close)
+        },
+        (2, [(first, first_piece, first_color), (second, second_piece, second_color)]) if first_color == second_color => {
+            let bishop = match (first_piece, second_piece) {
+                (Pieces::Bishop, Pieces::Knight) => first,
+                (Pieces::Knight, Pieces::Bishop) => second,
+                _ => return None
+            };
+            let weak_king = if first_color == Colors::White { black_king } else { white_king };
+            let [x, y] = get_render_coords(bishop);
+            let dark_bishop = (x + y) % 2 == 1;
+            let close = 7 - distance(white_king, black_king) as i32;
+            let corner = 7 - corner_distance(weak_king, dark_bishop);
+            (first_color, KNOWN_WIN + PUSH_TO_CORNER * corner + PUSH_CLOSE * close)
+        },
+        _ => return None
+    };
+
+    Some(if strong_color == turn { score } else { -score })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::position::Position;
+    use crate::tablebase::{generate, DRAW, ILLEGAL};
+    use crate::initialise_board;
+
+    fn evaluate_fen(fen: &str) -> Option<i32> {
+        let position = Position::from_fen(fen).unwrap();
+        evaluate_endgame(position.board(), position.turn())
+    }
+
+    #[test]
+    fn knows_kpk_wins_and_draws() {
+        for fen in [
+            // The king on the sixth in front of its pawn wins whoever moves
+            "4k3/8/4K3/4P3/8/8/8/8 w - - 0 1",
+            "4k3/8/4K3/4P3/8/8/8/8 b - - 0 1",
+            // The lone king is outside the square of the pawn
+            "7k/8/8/8/P7/8/8/K7 b - - 0 1",
+            // The lone king has to give up the opposition
+            "8/8/4k3/8/4K3/4P3/8/8 b - - 0 1",
+            // The same for Black
+            "8/8/8/8/4p3/4k3/8/4K3 w - - 0 1"
+        ] {
+            let position = Position::from_fen(fen).unwrap();
+            let strong = if position.board().iter().any(|tile| tile.color == Some(Colors::White) && matches!(tile.piece, Some(Pieces::Pawn { .. }))) { Colors::White } else { Colors::Black };
+            let score = evaluate_fen(fen).unwrap();
+            assert!(if position.turn() == strong { score > KNOWN_WIN } else { score < -KNOWN_WIN }, "{}", fen);
+        }
+        for fen in [
+            // Stalemate
+            "4k3/4P3/4K3/8/8/8/8/8 b - - 0 1",
+            // The lone king keeps the opposition
+            "8/8/4k3/8/4K3/4P3/8/8 w - - 0 1",
+            // A rook pawn with the lone king in its corner
+            "k7/8/K7/P7/8/8/8/8 w - - 0 1",
+            // The lone king is inside the square of a rook pawn
+            "8/8/8/8/P3k3/8/8/K7 w - - 0 1"
+        ] {
+            assert_eq!(evaluate_fen(fen), Some(0), "{}", fen);
+        }
+    }
+
+    /// Checks the bitbase against the KPvK table of the tablebase generator,
+    /// which finds the results by searching to mate instead
+    #[test]
+    fn kpk_matches_the_generated_table() {
+        let mut tables = HashMap::new();
+        generate("KPvK", &mut tables).unwrap();
+        let table = &tables["KPvK"];
+        for pawn in 0o10..0o60 {
+            for white_king in (0..64).filter(|&tile| tile != pawn) {
+                for black_king in (0..64).filter(|&tile| tile != pawn && tile != white_king) {
+                    let mut board = initialise_board();
+                    for (tile, piece, color) in [(pawn, Pieces::Pawn { has_moved: true, en_passantable: false }, Colors::White), (white_king, Pieces::King { has_moved: true }, Colors::White), (black_king, Pieces::King { has_moved: true }, Colors::Black)] {
+                        board[tile].piece = Some(piece);
+                        board[tile].color = Some(color);
+                    }
+                    for turn in [Colors::White, Colors::Black] {
+                        let value = table.probe(&board, turn).unwrap();
+                        if value == ILLEGAL {
+                            continue;
+                        }
+                        let won = value != DRAW;
+                        assert_eq!(probe_kpk(white_king, black_king, pawn, Colors::White, turn), won, "{:?} to move, kings {} {} pawn {}", turn, white_king, black_king, pawn);
+                        // The same position with the colours and rows swapped
+                        let [white_king, black_king, pawn] = [white_king, black_king, pawn].map(|tile| tile ^ 0o70);
+                        assert_eq!(probe_kpk(white_king, black_king, pawn, Colors::Black, turn.opposite()), won);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,192 @@
+// Evaluation parameters
+//
+// Every weight of the handcrafted evaluation that can be tuned lives here,
+// so that the tuner can adjust them and write them back out. The weights
+// the engine plays with are in `eval_weights`, which the tuner overwrites
+// when given it as its output.
+//
+// Pairs of values are (middle game, end game). Piece square tables are laid
+// out as the board is indexed, a8 first and h1 last, from White's point of
+// view.
+
+use std::fmt::Write;
+
+use crate::Pieces;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvalParams {
+    // Material of each piece, indexed as `piece_index`, the king having none
+    pub piece_values : [[i32; 2]; 6],
+    // Middle game and end game piece square tables, indexed as `piece_index`
+    pub piece_square_tables : [[[i32; 64]; 2]; 6],
+
+    pub doubled_pawn : [i32; 2],
+    pub isolated_pawn : [i32; 2],
+    pub backward_pawn : [i32; 2],
+    // Indexed by rank from the pawn's own side, 0 being its back rank
+    pub connected_pawn : [i32; 8],
+    pub passed_pawn : [[i32; 8]; 2],
+    // End game bonus per tile of distance of each king from the tile in
+    // front of a passed pawn
+    pub enemy_king_distance : i32,
+    pub own_king_distance : i32,
+
+    // Bonus per move above a piece's typical move count
+    pub knight_mobility : [i32; 2],
+    pub bishop_mobility : [i32; 2],
+    pub rook_mobility : [i32; 2],
+    pub queen_mobility : [i32; 2],
+
+    // Middle game king safety
+    pub shield_pawn_close : i32,
+    pub shield_pawn_far : i32,
+    pub missing_shield_pawn : i32,
+    pub semi_open_file : i32,
+    pub open_file : i32,
+    // Penalty by the summed weight of the pieces attacking the king zone
+    pub attack_penalty : [i32; 16]
+}
+
+/// Index of a piece in the piece tables
+#[inline]
+pub fn piece_index(piece: Pieces) -> usize {
+    match piece {
+        Pieces::King { .. } => 0,
+        Pieces::Queen => 1,
+        Pieces::Rook { .. } => 2,
+        Pieces::Knight => 3,
+        Pieces::Bishop => 4,
+        Pieces::Pawn { .. } => 5
+    }
+}
+
+const PIECE_NAMES : [&str; 6] = ["king", "queen", "rook", "knight", "bishop", "pawn"];
+
+/// Writes an array of numbers as Rust source, eight to a line
+fn write_array(source: &mut String, values: &[i32], indent: usize) {
+    if values.len() <= 8 {
+        let values : Vec<String> = values.iter().map(|value| value.to_string()).collect();
+        let _ = write!(source, "[{}]", values.join(", "));
+        return;
+    }
+    source.push_str("[\n");
+    for (i, row) in values.chunks(8).enumerate() {
+        let row : Vec<String> = row.iter().map(|value| format!("{:4}", value)).collect();
+        let separator = if (i + 1) * 8 < values.len() { "," } else { "" };
+        let _ = writeln!(source, "{}{}{}", " ".repeat(indent + 4), row.join(","), separator);
+    }
+    let _ = write!(source, "{}]", " ".repeat(indent));
+}
+
+impl EvalParams {
+    /// Every tunable weight, in a fixed order
+    pub fn weights_mut(&mut self) -> Vec<&mut i32> {
+        let mut weights : Vec<&mut i32> = vec![];
+        // The king is on the board in every position, so it has no value to tune
+        weights.extend(self.piece_values.iter_mut().skip(1).flatten());
+        weights.extend(self.piece_square_tables.iter_mut().flatten().flatten());
+        weights.extend(self.doubled_pawn.iter_mut());
+        weights.extend(self.isolated_pawn.iter_mut());
+        weights.extend(self.backward_pawn.iter_mut());
+        weights.extend(self.connected_pawn.iter_mut());
+        weights.extend(self.passed_pawn.iter_mut().flatten());
+        weights.push(&mut self.enemy_king_distance);
+        weights.push(&mut self.own_king_distance);
+        weights.extend(self.knight_mobility.iter_mut());
+        weights.extend(self.bishop_mobility.iter_mut());
+        weights.extend(self.rook_mobility.iter_mut());
+        weights.extend(self.queen_mobility.iter_mut());
+        weights.push(&mut self.shield_pawn_close);
+        weights.push(&mut self.shield_pawn_far);
+        weights.push(&mut self.missing_shield_pawn);
+        weights.push(&mut self.semi_open_file);
+        weights.push(&mut self.open_file);
+        weights.extend(self.attack_penalty.iter_mut());
+        weights
+    }
+
+    /// Writes the parameters as the Rust source of `eval_weights`
+    pub fn to_rust_source(&self) -> String {
+        let mut source = String::from(
+            "// Evaluation weights\n\
+             //\n\
+             // The initial weights, set by hand. The tuner overwrites them with tuned ones\n\
+             // when given this file as its output, see `tuner`.\n\
+             \n\
+             use crate::eval_params::EvalParams;\n\
+             \n\
+             #[rustfmt::skip]\n\
+             pub const DEFAULT_PARAMS : EvalParams = EvalParams {\n"
+        );
+
+        source.push_str("    piece_values : [\n");
+        for (i, value) in self.piece_values.iter().enumerate() {
+            let separator = if i + 1 < self.piece_values.len() { "," } else { "" };
+            let _ = writeln!(source, "        [{}, {}]{} // {}", value[0], value[1], separator, PIECE_NAMES[i]);
+        }
+        source.push_str("    ],\n");
+
+        source.push_str("    piece_square_tables : [\n");
+        for (i, tables) in self.piece_square_tables.iter().enumerate() {
+            let _ = writeln!(source, "        // {}", PIECE_NAMES[i]);
+            source.push_str("        [\n");
+            for (phase, table) in tables.iter().enumerate() {
+                source.push_str("            ");
+                write_array(&mut source, table, 12);
+                source.push_str(if phase == 0 { ",\n" } else { "\n" });
+            }
+            source.push_str(if i + 1 < self.piece_square_tables.len() { "        ],\n" } else { "        ]\n" });
+        }
+        source.push_str("    ],\n");
+
+        let arrays : [(&str, &[i32]); 8] = [
+            ("doubled_pawn", &self.doubled_pawn),
+            ("isolated_pawn", &self.isolated_pawn),
+            ("backward_pawn", &self.backward_pawn),
+            ("connected_pawn", &self.connected_pawn),
+            ("knight_mobility", &self.knight_mobility),
+            ("bishop_mobility", &self.bishop_mobility),
+            ("rook_mobility", &self.rook_mobility),
+            ("queen_mobility", &self.queen_mobility)
+        ];
+        for (name, values) in arrays {
+            let _ = write!(source, "    {} : ", name);
+            write_array(&mut source, values, 4);
+            source.push_str(",\n");
+        }
+        source.push_str("    passed_pawn : [\n        ");
+        write_array(&mut source, &self.passed_pawn[0], 8);
+        source.push_str(",\n        ");
+        write_array(&mut source, &self.passed_pawn[1], 8);
+        source.push_str("\n    ],\n");
+
+        let values : [(&str, i32); 7] = [
+            ("enemy_king_distance", self.enemy_king_distance),
+            ("own_king_distance", self.own_king_distance),
+            ("shield_pawn_close", self.shield_pawn_close),
+            ("shield_pawn_far", self.shield_pawn_far),
+            ("missing_shield_pawn", self.missing_shield_pawn),
+            ("semi_open_file", self.semi_open_file),
+            ("open_file", self.open_file)
+        ];
+        for (name, value) in values {
+            let _ = writeln!(source, "    {} : {},", name, value);
+        }
+        source.push_str("    attack_penalty : ");
+        write_array(&mut source, &self.attack_penalty, 4);
+        source.push_str("\n};\n");
+
+        source
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eval_weights::DEFAULT_PARAMS;
+
+    #[test]
+    fn writes_the_weights_file_back_unchanged() {
+        let source = include_str!("eval_weights.rs");
+        assert_eq!(DEFAULT_PARAMS.to_rust_source(), source);
+    }
+}
@@ -0,0 +1,181 @@
+// Evaluation weights
+//
+// The initial weights, set by hand. The tuner overwrites them with tuned ones
+// when given this file as its output, see `tuner`.
+
+use crate::eval_params::EvalParams;
+
+#[rustfmt::skip]
+pub const DEFAULT_PARAMS : EvalParams = EvalParams {
+    piece_values : [
+        [0, 0], // king
+        [1025, 936], // queen
+        [477, 512], // rook
+        [337, 281], // knight
+        [365, 297], // bishop
+        [82, 94] // pawn
+    ],
+    piece_square_tables : [
+        // king
+        [
+            [
+                 -30, -40, -40, -50, -50, -40, -40, -30,
+                 -30, -40, -40, -50, -50, -40, -40, -30,
+                 -30, -40, -40, -50, -50, -40, -40, -30,
+                 -30, -40, -40, -50, -50, -40, -40, -30,
+                 -20, -30, -30, -40, -40, -30, -30, -20,
+                 -10, -20, -20, -20, -20, -20, -20, -10,
+                  20,  20,   0,   0,   0,   0,  20,  20,
+                  20,  30,  10,   0,   0,  10,  30,  20
+            ],
+            [
+                 -50, -40, -30, -20, -20, -30, -40, -50,
+                 -30, -20, -10,   0,   0, -10, -20, -30,
+                 -30, -10,  20,  30,  30,  20, -10, -30,
+                 -30, -10,  30,  40,  40,  30, -10, -30,
+                 -30, -10,  30,  40,  40,  30, -10, -30,
+                 -30, -10,  20,  30,  30,  20, -10, -30,
+                 -30, -30,   0,   0,   0,   0, -30, -30,
+                 -50, -30, -30, -30, -30, -30, -30, -50
+            ]
+        ],
+        // queen
+        [
+            [
+                 -20, -10, -10,  -5,  -5, -10, -10, -20,
+                 -10,   0,   0,   0,   0,   0,   0, -10,
+                 -10,   0,   5,   5,   5,   5,   0, -10,
+                  -5,   0,   5,   5,   5,   5,   0,  -5,
+                   0,   0,   5,   5,   5,   5,   0,  -5,
+                 -10,   5,   5,   5,   5,   5,   0, -10,
+                 -10,   0,   5,   0,   0,   0,   0, -10,
+                 -20, -10, -10,  -5,  -5, -10, -10, -20
+            ],
+            [
+                 -20, -10, -10,  -5,  -5, -10, -10, -20,
+                 -10,   0,   0,   0,   0,   0,   0, -10,
+                 -10,   0,   5,   5,   5,   5,   0, -10,
+                  -5,   0,   5,   5,   5,   5,   0,  -5,
+                   0,   0,   5,   5,   5,   5,   0,  -5,
+                 -10,   5,   5,   5,   5,   5,   0, -10,
+                 -10,   0,   5,   0,   0,   0,   0, -10,
+                 -20, -10, -10,  -5,  -5, -10, -10, -20
+            ]
+        ],
+        // rook
+        [
+            [
+                   0,   0,   0,   0,   0,   0,   0,   0,
+                   5,  10,  10,  10,  10,  10,  10,   5,
+                  -5,   0,   0,   0,   0,   0,   0,  -5,
+                  -5,   0,   0,   0,   0,   0,   0,  -5,
+                  -5,   0,   0,   0,   0,   0,   0,  -5,
+                  -5,   0,   0,   0,   0,   0,   0,  -5,
+                  -5,   0,   0,   0,   0,   0,   0,  -5,
+                   0,   0,   0,   5,   5,   0,   0,   0
+            ],
+            [
+                   0,   0,   0,   0,   0,   0,   0,   0,
+                   5,  10,  10,  10,  10,  10,  10,   5,
+                  -5,   0,   0,   0,   0,   0,   0,  -5,
+                  -5,   0,   0,   0,   0,   0,   0,  -5,
+                  -5,   0,   0,   0,   0,   0,   0,  -5,
+                  -5,   0,   0,   0,   0,   0,   0,  -5,
+                  -5,   0,   0,   0,   0,   0,   0,  -5,
+                   0,   0,   0,   5,   5,   0,   0,   0
+            ]
+        ],
+        // knight
+        [
+            [
+                 -50, -40, -30, -30, -30, -30, -40, -50,
+                 -40, -20,   0,   0,   0,   0, -20, -40,
+                 -30,   0,  10,  15,  15,  10,   0, -30,
+                 -30,   5,  15,  20,  20,  15,   5, -30,
+                 -30,   0,  15,  20,  20,  15,   0, -30,
+                 -30,   5,  10,  15,  15,  10,   5, -30,
+                 -40, -20,   0,   5,   5,   0, -20, -40,
+                 -50, -40, -30, -30, -30, -30, -40, -50
+            ],
+            [
+                 -50, -40, -30, -30, -30, -30, -40, -50,
+                 -40, -20,   0,   0,   0,   0, -20, -40,
+                 -30,   0,  10,  15,  15,  10,   0, -30,
+                 -30,   5,  15,  20,  20,  15,   5, -30,
+                 -30,   0,  15,  20,  20,  15,   0, -30,
+                 -30,   5,  10,  15,  15,  10,   5, -30,
+                 -40, -20,   0,   5,   5,   0, -20, -40,
+                 -50, -40, -30, -30, -30, -30, -40, -50
+            ]
+        ],
+        // bishop
+        [
+            [
+                 -20, -10, -10, -10, -10, -10, -10, -20,
+                 -10,   0,   0,   0,   0,   0,   0, -10,
+                 -10,   0,   5,  10,  10,   5,   0, -10,
+                 -10,   5,   5,  10,  10,   5,   5, -10,
+                 -10,   0,  10,  10,  10,  10,   0, -10,
+                 -10,  10,  10,  10,  10,  10,  10, -10,
+                 -10,   5,   0,   0,   0,   0,   5, -10,
+                 -20, -10, -10, -10, -10, -10, -10, -20
+            ],
+            [
+                 -20, -10, -10, -10, -10, -10, -10, -20,
+                 -10,   0,   0,   0,   0,   0,   0, -10,
+                 -10,   0,   5,  10,  10,   5,   0, -10,
+                 -10,   5,   5,  10,  10,   5,   5, -10,
+                 -10,   0,  10,  10,  10,  10,   0, -10,
+                 -10,  10,  10,  10,  10,  10,  10, -10,
+                 -10,   5,   0,   0,   0,   0,   5, -10,
+                 -20, -10, -10, -10, -10, -10, -10, -20
+            ]
+        ],
+        // pawn
+        [
+            [
+                   0,   0,   0,   0,   0,   0,   0,   0,
+                  50,  50,  50,  50,  50,  50,  50,  50,
+                  10,  10,  20,  30,  30,  20,  10,  10,
+                   5,   5,  10,  25,  25,  10,   5,   5,
+                   0,   0,   0,  20,  20,   0,   0,   0,
+                   5,  -5, -10,   0,   0, -10,  -5,   5,
+                   5,  10,  10, -20, -20,  10,  10,   5,
+                   0,   0,   0,   0,   0,   0,   0,   0
+            ],
+            [
+                   0,   0,   0,   0,   0,   0,   0,   0,
+                  50,  50,  50,  50,  50,  50,  50,  50,
+                  10,  10,  20,  30,  30,  20,  10,  10,
+                   5,   5,  10,  25,  25,  10,   5,   5,
+                   0,   0,   0,  20,  20,   0,   0,   0,
+                   5,  -5, -10,   0,   0, -10,  -5,   5,
+                   5,  10,  10, -20, -20,  10,  10,   5,
+                   0,   0,   0,   0,   0,   0,   0,   0
+            ]
+        ]
+    ],
+    doubled_pawn : [-11, -24],
+    isolated_pawn : [-9, -14],
+    backward_pawn : [-8, -11],
+    connected_pawn : [0, 4, 6, 10, 18, 30, 50, 0],
+    knight_mobility : [4, 4],
+    bishop_mobility : [5, 5],
+    rook_mobility : [2, 4],
+    queen_mobility : [1, 2],
+    passed_pawn : [
+        [0, 3, 6, 12, 25, 45, 70, 0],
+        [0, 8, 14, 28, 50, 85, 130, 0]
+    ],
+    enemy_king_distance : 5,
+    own_king_distance : 2,
+    shield_pawn_close : 12,
+    shield_pawn_far : 6,
+    missing_shield_pawn : -14,
+    semi_open_file : -12,
+    open_file : -18,
+    attack_penalty : [
+           0,   0,   4,  10,  18,  28,  40,  54,
+          70,  88, 108, 130, 154, 180, 208, 240
+    ]
+};
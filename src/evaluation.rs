@@ -0,0 +1,91 @@
+// Static evaluation of a board
+//
+// Scores are in centipawns from the point of view of the side to move.
+// Every piece is given a material value plus a bonus from its piece square
+// table, with separate middle game and end game values blended by how much
+// material is left on the board. Pawn structure, mobility and king safety
+// are scored separately, see `pawn_structure`, `mobility` and `king_safety`.
+// The weights of every term are in `eval_params`.
+
+use crate::eval_params::{piece_index, EvalParams};
+use crate::eval_weights::DEFAULT_PARAMS;
+use crate::king_safety::evaluate_king_safety;
+use crate::mobility::evaluate_mobility;
+use crate::pawn_structure::{evaluate_pawns, passed_pawn_king_proximity, PawnHashTable};
+use crate::{Colors, Pieces, Tile};
+
+// Game phase weight of each piece, a full board adds up to 24
+const KNIGHT_PHASE : i32 = 1;
+const BISHOP_PHASE : i32 = 1;
+const ROOK_PHASE : i32 = 2;
+const QUEEN_PHASE : i32 = 4;
+const TOTAL_PHASE : i32 = 24;
+
+/// Material value of a piece in the middle game, used for move ordering
+pub fn piece_value(piece: Pieces) -> i32 {
+    match piece {
+        Pieces::King { .. } => 20000,
+        _ => DEFAULT_PARAMS.piece_values[piece_index(piece)][0]
+    }
+}
+
+/// Weight of a piece towards the game phase
+fn phase_weight(piece: Option<Pieces>) -> i32 {
+    match piece {
+        Some(Pieces::Queen) => QUEEN_PHASE,
+        Some(Pieces::Rook { .. }) => ROOK_PHASE,
+        Some(Pieces::Knight) => KNIGHT_PHASE,
+        Some(Pieces::Bishop) => BISHOP_PHASE,
+        _ => 0
+    }
+}
+
+/// Scores the board in centipawns from the point of view of `turn`
+pub fn evaluate(board: &[Tile; 64], turn: Colors, pawn_table: &mut PawnHashTable) -> i32 {
+    evaluate_with_params(board, turn, &DEFAULT_PARAMS, Some(pawn_table))
+}
+
+/// Scores the board with the given weights. Cached pawn scores are only valid
+/// for the weights they were scored with, so the tuner passes no pawn table.
+pub fn evaluate_with_params(board: &[Tile; 64], turn: Colors, params: &EvalParams, pawn_table: Option<&mut PawnHashTable>) -> i32 {
+    let mut score : [i32; 2] = [0, 0];
+    let mut phase : i32 = 0;
+    let mut kings : [Option<usize>; 2] = [None, None];
+
+    for (index, tile) in board.iter().enumerate() {
+        let (piece, color) = match (tile.piece, tile.color) {
+            (Some(piece), Some(color)) => (piece, color),
+            _ => continue
+        };
+        // Black reads the tables upside down
+        let (table_index, sign) = match color {
+            Colors::White => (index, 1),
+            Colors::Black => (index ^ 0o70, -1)
+        };
+        let piece = piece_index(piece);
+        score[0] += sign * (params.piece_values[piece][0] + params.piece_square_tables[piece][0][table_index]);
+        score[1] += sign * (params.piece_values[piece][1] + params.piece_square_tables[piece][1][table_index]);
+        phase += phase_weight(tile.piece);
+        if piece == piece_index(Pieces::King { has_moved: false }) {
+            kings[color as usize] = Some(index);
+        }
+    }
+
+    let (pawn_score, passed) = evaluate_pawns(board, params, pawn_table);
+    score[0] += pawn_score[0];
+    score[1] += pawn_score[1] + passed_pawn_king_proximity(board, params, passed, kings);
+
+    let (mobility_score, king_attacks) = evaluate_mobility(board, params, kings);
+    let king_safety_score = evaluate_king_safety(board, params, kings, &king_attacks);
+    score[0] += mobility_score[0] + king_safety_score[0];
+    score[1] += mobility_score[1] + king_safety_score[1];
+
+    // Blend the two scores by the amount of material left
+    let phase = phase.min(TOTAL_PHASE);
+    let blended = (score[0] * phase + score[1] * (TOTAL_PHASE - phase)) / TOTAL_PHASE;
+
+    match turn {
+        Colors::White => blended,
+        Colors::Black => -blended
+    }
+}
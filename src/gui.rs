@@ -0,0 +1,433 @@
+// Board window
+//
+// Draws the board with piston and plays moves picked with the mouse. A game
+// loaded from PGN can be stepped through with the arrow keys, the analysis
+// panel beside the board is toggled with A, and S saves the game as PGN.
+
+extern crate glutin_window;
+extern crate graphics;
+extern crate opengl_graphics;
+extern crate piston;
+
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use opengl_graphics::{GlGraphics, GlyphCache, OpenGL, Texture, TextureSettings};
+
+use glutin_window::GlutinWindow as Window;
+
+use piston::input::{RenderArgs, RenderEvent};
+use piston::{MouseCursorEvent, Button, MouseButton, PressEvent, Key};
+use piston::event_loop::{EventSettings, Events};
+use piston::window::WindowSettings;
+
+use graphics::rectangle::square;
+use graphics::Image;
+
+use chess_engine::analysis::Analysis;
+use chess_engine::pgn::{PgnGame, RecordedMove, ReplayedGame};
+use chess_engine::search::format_score;
+use chess_engine::transposition_table::TranspositionTable;
+use chess_engine::pgn;
+use chess_engine::san::move_to_san;
+use chess_engine::position::Position;
+use chess_engine::{get_array_index, get_render_coords, Colors, Move, Pieces};
+
+// Width of the analysis panel drawn to the right of the board
+const PANEL_WIDTH : u32 = 300;
+// Number of ranked lines shown in the analysis panel
+const ANALYSIS_LINES : usize = 3;
+const ANALYSIS_HASH_MEGABYTES : usize = 16;
+
+/// Opens the board window on the position, or on the last position of a
+/// game loaded from PGN when there is one, which can be stepped back through
+/// with the arrow keys
+pub fn initialise_window(position: Position, loaded_game: Option<(PgnGame, ReplayedGame)>) {
+    let opengl = OpenGL::V3_2;
+
+    let mut window: Window = WindowSettings::new("Chess", [800 + PANEL_WIDTH, 800])
+        .graphics_api(opengl)
+        .build()
+        .unwrap();
+
+    let texture_settings: TextureSettings = TextureSettings::new();
+    let piece_images: [Texture; 12] = [
+        Texture::from_path(Path::new("./resources/WhiteKing.png"), &texture_settings).unwrap(),
+        Texture::from_path(Path::new("./resources/WhiteQueen.png"), &texture_settings).unwrap(),
+        Texture::from_path(Path::new("./resources/WhiteBishop.png"), &texture_settings).unwrap(),
+        Texture::from_path(Path::new("./resources/WhiteKnight.png"), &texture_settings).unwrap(),
+        Texture::from_path(Path::new("./resources/WhiteRook.png"), &texture_settings).unwrap(),
+        Texture::from_path(Path::new("./resources/WhitePawn.png"), &texture_settings).unwrap(),
+        Texture::from_path(Path::new("./resources/BlackKing.png"), &texture_settings).unwrap(),
+        Texture::from_path(Path::new("./resources/BlackQueen.png"), &texture_settings).unwrap(),
+        Texture::from_path(Path::new("./resources/BlackBishop.png"), &texture_settings).unwrap(),
+        Texture::from_path(Path::new("./resources/BlackKnight.png"), &texture_settings).unwrap(),
+        Texture::from_path(Path::new("./resources/BlackRook.png"), &texture_settings).unwrap(),
+        Texture::from_path(Path::new("./resources/BlackPawn.png"), &texture_settings).unwrap()
+    ];
+    let mut image_locations : [[Image; 8]; 8] = [[Image::new(); 8]; 8];
+    // Creates the places where images can be drawn
+    for (x, column) in image_locations.iter_mut().enumerate() {
+        for (y, location) in column.iter_mut().enumerate() {
+            *location = Image::new()
+                    .rect(square((x * 200) as f64, (y * 200) as f64, 200f64));
+        }
+    }
+    
+    let glyphs : GlyphCache = GlyphCache::new(Path::new("./resources/DejaVuSansMono.ttf"), (), texture_settings).unwrap();
+
+    let started = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs());
+    let (history, moves, tags, game_title, result) = match loaded_game {
+        Some((game, replayed)) => {
+            let title = format!("{} - {}", game.tag("White").unwrap_or("?"), game.tag("Black").unwrap_or("?"));
+            let moves = replayed.moves.iter().zip(game.moves.iter()).map(|(&mv, pgn_move)| RecordedMove {
+                mv,
+                comment : pgn_move.comment.clone(),
+                elapsed : None,
+                eval : None
+            }).collect();
+            (replayed.positions, moves, game.tags, Some(title), game.result)
+        },
+        None => {
+            let tags = [("Event", "Casual game"), ("Site", "?"), ("Round", "-"), ("White", "?"), ("Black", "?")].iter()
+                .map(|&(name, value)| (name.to_string(), value.to_string()))
+                .chain([("Date".to_string(), pgn::date_tag())])
+                .collect();
+            (vec![position], vec![], tags, None, "*".to_string())
+        }
+    };
+
+    let mut app : App = App {
+        gl : GlGraphics::new(opengl),
+        piece_images,
+        image_locations,
+        glyphs,
+        selected_tile : None,
+        position,
+        analysis : None,
+        analysis_table : Arc::new(TranspositionTable::new(ANALYSIS_HASH_MEGABYTES)),
+        ply : 0,
+        history,
+        moves,
+        result,
+        position_reached : Instant::now(),
+        tags,
+        game_title,
+        pgn_path : format!("game_{}.pgn", started)
+    };
+    app.go_to_ply(app.history.len() - 1);
+
+    let mut mouse_position : [f64; 2] = [0f64, 0f64];
+    let mut events = Events::new(EventSettings::new());
+    while let Some(e) = events.next(&mut window) {
+        if let Some(args) = e.render_args() {
+            app.render(&args, &mouse_position);
+        }
+
+        if let Some(mouse_rel) = e.mouse_cursor_args() {
+            mouse_position = mouse_rel;
+        }
+
+        if let Some(button) = e.press_args() {
+            if let Button::Mouse(MouseButton::Left) = button {
+                let x_index = (mouse_position[0] / 100f64).floor();
+                let y_index = (mouse_position[1] / 100f64).floor();
+                // Clicks on the analysis panel are not on the board
+                if x_index < 8f64 {
+                    app.update_selected_tile(x_index, y_index);
+                }
+            }
+            match button {
+                Button::Mouse(MouseButton::Left) => {
+
+                },
+                Button::Keyboard(Key::Escape) => {
+                    app.clear_selected_tile();
+                },
+                Button::Keyboard(Key::A) => {
+                    app.toggle_analysis();
+                },
+                Button::Keyboard(Key::S) => {
+                    app.save_game();
+                },
+                Button::Keyboard(Key::Left) => {
+                    app.go_to_ply(app.ply.saturating_sub(1));
+                },
+                Button::Keyboard(Key::Right) => {
+                    app.go_to_ply(app.ply + 1);
+                },
+                _ => ()
+            }
+        }
+    }
+}
+
+struct App {
+    gl : GlGraphics,
+    piece_images : [Texture; 12],
+    image_locations : [[Image; 8]; 8],
+    glyphs : GlyphCache<'static>,
+    selected_tile : Option<usize>,
+    position : Position,
+    analysis : Option<Analysis>,
+    analysis_table : Arc<TranspositionTable>,
+    // Every position of the game so far, and which of them is on the board
+    history : Vec<Position>,
+    ply : usize,
+    // The moves between the positions, how the last one ended the game, and
+    // when the one on the board was reached
+    moves : Vec<RecordedMove>,
+    result : String,
+    position_reached : Instant,
+    // Tags the game is saved with, and its players when loaded from PGN
+    tags : Vec<(String, String)>,
+    game_title : Option<String>,
+    pgn_path : String
+}
+impl App {
+    #[inline]
+    fn render(&mut self, args: &RenderArgs, mouse_position : &[f64; 2]) {
+        use graphics::*;
+
+        let board = self.position.board();
+        let turn = self.position.turn();
+        let legal_targets = self.selected_tile.map_or(vec![], |tile| self.legal_targets(tile));
+
+        // Color constants
+        const BLACK  : [f32; 4] = [0.484f32, 0.582f32, 0.363f32, 1.00f32]; // (actually green)
+        const WHITE  : [f32; 4] = [0.929f32, 0.929f32, 0.832f32, 1.00f32]; // (actually cream)
+        const YELLOW : [f32; 4] = [0.871f32, 0.896f32, 0.375f32, 1.00f32];
+        const ORANGE : [f32; 4] = [0.770f32, 0.602f32, 0.426f32, 0.75f32];
+        const PALE_YELLOW : [f32; 4] = [0.871f32, 0.896f32, 0.375f32, 0.50f32];
+
+        // Draw all needed elements
+        self.gl.draw(args.viewport(), |c, gl| {
+            // Fills the board black
+            clear([0f32, 0f32, 0f32, 1f32], gl);
+
+            // Draws the board squares
+            let mut square : [f64; 4];
+            for x in 0..8 {
+                for y in 0..8 {
+                    square = rectangle::square((x * 100) as f64, (y * 100) as f64, 100f64);
+                    if y % 2 == x % 2 {
+                        // Adds in the black squares
+                        rectangle(WHITE, square, c.transform, gl);
+                        continue;
+                    } else {
+                        rectangle(BLACK, square, c.transform, gl);
+                    }
+                }
+            }
+
+            // Highlights the selected tile
+            if let Some(selected_tile) = self.selected_tile {
+                // Get coordinates of the tile
+                let render_coords = get_render_coords(selected_tile);
+                let x_position : f64 = (render_coords[0] * 100) as f64;
+                let y_position : f64 = (render_coords[1] * 100) as f64;
+                // Draws the square selected as yellow
+                rectangle(YELLOW, rectangle::square(x_position, y_position, 100f64), c.transform, gl);
+            }
+
+            // Highlights the square under the mouse cursor
+            if mouse_position[0] < 800f64 {
+                rectangle(PALE_YELLOW, rectangle::square((mouse_position[0] / 100f64).floor() * 100f64, (mouse_position[1] / 100f64).floor() * 100f64, 100f64), c.transform, gl);
+            }
+
+            if let Some(selected_tile) = self.selected_tile {
+                if Some(turn) == board[selected_tile].color {
+                    // Highlight the legal moves available
+                    let mut draw_position: [usize; 2];
+                    for i in legal_targets {
+                        draw_position = get_render_coords(i);
+                        rectangle(ORANGE, rectangle::square((draw_position[0] * 100) as f64, (draw_position[1] * 100) as f64, 100f64), c.transform, gl);
+                    }
+                }
+            }
+
+            let mut image_index: Option<usize>;
+            let draw_state : DrawState = DrawState::new_alpha();
+            // Creates a matrix transformation to scale down the images
+            let piece_transform = c.transform.scale(0.5, 0.5);
+            for x in 0..8 {
+                for y in 0..8 {
+                    // Draw the piece images
+                    image_index = board[get_array_index(x, y)].get_piece_image_index();
+                    if let Some(image_index) = image_index {
+                        self.image_locations[x][y].draw(&self.piece_images[image_index], &draw_state, piece_transform, gl)
+                    }
+                }
+            }
+
+            // Draws the analysis panel to the right of the board
+            const TEXT_SIZE : u32 = 18;
+            let text = Text::new_color(WHITE, TEXT_SIZE);
+            let mut panel_lines : Vec<String> = vec![];
+            if let Some(title) = &self.game_title {
+                panel_lines.push(title.clone());
+                panel_lines.push(format!("Ply {} of {}", self.ply, self.history.len() - 1));
+                panel_lines.push(String::new());
+            }
+            match &self.analysis {
+                None => panel_lines.push("Press A to analyse".to_string()),
+                Some(analysis) => match analysis.latest() {
+                    None => panel_lines.push("Analysing...".to_string()),
+                    Some(result) => {
+                        panel_lines.push(format!("Depth {}", result.depth));
+                        panel_lines.push(String::new());
+                        // Ranked best first, each score above its line
+                        for (rank, line) in result.lines.iter().enumerate() {
+                            panel_lines.push(format!("{}. {}", rank + 1, format_score(line.score)));
+                            // Each move is written for the position it is played from
+                            let mut position = self.position;
+                            let moves : Vec<String> = line.pv.iter().take(4).map(|&mv| {
+                                let san = move_to_san(&position, mv);
+                                position.play_move(mv);
+                                san
+                            }).collect();
+                            panel_lines.push(format!("   {}", moves.join(" ")));
+                        }
+                    }
+                }
+            }
+            for (i, line) in panel_lines.iter().enumerate() {
+                let transform = c.transform.trans(820f64, 40f64 + 28f64 * i as f64);
+                let _ = text.draw(line, &mut self.glyphs, &c.draw_state, transform, gl);
+            }
+        });
+    }
+
+    /// Starts analysing the current board, or stops if already analysing
+    fn toggle_analysis(&mut self) {
+        match self.analysis {
+            Some(_) => self.analysis = None,
+            None => self.restart_analysis()
+        }
+    }
+
+    /// Analyses the board afresh, for use after a move has been played
+    fn restart_analysis(&mut self) {
+        // The old analysis has to stop before the new one starts
+        self.analysis = None;
+        self.analysis = Some(Analysis::start(self.position, ANALYSIS_LINES, Arc::clone(&self.analysis_table)));
+    }
+
+    /// Shows the position after the given number of plies, if the game has
+    /// that many
+    fn go_to_ply(&mut self, ply : usize) {
+        if let Some(&position) = self.history.get(ply) {
+            self.ply = ply;
+            self.position = position;
+            self.position_reached = Instant::now();
+            self.clear_selected_tile();
+            if self.analysis.is_some() {
+                self.restart_analysis();
+            }
+        }
+    }
+
+    #[inline]
+    // Rework this function
+    fn update_selected_tile(&mut self, x_index : f64, y_index : f64) {
+        let new: usize = get_array_index(x_index as usize, y_index as usize);
+        let board = self.position.board();
+        // New
+        if let Some(current) = self.selected_tile {
+            if board[current].piece.is_some() {
+                if Some(self.position.turn()) == board[current].color {
+                    if self.legal_targets(current).contains(&new) {
+                        self.record_move(current, new);
+                    }
+                    self.clear_selected_tile()
+                }
+                else {
+                    self.selected_tile = Some(new);
+                }
+            } else {
+                self.selected_tile = Some(new);
+            }
+        } else {
+            self.selected_tile = Some(new);
+        }
+    }
+
+    /// The tiles the piece on a tile can legally move to, which leave its
+    /// king safe
+    fn legal_targets(&self, tile : usize) -> Vec<usize> {
+        let mut targets : Vec<usize> = self.position.legal_moves().iter().filter(|mv| mv.from == tile).map(|mv| mv.to).collect();
+        // Each promotion is a move of its own to the same tile
+        targets.dedup();
+        targets
+    }
+
+    /// Plays a move on the board and records it in the game, saving the game
+    /// when the move ends it. Pawns reaching the final row become queens.
+    /// Moves that are not legal are ignored.
+    fn record_move(&mut self, current : usize, new : usize) {
+        let promotion = match self.position.board()[current].piece {
+            Some(Pieces::Pawn { .. }) if new >> 3 == 0 || new >> 3 == 7 => Some(Pieces::Queen),
+            _ => None
+        };
+        let mv = Move { from: current, to: new, promotion };
+        if !self.position.legal_moves().contains(&mv) {
+            return;
+        }
+        self.record_eval();
+        // A move played part way through the game starts a new line
+        self.history.truncate(self.ply + 1);
+        self.moves.truncate(self.ply);
+        self.moves.push(RecordedMove {
+            mv,
+            comment : None,
+            elapsed : Some(self.position_reached.elapsed()),
+            eval : None
+        });
+
+        self.position.play_move(mv);
+        self.history.push(self.position);
+        self.ply += 1;
+        self.position_reached = Instant::now();
+        if self.analysis.is_some() {
+            self.restart_analysis();
+        }
+
+        self.result = pgn::game_result(&self.history).unwrap_or("*").to_string();
+        if self.result != "*" {
+            self.save_game();
+        }
+    }
+
+    /// Gives the move that reached the board the latest analysis of it, for
+    /// White
+    fn record_eval(&mut self) {
+        let score = self.analysis.as_ref().and_then(|analysis| analysis.latest()).and_then(|result| result.lines.first().map(|line| line.score));
+        if let (Some(score), Some(last)) = (score, self.ply.checked_sub(1).and_then(|ply| self.moves.get_mut(ply))) {
+            last.eval = Some(if self.position.turn() == Colors::White { score } else { -score });
+        }
+    }
+
+    /// Writes the game up to the position on the board to its PGN file, with
+    /// its result when that position ends it
+    fn save_game(&mut self) {
+        self.record_eval();
+        let result = if self.ply + 1 == self.history.len() {
+            self.result.as_str()
+        } else {
+            pgn::game_result(&self.history[..=self.ply]).unwrap_or("*")
+        };
+        let text = pgn::write_pgn(&self.tags, &self.history[0], &self.moves[..self.ply], result);
+        match fs::write(&self.pgn_path, text) {
+            Ok(()) => println!("saved the game to {}", self.pgn_path),
+            Err(error) => eprintln!("can not write {}: {}", self.pgn_path, error)
+        }
+    }
+
+    #[inline]
+    fn clear_selected_tile(&mut self) {
+        self.selected_tile = None;
+    }
+
+}
\ No newline at end of file
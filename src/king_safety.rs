@@ -0,0 +1,133 @@
+// King safety evaluation
+//
+// A king is safer behind an unbroken shield of its own pawns, and in danger
+// on open files and when several enemy pieces attack the tiles around it.
+// King safety matters while there is material left to attack with, so these
+// terms only score in the middle game.
+//
+// Scores are (middle game, end game) pairs from White's point of view.
+
+use crate::eval_params::EvalParams;
+use crate::mobility::KingAttacks;
+use crate::{get_array_index, get_render_coords, Colors, Pieces, Tile};
+
+/// Scores the safety of one king from its own point of view
+fn score_king(board: &[Tile; 64], params: &EvalParams, king: usize, color: Colors, attacks: &KingAttacks) -> i32 {
+    let [king_x, king_y] = get_render_coords(king);
+    let mut score : i32 = 0;
+
+    let is_pawn = |x: usize, y: usize, owner: Colors| {
+        let tile = board[get_array_index(x, y)];
+        tile.color == Some(owner) && matches!(tile.piece, Some(Pieces::Pawn { .. }))
+    };
+    // Rows in front of the king, nearest first
+    let rows_ahead : Vec<usize> = match color {
+        Colors::White => (0..king_y).rev().collect(),
+        Colors::Black => (king_y + 1..8).collect()
+    };
+
+    // The shield is only useful while the king is still near its back rank
+    let back_rank_distance = match color {
+        Colors::White => 7 - king_y,
+        Colors::Black => king_y
+    };
+
+    for file in king_x.saturating_sub(1)..=(king_x + 1).min(7) {
+        if back_rank_distance <= 1 {
+            if rows_ahead.first().is_some_and(|&y| is_pawn(file, y, color)) {
+                score += params.shield_pawn_close;
+            } else if rows_ahead.get(1).is_some_and(|&y| is_pawn(file, y, color)) {
+                score += params.shield_pawn_far;
+            } else {
+                score += params.missing_shield_pawn;
+            }
+        }
+
+        let own_pawn = (0..8).any(|y| is_pawn(file, y, color));
+        let enemy_pawn = (0..8).any(|y| is_pawn(file, y, color.opposite()));
+        if !own_pawn {
+            score += if enemy_pawn { params.semi_open_file } else { params.open_file };
+        }
+    }
+
+    // A lone attacker is rarely dangerous, so one attacker scores nothing
+    let attacker = color.opposite() as usize;
+    if attacks.attackers[attacker] >= 2 {
+        let weight = attacks.weight[attacker].clamp(0, params.attack_penalty.len() as i32 - 1);
+        score -= params.attack_penalty[weight as usize];
+    }
+
+    score
+}
+
+/// Scores both kings, returning the (middle game, end game) score
+pub fn evaluate_king_safety(board: &[Tile; 64], params: &EvalParams, kings: [Option<usize>; 2], attacks: &KingAttacks) -> [i32; 2] {
+    let mut score : i32 = 0;
+    if let Some(king) = kings[Colors::White as usize] {
+        score += score_king(board, params, king, Colors::White, attacks);
+    }
+    if let Some(king) = kings[Colors::Black as usize] {
+        score -= score_king(board, params, king, Colors::Black, attacks);
+    }
+    [score, 0]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval_weights::DEFAULT_PARAMS;
+    use crate::position::Position;
+
+    fn params() -> EvalParams {
+        let mut params = DEFAULT_PARAMS;
+        params.shield_pawn_close = 10;
+        params.shield_pawn_far = 5;
+        params.missing_shield_pawn = -20;
+        params.semi_open_file = -7;
+        params.open_file = -13;
+        params.attack_penalty = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15];
+        params
+    }
+
+    /// Safety of the white king on g1 or g3
+    fn white_king(fen: &str, attacks: &KingAttacks) -> i32 {
+        let board = *Position::from_fen(fen).unwrap().board();
+        let king = crate::find_king(&board, Colors::White).unwrap();
+        score_king(&board, &params(), king, Colors::White, attacks)
+    }
+
+    #[test]
+    fn scores_the_pawn_shield() {
+        let no_attacks = KingAttacks::default();
+        assert_eq!(white_king("6k1/8/8/8/8/8/5PPP/6K1 w - - 0 1", &no_attacks), 30);
+        assert_eq!(white_king("6k1/8/8/8/8/6P1/5P1P/6K1 w - - 0 1", &no_attacks), 25);
+        // A missing pawn leaves a hole and an open file, half open with an
+        // enemy pawn on it
+        assert_eq!(white_king("6k1/8/8/8/8/8/5PP1/6K1 w - - 0 1", &no_attacks), 20 - 20 - 13);
+        assert_eq!(white_king("6k1/7p/8/8/8/8/5PP1/6K1 w - - 0 1", &no_attacks), 20 - 20 - 7);
+        // Away from its back rank the king has no shield, only open files
+        assert_eq!(white_king("6k1/8/8/8/8/6K1/5PP1/8 w - - 0 1", &no_attacks), -13);
+    }
+
+    #[test]
+    fn scores_attacks_on_the_king_zone() {
+        let fen = "6k1/8/8/8/8/8/5PPP/6K1 w - - 0 1";
+        // One attacker is ignored, two or more cost the penalty of their weight
+        assert_eq!(white_king(fen, &KingAttacks { attackers : [0, 1], weight : [0, 5] }), 30);
+        assert_eq!(white_king(fen, &KingAttacks { attackers : [0, 2], weight : [0, 7] }), 30 - 7);
+        assert_eq!(white_king(fen, &KingAttacks { attackers : [0, 4], weight : [0, 40] }), 30 - 15);
+        // Own pieces near the enemy king do not count against this one
+        assert_eq!(white_king(fen, &KingAttacks { attackers : [3, 0], weight : [9, 0] }), 30);
+    }
+
+    #[test]
+    fn scores_both_kings_in_the_middle_game_only() {
+        let board = *Position::default().board();
+        let kings = [crate::find_king(&board, Colors::White), crate::find_king(&board, Colors::Black)];
+        assert_eq!(evaluate_king_safety(&board, &params(), kings, &KingAttacks::default()), [0, 0]);
+        let board = *Position::from_fen("6k1/8/8/8/8/8/5PPP/6K1 w - - 0 1").unwrap().board();
+        let kings = [crate::find_king(&board, Colors::White), crate::find_king(&board, Colors::Black)];
+        // Black's king has no shield, and White's pawns on its files
+        assert_eq!(evaluate_king_safety(&board, &params(), kings, &KingAttacks::default()), [30 - (-60 - 21), 0]);
+    }
+}
@@ -0,0 +1,962 @@
+// Chess engine library
+//
+// The board, move generation, FEN reading and writing, search and
+// evaluation of the engine, for the board window and command line of this
+// package and for other tools that depend on the engine directly.
+//
+// A board is an array of 64 tiles, from a8 to h1, and a `position::Position`
+// holds one with the side to move and the rest of the state of the game. It
+// reads and writes FEN, lists its legal moves and plays them, keeping its
+// castling rights, en passant square and hash as it goes, and the move
+// generation of `movegen` and the search work on it. The functions here work
+// on bare boards with the side to move beside them, reading castling and en
+// passant from the flags of the pieces, for the window, SAN and the
+// tablebases. The `search` module finds the best move of a position and
+// `evaluation` scores it without searching.
+
+use std::cmp;
+
+use position::CastlingRights;
+use square::Square;
+
+pub mod analysis;
+pub mod bench;
+pub mod book_builder;
+pub mod datagen;
+pub mod endgame;
+mod eval_params;
+mod eval_weights;
+pub mod evaluation;
+mod king_safety;
+pub mod mate_search;
+mod mobility;
+pub mod movegen;
+pub mod nnue;
+mod pawn_structure;
+pub mod perft;
+pub mod pgn;
+pub mod polyglot;
+pub mod polyglot_keys;
+pub mod position;
+pub mod san;
+pub mod search;
+pub mod square;
+pub mod syzygy;
+pub mod tablebase;
+pub mod terminal;
+pub mod text_board;
+pub mod time_management;
+pub mod transposition_table;
+pub mod tuner;
+pub mod uci;
+pub mod zobrist;
+
+pub const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+#[inline]
+pub fn initialise_board() -> [Tile; 64] {
+    let mut board: [Tile; 64] =
+        [
+            Tile {
+                color: None,
+                piece: None,
+                tiles_left: 0,
+                tiles_right: 0,
+                tiles_down: 0,
+                tiles_up: 0
+            }
+            ; 64
+        ];
+    for x in 0..8 {
+        for y in 0..8 {
+            board[get_array_index(x, y)].tiles_left = x;
+            board[get_array_index(x, y)].tiles_right = 7 - x;
+            board[get_array_index(x, y)].tiles_down = 7 - y;
+            board[get_array_index(x, y)].tiles_up = y;
+        }
+    }
+    board
+}
+
+#[inline]
+pub fn read_fen_string(string : &str) -> [Tile; 64] {
+    let white_piece_codes: [u8; 6] = [75, 81, 82, 78, 66, 80]; // ascii codes for "KQRNBP"
+    let black_piece_codes: [u8; 6] = [107, 113, 114, 110, 98, 112]; // ascii codes for "kqrnbp"
+    let mut board : [Tile; 64] = initialise_board();
+    let mut index : usize = 0;
+    let characters: &[u8] = string.as_bytes();
+    for &character in characters {
+        // Only the piece placement field is read, anything after it is game state
+        if character == 32 {
+            break;
+        }
+        // If character is a number
+        if (48..=57).contains(&character) {
+            // In ASCII, 48 is 0, so use a -48 offset
+            // In ASCII, 57 is 9, which will never be used
+            index += (character - 48u8) as usize;
+            continue;
+        } else if character == 47 {
+            continue;
+        } else if index >= 64 {
+            // Pieces past the last tile have nowhere to go
+            break;
+        } else if white_piece_codes.contains(&character) {
+            board[index].color = Some(Colors::White);
+
+            // Compares the character utf-8 code to the correct piece
+            match character {
+                75 => board[index].piece = Some(Pieces::King {has_moved : false}),
+                81 => board[index].piece = Some(Pieces::Queen),
+                82 => board[index].piece = Some(Pieces::Rook {has_moved : false}),
+                78 => board[index].piece = Some(Pieces::Knight),
+                66 => board[index].piece = Some(Pieces::Bishop),
+                80 => board[index].piece = Some(Pieces::Pawn {has_moved : false, en_passantable : false}),
+                _ => () // This will never be reached
+            }
+
+            index += 1;
+        } else if black_piece_codes.contains(&character) {
+            board[index].color = Some(Colors::Black);
+
+            // Compares the character utf-8 code to the correct piece
+            match character {
+                107 => board[index].piece = Some(Pieces::King {has_moved : false}),
+                113 => board[index].piece = Some(Pieces::Queen),
+                114 => board[index].piece = Some(Pieces::Rook {has_moved : false}),
+                110 => board[index].piece = Some(Pieces::Knight),
+                98  => board[index].piece = Some(Pieces::Bishop),
+                112 => board[index].piece = Some(Pieces::Pawn {has_moved : false, en_passantable : false}),
+                _ => () // This will never be reached
+            }
+
+            index += 1;
+        }
+    }
+
+    // Pawns away from their starting rank can no longer double move
+    for (index, tile) in board.iter_mut().enumerate() {
+        let starting_row = match tile.color {
+            Some(Colors::White) => 6,
+            Some(Colors::Black) => 1,
+            None => continue
+        };
+        if let Some(Pieces::Pawn { .. }) = tile.piece {
+            if index >> 3 != starting_row {
+                tile.piece = Some(Pieces::Pawn { has_moved: true, en_passantable: false });
+            }
+        }
+    }
+
+    board
+}
+
+/// Reads a complete FEN string, including the side to move, castling rights
+/// and en passant square. Castling rights are stored as the `has_moved` flags
+/// of the kings and rooks, and the en passant square as the `en_passantable`
+/// flag of the pawn that just double moved.
+pub fn read_full_fen_string(string : &str) -> ([Tile; 64], Colors) {
+    let mut board: [Tile; 64] = read_fen_string(string);
+    let mut fields = string.split_whitespace().skip(1);
+
+    let turn = match fields.next() {
+        Some("b") => Colors::Black,
+        _ => Colors::White
+    };
+
+    // Every king and rook starts as moved, then castling rights restore them
+    for tile in board.iter_mut() {
+        match tile.piece {
+            Some(Pieces::King { .. }) => tile.piece = Some(Pieces::King { has_moved: true }),
+            Some(Pieces::Rook { .. }) => tile.piece = Some(Pieces::Rook { has_moved: true }),
+            _ => ()
+        }
+    }
+    let castling = fields.next().unwrap_or("-");
+    // (right, colour, king index, rook index)
+    let rights: [(char, Colors, usize, usize); 4] = [
+        ('K', Colors::White, 0o74, 0o77),
+        ('Q', Colors::White, 0o74, 0o70),
+        ('k', Colors::Black, 0o04, 0o07),
+        ('q', Colors::Black, 0o04, 0o00)
+    ];
+    for (right, color, king, rook) in rights {
+        if castling.contains(right)
+            && board[king].color == Some(color)
+            && matches!(board[king].piece, Some(Pieces::King { .. }))
+            && board[rook].color == Some(color)
+            && matches!(board[rook].piece, Some(Pieces::Rook { .. })) {
+            board[king].piece = Some(Pieces::King { has_moved: false });
+            board[rook].piece = Some(Pieces::Rook { has_moved: false });
+        }
+    }
+
+    // The pawn that can be taken sits one row past the en passant square
+    if let Some(target) = fields.next().and_then(parse_square_name) {
+        let pawn_index = match target >> 3 {
+            5 => Some(target - 0o10),
+            2 => Some(target + 0o10),
+            _ => None
+        };
+        if let Some(pawn_index) = pawn_index {
+            if let Some(Pieces::Pawn { .. }) = board[pawn_index].piece {
+                board[pawn_index].piece = Some(Pieces::Pawn { has_moved: true, en_passantable: true });
+            }
+        }
+    }
+
+    (board, turn)
+}
+
+/// Writes the piece placement field of FEN for the board
+pub fn write_fen_placement(board: &[Tile; 64]) -> String {
+    let mut fen = String::new();
+    for y in 0..8 {
+        let mut empty = 0;
+        for x in 0..8 {
+            let tile = board[get_array_index(x, y)];
+            let (piece, color) = match (tile.piece, tile.color) {
+                (Some(piece), Some(color)) => (piece, color),
+                _ => {
+                    empty += 1;
+                    continue;
+                }
+            };
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+                empty = 0;
+            }
+            let code = match piece {
+                Pieces::King { .. } => 'k',
+                Pieces::Queen => 'q',
+                Pieces::Rook { .. } => 'r',
+                Pieces::Knight => 'n',
+                Pieces::Bishop => 'b',
+                Pieces::Pawn { .. } => 'p'
+            };
+            fen.push(if color == Colors::White { code.to_ascii_uppercase() } else { code });
+        }
+        if empty > 0 {
+            fen.push_str(&empty.to_string());
+        }
+        if y < 7 {
+            fen.push('/');
+        }
+    }
+    fen
+}
+
+/// Writes a complete FEN string for the board, reading the castling rights
+/// and en passant square back out of the `has_moved` and `en_passantable`
+/// flags. The move counters are not kept on the board, so they are given.
+pub fn write_fen_string(board: &[Tile; 64], turn: Colors, halfmove_clock: u32, fullmove_number: u32) -> String {
+    let mut fen = write_fen_placement(board);
+    fen.push_str(if turn == Colors::White { " w " } else { " b " });
+
+    fen.push_str(&CastlingRights::from_board(board).to_string());
+    fen.push(' ');
+    fen.push_str(&en_passant_square(board, turn).map_or(String::from("-"), get_square_name));
+
+    fen.push_str(&format!(" {} {}", halfmove_clock, fullmove_number));
+    fen
+}
+
+/// The tile a pawn of the side to move can take en passant on, the one the
+/// opposing pawn that just double moved passed over
+pub fn en_passant_square(board: &[Tile; 64], turn: Colors) -> Option<usize> {
+    board.iter().enumerate().find_map(|(index, tile)| match (tile.piece, tile.color) {
+        (Some(Pieces::Pawn { en_passantable: true, .. }), Some(Colors::White)) if turn == Colors::Black => Some(index + 0o10),
+        (Some(Pieces::Pawn { en_passantable: true, .. }), Some(Colors::Black)) if turn == Colors::White => Some(index - 0o10),
+        _ => None
+    })
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Tile {
+    pub color : Option<Colors>,
+    pub piece : Option<Pieces>,
+    pub tiles_left : usize,
+    pub tiles_right : usize,
+    pub tiles_up : usize,
+    pub tiles_down : usize
+}
+impl Tile {
+    pub fn get_piece_image_index(&self) -> Option<usize> {
+        let mut index : usize = 0;
+
+        match self.color {
+            Some(Colors::Black) => index += 6, // Uses the black piece indexes (2nd row)
+            Some(Colors::White) => index += 0, // Uses the white piece indexes (1st row)
+            None => return None                // No piece is in this Tile
+        }
+
+        match self.piece {
+            Some(Pieces::King { .. }) => index += 0, // 1st image in the row
+            Some(Pieces::Queen) => index += 1,     // 2nd image in the row
+            Some(Pieces::Bishop) => index += 2,    // 3rd image in the row
+            Some(Pieces::Knight) => index += 3,    // 4th image in the row
+            Some(Pieces::Rook { .. }) => index += 4, // 5th image in the row
+            Some(Pieces::Pawn { .. }) => index += 5, // 6th image in the row
+            None => return None
+        }
+
+        Some(index)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Colors {
+    White,
+    Black
+}
+impl Colors {
+    #[inline]
+    pub fn opposite(&self) -> Colors {
+        match self {
+            Colors::White => Colors::Black,
+            Colors::Black => Colors::White
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Pieces {
+    King {has_moved : bool},
+    Queen,
+    Rook {has_moved : bool},
+    Knight,
+    Bishop,
+    Pawn {has_moved : bool, en_passantable : bool}
+}
+
+pub fn generate_legal_tile_movements(board: &[Tile; 64], index: usize) -> Option<Vec<usize>> {
+    let tile: Tile = board[index];
+    let mut legal_moves: Vec<usize> = vec![];
+    match tile.piece {
+        None => return None,
+        Some(_) => {
+            // Allows for safe unwrapping of the tile
+            let piece = tile.piece.unwrap();
+            let color = tile.color.unwrap();
+            match piece {
+
+                Pieces::King { .. } => {
+                    // This syntax allows for the king piece variants to be done
+                    // inside of the general king piece
+                    if let Pieces::King { has_moved : false } = piece {
+                        // Evaluate castling moves
+                        match color {
+                            // An unmoved king can only castle from its home square
+                            Colors::White if index == 0o74 => {
+                                // King side castle
+                                // Check for a rook on the king side, must be of the
+                                // same colour as it has to have not moved
+                                if let (Some(Pieces::Rook { has_moved : false }), Some(Colors::White)) = (board[0o77].piece, board[0o77].color) {
+                                    // Squares in the middle of the two pieces must be empty
+                                    if board[0o76].piece.is_none() && board[0o75].piece.is_none() {
+                                        legal_moves.push(index + 0o02);
+                                    }
+                                }
+                                if let (Some(Pieces::Rook { has_moved : false }), Some(Colors::White)) = (board[0o70].piece, board[0o70].color) {
+                                    // Squares in the middle of the two pieces must be empty
+                                    if board[0o71].piece.is_none() && board[0o72].piece.is_none() && board[0o73].piece.is_none() {
+                                        legal_moves.push(index - 0o02);
+                                    }
+                                }
+                            },
+                            Colors::Black if index == 0o04 => {
+                                // King side castle
+                                // Check for a rook on the king side, must be of the
+                                // same colour as it has to have not moved
+                                if let (Some(Pieces::Rook { has_moved : false }), Some(Colors::Black)) = (board[0o07].piece, board[0o07].color) {
+                                    // Squares in the middle of the two pieces must be empty
+                                    if board[0o05].piece.is_none() && board[0o06].piece.is_none() {
+                                        legal_moves.push(index + 0o02);
+                                    }
+                                }
+                                if let (Some(Pieces::Rook { has_moved : false }), Some(Colors::Black)) = (board[0o00].piece, board[0o00].color) {
+                                    // Squares in the middle of the two pieces must be empty
+                                    if board[0o01].piece.is_none() && board[0o02].piece.is_none() && board[0o03].piece.is_none() {
+                                        legal_moves.push(index - 0o02);
+                                    }
+                                }
+                            },
+                            _ => ()
+                        }
+                    }
+
+                    if tile.tiles_left > 0 && board[index - 0o01].color != Some(color) {
+                        legal_moves.push(index - 1);
+                    }
+                    if tile.tiles_right > 0 && board[index + 0o01].color != Some(color) {
+                        legal_moves.push(index + 0o01);
+                    }
+                    if tile.tiles_up > 0 {
+                        if board[index - 8].color != Some(color) {
+                            legal_moves.push(index - 0o10);
+                        }
+                        if tile.tiles_left > 0 && board[index - 9].color != Some(color) {
+                            legal_moves.push(index - 0o11);
+                        }
+                        if tile.tiles_right > 0 && board[index - 7].color != Some(color) {
+                            legal_moves.push(index - 0o07);
+                        }
+                    }
+                    if tile.tiles_down > 0 {
+                        if board[index + 0o10].color != Some(color) {
+                            legal_moves.push(index + 8);
+                        }
+                        if tile.tiles_left > 0 && board[index + 0o07].color != Some(color) {
+                            legal_moves.push(index + 0o07);
+                        }
+                        if tile.tiles_right > 0 && board[index + 0o11].color != Some(color) {
+                            legal_moves.push(index + 0o11);
+                        }
+                    }
+                },
+
+                Pieces::Queen => {
+                    // Calculate moves right
+                    for x in 0o01..tile.tiles_right + 0o01 {
+                        match board[index + x].color {
+                            None => { legal_moves.push(index + x); }
+                            Some(_) => {
+                                if board[index + x].color != Some(color) {
+                                    // If opposing color add the take to legal moves
+                                    legal_moves.push(index + x);
+                                }
+                                break;
+                            }
+                        }
+                    }
+                    // Calculate moves left
+                    for x in 0o01..tile.tiles_left + 0o01 {
+                        match board[index - x].color {
+                            None => { legal_moves.push(index - x); }
+                            Some(_) => {
+                                if board[index - x].color != Some(color) {
+                                    // If opposing color add the take to legal moves
+                                    legal_moves.push(index - x);
+                                }
+                                break;
+                            }
+                        }
+                    }
+                    // Calculate moves down
+                    for y in (0o10..tile.tiles_down * 0o10 + 0o10).step_by(0o10) {
+                        match board[index + y].color {
+                            None => { legal_moves.push(index + y); }
+                            Some(_) => {
+                                if board[index + y].color != Some(color) {
+                                    // If opposing color add the take to legal moves
+                                    legal_moves.push(index + y);
+                                }
+                                break;
+                            }
+                        }
+                    }
+                    // Calculate moves up
+                    for y in (0o10..tile.tiles_up * 0o10 + 0o10).step_by(0o10) {
+                        match board[index - y].color {
+                            None => { legal_moves.push(index - y); }
+                            Some(_) => {
+                                if board[index - y].color != Some(color) {
+                                    // If opposing color add the take to legal moves
+                                    legal_moves.push(index - y);
+                                }
+                                break;
+                            }
+                        }
+                    }
+                    let max_up_left = cmp::min(tile.tiles_up, tile.tiles_left);
+                    let max_down_left = cmp::min(tile.tiles_down, tile.tiles_left);
+                    let max_up_right = cmp::min(tile.tiles_up, tile.tiles_right);
+                    let max_down_right = cmp::min(tile.tiles_down, tile.tiles_right);
+                    // Calculate moves up and left
+                    for z in (0o11..max_up_left * 0o11 + 0o11).step_by(0o11) {
+                        match board[index - z].color {
+                            None => { legal_moves.push(index - z); }
+                            Some(_) => {
+                                if board[index - z].color != Some(color) {
+                                    // If opposing color add the take to legal moves
+                                    legal_moves.push(index - z);
+                                }
+                                break;
+                            }
+                        }
+                    }
+                    // Calculates moves down and right
+                    for z in (0o11..max_down_right * 0o11 + 0o11).step_by(0o11) {
+                        match board[index + z].color {
+                            None => { legal_moves.push(index + z); }
+                            Some(_) => {
+                                if board[index + z].color != Some(color) {
+                                    // If opposing color add the take to legal moves
+                                    legal_moves.push(index + z);
+                                }
+                                break;
+                            }
+                        }
+                    }
+                    // Calculate moves up and right
+                    for z in (0o07..max_up_right * 0o07 + 0o07).step_by(0o07) {
+                        match board[index - z].color {
+                            None => { legal_moves.push(index - z); }
+                            Some(_) => {
+                                if board[index - z].color != Some(color) {
+                                    // If opposing color add the take to legal moves
+                                    legal_moves.push(index - z);
+                                }
+                                break;
+                            }
+                        }
+                    }
+                    // Calculates moves down and left
+                    for z in (0o07..max_down_left * 0o07 + 0o07).step_by(0o07) {
+                        match board[index + z].color {
+                            None => { legal_moves.push(index + z); }
+                            Some(_) => {
+                                if board[index + z].color != Some(color) {
+                                    // If opposing color add the take to legal moves
+                                    legal_moves.push(index + z);
+                                }
+                                break;
+                            }
+                        }
+                    }
+                },
+
+                Pieces::Rook { .. } => {
+                    // All castling mechanics are handled by the King piece
+                    // Calculate moves right
+                    for x in 0o01..tile.tiles_right + 0o01 {
+                        match board[index + x].color {
+                            None => { legal_moves.push(index + x); }
+                            Some(_) => {
+                                if board[index + x].color != Some(color) {
+                                    // If opposing color add the take to legal moves
+                                    legal_moves.push(index + x);
+                                }
+                                break;
+                            }
+                        }
+                    }
+                    // Calculate moves left
+                    for x in 0o01..tile.tiles_left + 0o01 {
+                        match board[index - x].color {
+                            None => { legal_moves.push(index - x); }
+                            Some(_) => {
+                                if board[index - x].color != Some(color) {
+                                    // If opposing color add the take to legal moves
+                                    legal_moves.push(index - x);
+                                }
+                                break;
+                            }
+                        }
+                    }
+                    // Calculate moves down
+                    for y in (0o10..tile.tiles_down * 0o10 + 0o10).step_by(0o10) {
+                        match board[index + y].color {
+                            None => { legal_moves.push(index + y); }
+                            Some(_) => {
+                                if board[index + y].color != Some(color) {
+                                    // If opposing color add the take to legal moves
+                                    legal_moves.push(index + y);
+                                }
+                                break;
+                            }
+                        }
+                    }
+                    // Calculate moves up
+                    for y in (0o10..tile.tiles_up * 0o10 + 0o10).step_by(0o10) {
+                        match board[index - y].color {
+                            None => { legal_moves.push(index - y); }
+                            Some(_) => {
+                                if board[index - y].color != Some(color) {
+                                    // If opposing color add the take to legal moves
+                                    legal_moves.push(index - y);
+                                }
+                                break;
+                            }
+                        }
+                    }
+                },
+
+                Pieces::Bishop => {
+                    let max_up_left = cmp::min(tile.tiles_up, tile.tiles_left);
+                    let max_down_left = cmp::min(tile.tiles_down, tile.tiles_left);
+                    let max_up_right = cmp::min(tile.tiles_up, tile.tiles_right);
+                    let max_down_right = cmp::min(tile.tiles_down, tile.tiles_right);
+                    // Calculate moves up and left
+                    for z in (0o11..max_up_left * 0o11 + 0o11).step_by(0o11) {
+                        match board[index - z].color {
+                            None => { legal_moves.push(index - z); }
+                            Some(_) => {
+                                if board[index - z].color != Some(color) {
+                                    // If opposing color add the take to legal moves
+                                    legal_moves.push(index - z);
+                                }
+                                break;
+                            }
+                        }
+                    }
+                    // Calculates moves down and right
+                    for z in (0o11..max_down_right * 0o11 + 0o11).step_by(0o11) {
+                        match board[index + z].color {
+                            None => { legal_moves.push(index + z); }
+                            Some(_) => {
+                                if board[index + z].color != Some(color) {
+                                    // If opposing color add the take to legal moves
+                                    legal_moves.push(index + z);
+                                }
+                                break;
+                            }
+                        }
+                    }
+                    // Calculate moves up and right
+                    for z in (0o07..max_up_right * 0o07 + 0o07).step_by(0o07) {
+                        match board[index - z].color {
+                            None => { legal_moves.push(index - z); }
+                            Some(_) => {
+                                if board[index - z].color != Some(color) {
+                                    // If opposing color add the take to legal moves
+                                    legal_moves.push(index - z);
+                                }
+                                break;
+                            }
+                        }
+                    }
+                    // Calculates moves down and left
+                    for z in (0o07..max_down_left * 0o07 + 0o07).step_by(0o07) {
+                        match board[index + z].color {
+                            None => { legal_moves.push(index + z); }
+                            Some(_) => {
+                                if board[index + z].color != Some(color) {
+                                    // If opposing color add the take to legal moves
+                                    legal_moves.push(index + z);
+                                }
+                                break;
+                            }
+                        }
+                    }
+                },
+
+                Pieces::Knight => {
+                    const OFFSETS: [usize; 4] = [0o06, 0o12, 0o17, 0o21];
+                    let positive_condition_evals: [bool; 4] = [
+                        tile.tiles_left >= 2 && tile.tiles_down >= 1,
+                        tile.tiles_right >= 2 && tile.tiles_down >= 1,
+                        tile.tiles_left >= 1 && tile.tiles_down >= 2,
+                        tile.tiles_right >= 1 && tile.tiles_down >= 2
+                    ];
+                    let negative_condition_evals: [bool; 4] = [
+                        tile.tiles_right >= 2 && tile.tiles_up >= 1,
+                        tile.tiles_left >= 2 && tile.tiles_up >= 1,
+                        tile.tiles_right >= 1 && tile.tiles_up >= 2,
+                        tile.tiles_left >= 1 && tile.tiles_up >= 2
+                    ];
+                    for i in 0..4 {
+                        if positive_condition_evals[i] {
+                            match board[index + OFFSETS[i]].color {
+                                None => { legal_moves.push(index + OFFSETS[i]); }
+                                Some(_) => {
+                                    if board[index + OFFSETS[i]].color != Some(color) {
+                                        // If opposing color add the take to legal moves
+                                        legal_moves.push(index + OFFSETS[i]);
+                                    }
+                                }
+                            }
+                        }
+                        if negative_condition_evals[i] {
+                            match board[index - OFFSETS[i]].color {
+                                None => { legal_moves.push(index - OFFSETS[i]); }
+                                Some(_) => {
+                                    if board[index - OFFSETS[i]].color != Some(color) {
+                                        // If opposing color add the take to legal moves
+                                        legal_moves.push(index - OFFSETS[i]);
+                                    }
+                                }
+                            }
+                        }
+                    }
+                },
+
+                Pieces::Pawn { .. } => {
+                    let opposing_color = color.opposite();
+                    // A pawn beside this one that has just double moved can be taken en passant
+                    let en_passant = |side: usize| {
+                        board[side].color == Some(opposing_color)
+                            && board[side].piece == Some(Pieces::Pawn { has_moved: true, en_passantable: true })
+                    };
+                    // Pawn movements rely on the color for direction of movement
+                    match color {
+                        Colors::White => {
+                            if tile.tiles_up >= 1 {
+                                // Checks for a single move forwards
+                                if board[index - 0o10].piece.is_none() {
+                                    legal_moves.push(index - 0o10);
+                                    // Check eligibility for double move
+                                    // This is enclosed here as a double move can only occur
+                                    // If a single move can also occur
+                                    // Pawns can only move forward into an empty space
+                                    if let Pieces::Pawn { has_moved: false, .. } = piece {
+                                        if tile.tiles_up >= 2 && board[index - 0o20].piece.is_none() {
+                                            legal_moves.push(index - 0o20);
+                                        }
+                                    }
+                                }
+                                if tile.tiles_left >= 1 && (board[index - 0o11].color == Some(opposing_color) || en_passant(index - 0o01)) {
+                                    legal_moves.push(index - 0o11);
+                                }
+                                if tile.tiles_right >= 1 && (board[index - 0o07].color == Some(opposing_color) || en_passant(index + 0o01)) {
+                                    legal_moves.push(index - 0o07);
+                                }
+                            }
+                        },
+                        Colors::Black => {
+                            if tile.tiles_down >= 1 {
+                                // Checks for a single move forwards
+                                if board[index + 0o10].piece.is_none() {
+                                    legal_moves.push(index + 0o10);
+                                    // Check eligibility for double move
+                                    // This is enclosed here as a double move can only occur
+                                    // If a single move can also occur
+                                    // Pawns can only move forward into an empty space
+                                    if let Pieces::Pawn { has_moved: false, .. } = piece {
+                                        if tile.tiles_down >= 2 && board[index + 0o20].piece.is_none() {
+                                            legal_moves.push(index + 0o20);
+                                        }
+                                    }
+                                }
+                                if tile.tiles_left >= 1 && (board[index + 0o07].color == Some(opposing_color) || en_passant(index - 0o01)) {
+                                    legal_moves.push(index + 0o07);
+                                }
+                                if tile.tiles_right >= 1 && (board[index + 0o11].color == Some(opposing_color) || en_passant(index + 0o01)) {
+                                    legal_moves.push(index + 0o11);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    Some(legal_moves)
+}
+
+pub fn play_move(board: &mut [Tile; 64], current: usize, new: usize) {
+    // En passant is only available on the move straight after a double move,
+    // so any opposing pawn that could have been taken no longer can be
+    if let Some(color) = board[current].color {
+        for tile in board.iter_mut() {
+            if tile.color != Some(color) {
+                if let Some(Pieces::Pawn { has_moved, en_passantable: true }) = tile.piece {
+                    tile.piece = Some(Pieces::Pawn { has_moved, en_passantable: false });
+                }
+            }
+        }
+    }
+    board[new].piece = match board[current].piece {
+        Some(Pieces::King { .. }) => {
+            let distance = new.abs_diff(current);
+            if distance == 2 {
+                // At this point the king will have had to have castled
+                if new > current {
+                    // King side castle
+                    // King to +2 | Rook to -2
+                    // Rook is currently at +3
+                    play_move(board, current + 3, current + 1)
+                } else {
+                    // Queen side castle
+                    // King to -2 | Rook to +3
+                    // Rook is currently at -4
+                    play_move(board, current - 4, current - 1)
+                }
+                
+            }
+            // Places king in the new square
+            Some(Pieces::King { has_moved: true })
+        },
+        Some(Pieces::Rook { .. }) => Some(Pieces::Rook { has_moved: true }),
+        Some(Pieces::Pawn { .. }) => {
+            let distance = new.abs_diff(current);
+            if distance == 0o20 {
+                Some(Pieces::Pawn { has_moved: true, en_passantable: true })
+            } else {
+                // A diagonal move onto an empty tile is an en passant capture,
+                // the taken pawn is beside the current tile on the new column
+                if distance != 0o10 && board[new].piece.is_none() {
+                    let taken = current - current % 8 + new % 8;
+                    board[taken].piece = None;
+                    board[taken].color = None;
+                }
+                Some(Pieces::Pawn { has_moved: true, en_passantable: false })
+            }
+        },
+        _ => board[current].piece
+    };
+    board[new].color = board[current].color;
+    board[current].piece = None;
+    board[current].color = None;
+}
+
+/// A single move from one tile to another, with the piece chosen when a pawn
+/// reaches the final row
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Move {
+    pub from : usize,
+    pub to : usize,
+    pub promotion : Option<Pieces>
+}
+
+/// Plays a move on the board, including the promotion of a pawn
+pub fn make_move(board: &mut [Tile; 64], mv: Move) {
+    play_move(board, mv.from, mv.to);
+    if let Some(promotion) = mv.promotion {
+        board[mv.to].piece = Some(promotion);
+    }
+}
+
+/// Checks whether any piece of the attacking colour could capture on the given tile
+pub fn is_square_attacked(board: &[Tile; 64], index: usize, attacker: Colors) -> bool {
+    let [x, y] = get_render_coords(index);
+    let (x, y) = (x as i32, y as i32);
+    // Returns the occupant of a tile, or None when off the board
+    let tile_at = |x: i32, y: i32| -> Option<Tile> {
+        if (0..8).contains(&x) && (0..8).contains(&y) {
+            Some(board[get_array_index(x as usize, y as usize)])
+        } else {
+            None
+        }
+    };
+    let attacker_at = |x: i32, y: i32| -> Option<Pieces> {
+        tile_at(x, y).filter(|tile| tile.color == Some(attacker)).and_then(|tile| tile.piece)
+    };
+
+    // Pawns attack diagonally forwards, so look one row back towards the attacker
+    let pawn_row = match attacker {
+        Colors::White => y + 1,
+        Colors::Black => y - 1
+    };
+    for dx in [-1, 1] {
+        if let Some(Pieces::Pawn { .. }) = attacker_at(x + dx, pawn_row) {
+            return true;
+        }
+    }
+
+    const KNIGHT_OFFSETS: [(i32, i32); 8] = [(1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2)];
+    for (dx, dy) in KNIGHT_OFFSETS {
+        if let Some(Pieces::Knight) = attacker_at(x + dx, y + dy) {
+            return true;
+        }
+    }
+
+    const KING_OFFSETS: [(i32, i32); 8] = [(1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1), (0, -1), (1, -1)];
+    for (dx, dy) in KING_OFFSETS {
+        if let Some(Pieces::King { .. }) = attacker_at(x + dx, y + dy) {
+            return true;
+        }
+    }
+
+    // Sliding pieces attack along a ray until the first occupied tile
+    for (dx, dy) in KING_OFFSETS {
+        let diagonal = dx != 0 && dy != 0;
+        let (mut ray_x, mut ray_y) = (x + dx, y + dy);
+        while let Some(tile) = tile_at(ray_x, ray_y) {
+            if tile.piece.is_some() {
+                if tile.color == Some(attacker) {
+                    match tile.piece {
+                        Some(Pieces::Queen) => return true,
+                        Some(Pieces::Bishop) if diagonal => return true,
+                        Some(Pieces::Rook { .. }) if !diagonal => return true,
+                        _ => ()
+                    }
+                }
+                break;
+            }
+            ray_x += dx;
+            ray_y += dy;
+        }
+    }
+
+    false
+}
+
+/// Finds the tile holding the king of the given colour
+pub fn find_king(board: &[Tile; 64], color: Colors) -> Option<usize> {
+    (0..64).find(|&index| {
+        board[index].color == Some(color) && matches!(board[index].piece, Some(Pieces::King { .. }))
+    })
+}
+
+pub fn is_in_check(board: &[Tile; 64], color: Colors) -> bool {
+    match find_king(board, color) {
+        Some(index) => is_square_attacked(board, index, color.opposite()),
+        None => false
+    }
+}
+
+/// Generates every move for the given colour that does not leave its own king
+/// in check. Each tile's movements are played on a copy of the board and kept
+/// only if the king is safe afterwards.
+pub fn generate_legal_moves(board: &[Tile; 64], color: Colors) -> Vec<Move> {
+    let mut moves: Vec<Move> = vec![];
+    for from in 0..64 {
+        if board[from].color != Some(color) {
+            continue;
+        }
+        let targets = match generate_legal_tile_movements(board, from) {
+            Some(targets) => targets,
+            None => continue
+        };
+        let piece = board[from].piece;
+        for to in targets {
+            if let Some(Pieces::King { .. }) = piece {
+                // Castling can not start from, or pass through, an attacked tile
+                if from.abs_diff(to) == 2
+                    && (is_square_attacked(board, from, color.opposite())
+                        || is_square_attacked(board, (from + to) / 2, color.opposite())) {
+                    continue;
+                }
+            }
+            if let (Some(Pieces::Pawn { .. }), 0 | 7) = (piece, to >> 3) {
+                // Pawns reaching the final row must promote
+                for promotion in [Pieces::Queen, Pieces::Rook { has_moved: true }, Pieces::Bishop, Pieces::Knight] {
+                    moves.push(Move { from, to, promotion: Some(promotion) });
+                }
+            } else {
+                moves.push(Move { from, to, promotion: None });
+            }
+        }
+    }
+
+    moves.retain(|&mv| {
+        let mut after: [Tile; 64] = *board;
+        make_move(&mut after, mv);
+        !is_in_check(&after, color)
+    });
+    moves
+}
+
+/// Converts a square name such as "e4" into its board index
+pub fn parse_square_name(name: &str) -> Option<usize> {
+    name.parse::<Square>().ok().map(Square::index)
+}
+
+/// Converts a board index into its square name, such as "e4"
+pub fn get_square_name(index: usize) -> String {
+    Square(index).to_string()
+}
+
+#[inline]
+pub fn get_render_coords(index : usize) -> [usize; 2] {
+    let x = index  % 8;
+    let y = index >> 3;
+    [x, y]
+}
+
+#[inline]
+pub fn get_array_index(x: usize, y: usize) -> usize {
+    y * 8 + x
+}
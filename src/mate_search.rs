@@ -0,0 +1,208 @@
+// Mate in N solver
+//
+// Proves or refutes a forced mate for the side to move by exhaustive search.
+// At the attacker's turns checking moves are tried first, as they are the
+// most likely to mate and leave the defender the fewest replies, and on the
+// final move only checks are tried at all. At the defender's turns every
+// reply must lose to a mate, and the reply holding out longest is kept so
+// that the returned line shows the best defence. `run` prints the line in
+// SAN, for the command line.
+
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::position::Position;
+use crate::san::move_to_san;
+use crate::Move;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MateSearchResult {
+    // The shortest forced mate found, alternating attacker and defender moves
+    Mate(Vec<Move>),
+    // There is no forced mate within the number of moves searched
+    NoMate,
+    // The search was stopped before it could decide
+    Stopped
+}
+
+struct MateSearcher<'a> {
+    stop : &'a AtomicBool,
+    nodes : u64,
+    stopped : bool,
+    // Attacker positions, by hash and moves left, already shown not to mate
+    refuted : HashSet<(u64, u32)>
+}
+impl MateSearcher<'_> {
+    /// Finds a move for the attacker that forces mate within `moves_left` moves
+    fn attack(&mut self, position: &Position, moves_left: u32) -> Option<Vec<Move>> {
+        self.nodes += 1;
+        if self.stopped || self.stop.load(Ordering::Relaxed) {
+            self.stopped = true;
+            return None;
+        }
+        let hash = position.hash();
+        if self.refuted.contains(&(hash, moves_left)) {
+            return None;
+        }
+
+        // Play every move once, sorting the checks to the front
+        let mut replies : Vec<(bool, Move, Position)> = position.legal_moves()
+            .into_iter()
+            .map(|mv| {
+                let mut after = *position;
+                after.play_move(mv);
+                (after.is_in_check(), mv, after)
+            })
+            .filter(|&(check, _, _)| check || moves_left > 1)
+            .collect();
+        replies.sort_by_key(|&(check, _, _)| !check);
+
+        for (_, mv, after) in replies {
+            if let Some(line) = self.defend(&after, moves_left) {
+                let mut mating_line = vec![mv];
+                mating_line.extend(line);
+                return Some(mating_line);
+            }
+            if self.stopped {
+                return None;
+            }
+        }
+
+        self.refuted.insert((hash, moves_left));
+        None
+    }
+
+    /// Checks that every defence loses to a mate within `moves_left` attacker
+    /// moves, counting the one just played, and returns the longest line
+    fn defend(&mut self, position: &Position, moves_left: u32) -> Option<Vec<Move>> {
+        self.nodes += 1;
+        let defences : Vec<Move> = position.legal_moves();
+        if defences.is_empty() {
+            // Stalemate is not a mate
+            return if position.is_in_check() { Some(vec![]) } else { None };
+        }
+        if moves_left == 1 {
+            return None;
+        }
+
+        let mut longest : Vec<Move> = vec![];
+        for defence in defences {
+            let mut after = *position;
+            after.play_move(defence);
+            let line = self.attack(&after, moves_left - 1)?;
+            if line.len() + 1 > longest.len() {
+                longest = vec![defence];
+                longest.extend(line);
+            }
+        }
+        Some(longest)
+    }
+}
+
+/// Searches for a forced mate in at most `moves` moves for the side to move,
+/// returning the shortest one. `report` is called with the number of moves
+/// and nodes searched each time a mate in one more move has been refuted.
+pub fn find_mate(position: &Position, moves: u32, stop: &AtomicBool, mut report: impl FnMut(u32, u64)) -> MateSearchResult {
+    let mut searcher = MateSearcher {
+        stop,
+        nodes : 0,
+        stopped : false,
+        refuted : HashSet::new()
+    };
+
+    // Searching each length in turn finds the shortest mate first
+    for moves_left in 1..=moves {
+        if let Some(line) = searcher.attack(position, moves_left) {
+            return MateSearchResult::Mate(line);
+        }
+        if searcher.stopped {
+            return MateSearchResult::Stopped;
+        }
+        report(moves_left, searcher.nodes);
+    }
+
+    MateSearchResult::NoMate
+}
+
+
+/// Looks for a mate in at most `moves` moves, printing each length refuted
+/// and then the mating line
+pub fn run(position: &Position, moves: u32) {
+    let result = find_mate(position, moves, &AtomicBool::new(false), |moves, nodes| {
+        println!("no mate in {} ({} nodes)", moves, nodes);
+    });
+    match result {
+        MateSearchResult::Mate(line) => {
+            let mut position = *position;
+            let mut written : Vec<String> = vec![];
+            for &mv in line.iter() {
+                written.push(move_to_san(&position, mv));
+                position.play_move(mv);
+            }
+            println!("mate in {}: {}", line.len().div_ceil(2), written.join(" "));
+        },
+        MateSearchResult::NoMate => println!("no mate in {} or fewer moves", moves),
+        MateSearchResult::Stopped => ()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::square::move_to_uci;
+
+    /// Solves the problem, checking that the line found mates, and returns
+    /// it in coordinates
+    fn solve(fen: &str, moves: u32) -> Option<Vec<String>> {
+        let mut position = Position::from_fen(fen).unwrap();
+        let line = match find_mate(&position, moves, &AtomicBool::new(false), |_, _| {}) {
+            MateSearchResult::Mate(line) => line,
+            MateSearchResult::NoMate => return None,
+            MateSearchResult::Stopped => panic!("stopped without being told to")
+        };
+        for &mv in line.iter() {
+            position.play_move(mv);
+        }
+        assert!(position.is_in_check() && position.legal_moves().is_empty(), "{} does not end in mate", fen);
+        Some(line.iter().map(|&mv| move_to_uci(mv)).collect())
+    }
+
+    #[test]
+    fn solves_mate_in_one() {
+        assert_eq!(solve("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", 1), Some(vec!["a1a8".to_string()]));
+    }
+
+    #[test]
+    fn solves_mate_in_two() {
+        // Legal's mate: 1. Nf6+ gxf6 2. Bxf7#
+        let line = solve("r2qkb1r/pp2nppp/3p4/2pNN1B1/2BnP3/3P4/PPP2PPP/R2bK2R w KQkq - 1 10", 2);
+        assert_eq!(line, Some(vec!["d5f6".to_string(), "g7f6".to_string(), "c4f7".to_string()]));
+    }
+
+    #[test]
+    fn solves_mate_in_three() {
+        // Philidor's smothered mate: 1. Nh6+ Kh8 2. Qg8+ Rxg8 3. Nf7#, with
+        // 1... Kf8 lasting only until 2. Qf7#
+        let line = solve("3r2k1/5Npp/8/8/8/1Q6/8/6K1 w - - 0 1", 3).unwrap();
+        assert_eq!(line, ["f7h6", "g8h8", "b3g8", "d8g8", "h6f7"]);
+    }
+
+    #[test]
+    fn finds_the_shortest_mate() {
+        // The mate in one is found when asked for up to three
+        assert_eq!(solve("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", 3).map(|line| line.len()), Some(1));
+        assert_eq!(solve("3r2k1/5Npp/8/8/8/1Q6/8/6K1 w - - 0 1", 2), None);
+    }
+
+    #[test]
+    fn refutes_positions_without_a_mate() {
+        assert_eq!(solve("7k/8/6Q1/8/8/8/8/6K1 w - - 0 1", 1), None);
+        assert_eq!(solve(crate::STARTING_FEN, 2), None);
+    }
+
+    #[test]
+    fn stops_when_told() {
+        let position = Position::from_fen("3r2k1/5Npp/8/8/8/1Q6/8/6K1 w - - 0 1").unwrap();
+        assert_eq!(find_mate(&position, 3, &AtomicBool::new(true), |_, _| {}), MateSearchResult::Stopped);
+    }
+}
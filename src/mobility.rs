@@ -0,0 +1,139 @@
+// Mobility evaluation
+//
+// Pieces with more moves are more active. Each knight, bishop, rook and
+// queen scores for the number of tiles it can move to, found as a bit set by
+// `piece_moves` so that nothing is allocated, above or below a typical count
+// for the piece. While the moves are being counted, the pieces with a move
+// next to the enemy king are also counted for king safety, see
+// `king_safety`.
+//
+// Scores are (middle game, end game) pairs from White's point of view.
+
+use crate::eval_params::EvalParams;
+use crate::movegen::piece_moves;
+use crate::{get_render_coords, Colors, Pieces, Tile};
+
+// Number of moves a piece typically has, below which it is penalised
+const KNIGHT_TYPICAL_MOVES : i32 = 4;
+const BISHOP_TYPICAL_MOVES : i32 = 6;
+const ROOK_TYPICAL_MOVES : i32 = 7;
+const QUEEN_TYPICAL_MOVES : i32 = 13;
+
+// Weight of each piece type attacking the tiles around the enemy king
+const KNIGHT_ATTACK_WEIGHT : i32 = 2;
+const BISHOP_ATTACK_WEIGHT : i32 = 2;
+const ROOK_ATTACK_WEIGHT : i32 = 3;
+const QUEEN_ATTACK_WEIGHT : i32 = 5;
+
+/// Pieces of each colour attacking the zone around the enemy king
+#[derive(Debug, Copy, Clone, Default)]
+pub struct KingAttacks {
+    // Number of pieces attacking the zone, indexed by the attacking colour
+    pub attackers : [i32; 2],
+    // Sum of the attack weights of those pieces
+    pub weight : [i32; 2]
+}
+
+/// The king's tile and every tile next to it, as a bit set
+pub fn king_zone(king: usize) -> u64 {
+    let [x, y] = get_render_coords(king);
+    let mut zone : u64 = 0;
+    for zone_y in y.saturating_sub(1)..=(y + 1).min(7) {
+        for zone_x in x.saturating_sub(1)..=(x + 1).min(7) {
+            zone |= 1 << (zone_y * 8 + zone_x);
+        }
+    }
+    zone
+}
+
+/// Scores the mobility of every piece and counts the attacks on each king
+pub fn evaluate_mobility(board: &[Tile; 64], params: &EvalParams, kings: [Option<usize>; 2]) -> ([i32; 2], KingAttacks) {
+    let mut score : [i32; 2] = [0, 0];
+    let mut attacks = KingAttacks::default();
+    let zones : [u64; 2] = [
+        kings[Colors::White as usize].map_or(0, king_zone),
+        kings[Colors::Black as usize].map_or(0, king_zone)
+    ];
+
+    for (index, tile) in board.iter().enumerate() {
+        let (piece, color) = match (tile.piece, tile.color) {
+            (Some(piece), Some(color)) => (piece, color),
+            _ => continue
+        };
+        let (mobility, typical_moves, attack_weight) = match piece {
+            Pieces::Knight => (params.knight_mobility, KNIGHT_TYPICAL_MOVES, KNIGHT_ATTACK_WEIGHT),
+            Pieces::Bishop => (params.bishop_mobility, BISHOP_TYPICAL_MOVES, BISHOP_ATTACK_WEIGHT),
+            Pieces::Rook { .. } => (params.rook_mobility, ROOK_TYPICAL_MOVES, ROOK_ATTACK_WEIGHT),
+            Pieces::Queen => (params.queen_mobility, QUEEN_TYPICAL_MOVES, QUEEN_ATTACK_WEIGHT),
+            _ => continue
+        };
+        let moves = piece_moves(board, index);
+
+        let sign = if color == Colors::White { 1 } else { -1 };
+        let extra_moves = moves.count_ones() as i32 - typical_moves;
+        score[0] += sign * mobility[0] * extra_moves;
+        score[1] += sign * mobility[1] * extra_moves;
+
+        let enemy_zone = zones[color.opposite() as usize];
+        if moves & enemy_zone != 0 {
+            attacks.attackers[color as usize] += 1;
+            attacks.weight[color as usize] += attack_weight;
+        }
+    }
+
+    (score, attacks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval_weights::DEFAULT_PARAMS;
+    use crate::position::Position;
+    use crate::{find_king, generate_legal_tile_movements};
+
+    fn mobility(fen: &str, params: &EvalParams) -> ([i32; 2], KingAttacks) {
+        let board = *Position::from_fen(fen).unwrap().board();
+        evaluate_mobility(&board, params, [find_king(&board, Colors::White), find_king(&board, Colors::Black)])
+    }
+
+    #[test]
+    fn counts_the_same_moves_as_the_tile_moves() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "4k3/8/8/3Q4/8/8/1n6/4K2R w K - 0 1"
+        ] {
+            let board = *Position::from_fen(fen).unwrap().board();
+            for index in 0..64 {
+                if matches!(board[index].piece, Some(Pieces::Knight | Pieces::Bishop | Pieces::Rook { .. } | Pieces::Queen)) {
+                    let expected = generate_legal_tile_movements(&board, index).unwrap().iter().fold(0u64, |moves, &to| moves | 1 << to);
+                    assert_eq!(piece_moves(&board, index), expected, "tile {} of {}", index, fen);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn scores_moves_above_the_typical_count() {
+        let mut params = DEFAULT_PARAMS;
+        params.knight_mobility = [4, 5];
+        // Two moves from the corner and eight from the middle, against four
+        assert_eq!(mobility("4k3/8/8/8/8/8/8/N3K3 w - - 0 1", &params).0, [-8, -10]);
+        assert_eq!(mobility("4k3/8/8/8/3N4/8/8/4K3 w - - 0 1", &params).0, [16, 20]);
+        assert_eq!(mobility("4k3/8/8/8/3n4/8/8/4K3 w - - 0 1", &params).0, [-16, -20]);
+        // Own pieces block moves, enemy pieces can be taken
+        assert_eq!(mobility("4k3/8/8/8/8/1P6/2P5/N3K3 w - - 0 1", &params).0, [-16, -20]);
+        assert_eq!(mobility("4k3/8/8/8/8/1p6/2p5/N3K3 w - - 0 1", &params).0, [-8, -10]);
+    }
+
+    #[test]
+    fn counts_pieces_attacking_the_king_zone() {
+        // The queen reaches d8 and d7 beside the king, the knight nothing
+        let (_, attacks) = mobility("4k3/8/8/8/8/8/8/N2QK3 w - - 0 1", &DEFAULT_PARAMS);
+        assert_eq!((attacks.attackers, attacks.weight), ([1, 0], [QUEEN_ATTACK_WEIGHT, 0]));
+        let (_, attacks) = mobility("4k3/8/8/8/8/8/5n2/3QK1r1 w - - 0 1", &DEFAULT_PARAMS);
+        assert_eq!((attacks.attackers, attacks.weight), ([1, 2], [QUEEN_ATTACK_WEIGHT, KNIGHT_ATTACK_WEIGHT + ROOK_ATTACK_WEIGHT]));
+        assert_eq!(king_zone(0o77).count_ones(), 4);
+        assert_eq!(king_zone(0o44).count_ones(), 9);
+    }
+}
@@ -0,0 +1,576 @@
+// Whole position move generation
+//
+// `generate_legal_moves` builds a list of tiles for every piece and then a
+// `Vec` of moves, which is fine for the window but slow where moves are
+// generated millions of times. The generators here walk the board once and
+// write the legal moves of the side to move into a `MoveList`, a fixed array
+// on the stack with room for more moves than any position has, so nothing is
+// allocated.
+//
+// Moves can be generated all at once, or split into captures and quiet
+// moves for searches that look at captures first or only. Promotions to a
+// queen count as captures, as they change the material as much as one does,
+// and the other promotions without a capture count as quiet moves, so the
+// captures and the quiet moves of a position are all of its moves, each
+// once.
+//
+// Only legal moves are generated, without playing any of them to see if the
+// king is left attacked. The pieces giving check and the pieces pinned to
+// the king are found first, by looking out from the king:
+//
+//   - in double check only the king can move
+//   - in check the other pieces can only take the checking piece or step
+//     between it and the king
+//   - a pinned piece can only move along the line through the king and the
+//     piece pinning it
+//   - the king can not move to an attacked tile, counting the tiles behind
+//     it along the ray of a checking piece, as the king would not block it
+//
+// En passant is the one move that takes a piece off a tile other than the
+// one moved to. The taken pawn may be the one giving check, and the two
+// pawns leaving the row together can uncover the king along the row though
+// neither is pinned on its own, which is looked for separately.
+//
+// The castling rights and the en passant square are the ones the position
+// keeps, and a pawn can move two rows from the row pawns start on.
+
+use std::ops::{Deref, DerefMut};
+
+use crate::position::Position;
+use crate::{find_king, get_array_index, get_render_coords, is_square_attacked, Colors, Move, Pieces, Tile};
+
+/// More moves than any legal position has, the most known being 218
+pub const MAX_MOVES : usize = 256;
+
+const KNIGHT_OFFSETS : [(i32, i32); 8] = [(1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2)];
+const KING_OFFSETS : [(i32, i32); 8] = [(1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1), (0, -1), (1, -1)];
+const ROOK_DIRECTIONS : [(i32, i32); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+const BISHOP_DIRECTIONS : [(i32, i32); 4] = [(1, 1), (-1, 1), (-1, -1), (1, -1)];
+
+/// Moves written into a fixed array, which reads as a slice of the moves
+/// written so far
+#[derive(Copy, Clone)]
+pub struct MoveList {
+    moves : [Move; MAX_MOVES],
+    len : usize
+}
+impl MoveList {
+    pub fn new() -> MoveList {
+        MoveList {
+            moves : [Move { from: 0, to: 0, promotion: None }; MAX_MOVES],
+            len : 0
+        }
+    }
+
+    pub fn push(&mut self, mv: Move) {
+        self.moves[self.len] = mv;
+        self.len += 1;
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Keeps only the moves the test passes, in the same order
+    pub fn retain(&mut self, mut keep: impl FnMut(&Move) -> bool) {
+        let mut kept = 0;
+        for i in 0..self.len {
+            if keep(&self.moves[i]) {
+                self.moves[kept] = self.moves[i];
+                kept += 1;
+            }
+        }
+        self.len = kept;
+    }
+}
+impl Default for MoveList {
+    fn default() -> MoveList {
+        MoveList::new()
+    }
+}
+impl Deref for MoveList {
+    type Target = [Move];
+
+    fn deref(&self) -> &[Move] {
+        &self.moves[..self.len]
+    }
+}
+impl DerefMut for MoveList {
+    fn deref_mut(&mut self) -> &mut [Move] {
+        &mut self.moves[..self.len]
+    }
+}
+impl<'a> IntoIterator for &'a MoveList {
+    type Item = &'a Move;
+    type IntoIter = std::slice::Iter<'a, Move>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// What a move has to keep to for the king not to be left attacked, worked
+/// out once for a position
+struct Legality {
+    // The king of the side to move, missing in some test positions
+    king : Option<usize>,
+    // The number of pieces giving check
+    checkers : usize,
+    // Tiles a piece other than the king can move to, which in check are the
+    // checking piece and the tiles between it and the king
+    check_mask : [bool; 64],
+    // The direction from the king of the ray each pinned piece is pinned along
+    pins : [Option<(i32, i32)>; 64],
+    // The board with the king taken off, so that it does not hide the tiles
+    // behind it from a checking piece
+    without_king : [Tile; 64]
+}
+impl Legality {
+    fn new(board: &[Tile; 64], turn: Colors) -> Legality {
+        let mut legality = Legality {
+            king : find_king(board, turn),
+            checkers : 0,
+            check_mask : [true; 64],
+            pins : [None; 64],
+            without_king : *board
+        };
+        let king = match legality.king {
+            Some(king) => king,
+            None => return legality
+        };
+        legality.without_king[king].piece = None;
+        legality.without_king[king].color = None;
+
+        let enemy = turn.opposite();
+        let mut check_mask = [false; 64];
+        let enemy_piece = |index: usize| board[index].piece.filter(|_| board[index].color == Some(enemy));
+
+        // Pawns and knights check from a single tile, and can not be blocked.
+        // Pawns attack diagonally forwards, so look one row towards the enemy
+        let pawn_row = if enemy == Colors::White { 1 } else { -1 };
+        for dx in [-1, 1] {
+            if let Some(index) = step(king, dx, pawn_row).filter(|&index| matches!(enemy_piece(index), Some(Pieces::Pawn { .. }))) {
+                legality.checkers += 1;
+                check_mask[index] = true;
+            }
+        }
+        for (dx, dy) in KNIGHT_OFFSETS {
+            if let Some(index) = step(king, dx, dy).filter(|&index| enemy_piece(index) == Some(Pieces::Knight)) {
+                legality.checkers += 1;
+                check_mask[index] = true;
+            }
+        }
+
+        // Along each ray from the king, a sliding piece of the enemy that
+        // moves that way checks when nothing is between, and pins a piece of
+        // the side to move when that piece is all that is between
+        for (dx, dy) in KING_OFFSETS {
+            let diagonal = dx != 0 && dy != 0;
+            let mut own : Option<usize> = None;
+            let mut ray = step(king, dx, dy);
+            while let Some(index) = ray {
+                if board[index].color == Some(turn) {
+                    if own.is_some() {
+                        break;
+                    }
+                    own = Some(index);
+                } else if let Some(piece) = enemy_piece(index) {
+                    let slides_this_way = match piece {
+                        Pieces::Queen => true,
+                        Pieces::Bishop => diagonal,
+                        Pieces::Rook { .. } => !diagonal,
+                        _ => false
+                    };
+                    match own {
+                        Some(pinned) if slides_this_way => legality.pins[pinned] = Some((dx, dy)),
+                        None if slides_this_way => {
+                            legality.checkers += 1;
+                            let mut between = step(king, dx, dy);
+                            while let Some(tile) = between {
+                                check_mask[tile] = true;
+                                between = if tile == index { None } else { step(tile, dx, dy) };
+                            }
+                        },
+                        _ => ()
+                    }
+                    break;
+                }
+                ray = step(index, dx, dy);
+            }
+        }
+
+        if legality.checkers > 0 {
+            legality.check_mask = check_mask;
+        }
+        legality
+    }
+
+    /// Whether a piece other than the king can move between the tiles
+    #[inline]
+    fn allows(&self, from: usize, to: usize) -> bool {
+        self.checkers < 2 && self.check_mask[to] && self.keeps_pin(from, to)
+    }
+
+    /// Whether a move keeps a pinned piece on the line through the king and
+    /// the piece pinning it, as any move of a piece that is not pinned does
+    #[inline]
+    fn keeps_pin(&self, from: usize, to: usize) -> bool {
+        match (self.pins[from], self.king) {
+            (Some((dx, dy)), Some(king)) => {
+                let [king_x, king_y] = get_render_coords(king);
+                let [x, y] = get_render_coords(to);
+                let (x, y) = (x as i32 - king_x as i32, y as i32 - king_y as i32);
+                x * dy == y * dx
+            },
+            _ => true
+        }
+    }
+
+    /// Whether the king can move to a tile without being attacked on it
+    #[inline]
+    fn king_can_move_to(&self, to: usize, enemy: Colors) -> bool {
+        !is_square_attacked(&self.without_king, to, enemy)
+    }
+
+    /// Whether a pawn can take en passant, which takes the pawn on `taken`
+    /// rather than one on the tile moved to
+    fn allows_en_passant(&self, board: &[Tile; 64], from: usize, to: usize, taken: usize) -> bool {
+        self.checkers < 2
+            && (self.check_mask[to] || self.check_mask[taken])
+            && self.keeps_pin(from, to)
+            && !self.uncovers_row(board, from, taken)
+    }
+
+    /// Whether taking en passant opens the row of the king to a rook or queen,
+    /// with both pawns leaving the row at once
+    fn uncovers_row(&self, board: &[Tile; 64], from: usize, taken: usize) -> bool {
+        let king = match self.king {
+            Some(king) if king >> 3 == from >> 3 => king,
+            _ => return false
+        };
+        let enemy = board[taken].color;
+        for dx in [-1, 1] {
+            let mut ray = step(king, dx, 0);
+            while let Some(index) = ray {
+                if index != from && index != taken {
+                    if let Some(piece) = board[index].piece {
+                        if board[index].color == enemy && matches!(piece, Pieces::Queen | Pieces::Rook { .. }) {
+                            return true;
+                        }
+                        break;
+                    }
+                }
+                ray = step(index, dx, 0);
+            }
+        }
+        false
+    }
+}
+
+/// Which of the moves of a position to generate
+#[derive(Copy, Clone, PartialEq)]
+enum MoveKind {
+    All,
+    Captures,
+    Quiets
+}
+
+/// Writes every legal move of the side to move into the list
+pub fn generate_moves(position: &Position, moves: &mut MoveList) {
+    generate(position, MoveKind::All, moves);
+}
+
+/// Writes the legal captures, en passant included, and the promotions to a
+/// queen of the side to move into the list
+pub fn generate_captures(position: &Position, moves: &mut MoveList) {
+    generate(position, MoveKind::Captures, moves);
+}
+
+/// Writes the legal moves of the side to move that are not captures or
+/// promotions to a queen into the list
+pub fn generate_quiets(position: &Position, moves: &mut MoveList) {
+    generate(position, MoveKind::Quiets, moves);
+}
+
+/// The tile a step away from the given one, or None when off the board
+#[inline]
+fn step(index: usize, dx: i32, dy: i32) -> Option<usize> {
+    let [x, y] = get_render_coords(index);
+    let (x, y) = (x as i32 + dx, y as i32 + dy);
+    if (0..8).contains(&x) && (0..8).contains(&y) {
+        Some(get_array_index(x as usize, y as usize))
+    } else {
+        None
+    }
+}
+
+/// The tiles the knight, bishop, rook or queen on a tile can move to as a bit
+/// set, empty or holding an enemy piece, without looking at pins or checks.
+/// Other pieces have none.
+pub fn piece_moves(board: &[Tile; 64], from: usize) -> u64 {
+    let color = board[from].color;
+    let directions : &[(i32, i32)] = match board[from].piece {
+        Some(Pieces::Knight) => {
+            return KNIGHT_OFFSETS.iter()
+                .filter_map(|&(dx, dy)| step(from, dx, dy))
+                .filter(|&to| board[to].color != color)
+                .fold(0, |moves, to| moves | 1 << to);
+        },
+        Some(Pieces::Bishop) => &BISHOP_DIRECTIONS,
+        Some(Pieces::Rook { .. }) => &ROOK_DIRECTIONS,
+        Some(Pieces::Queen) => &KING_OFFSETS,
+        _ => return 0
+    };
+    let mut moves : u64 = 0;
+    for &(dx, dy) in directions {
+        let mut ray = step(from, dx, dy);
+        while let Some(to) = ray {
+            if board[to].color != color {
+                moves |= 1 << to;
+            }
+            if board[to].piece.is_some() {
+                break;
+            }
+            ray = step(to, dx, dy);
+        }
+    }
+    moves
+}
+
+fn generate(position: &Position, kind: MoveKind, moves: &mut MoveList) {
+    let (board, turn) = (position.board(), position.turn());
+    moves.clear();
+    let legality = Legality::new(board, turn);
+    let captures = kind != MoveKind::Quiets;
+    let quiets = kind != MoveKind::Captures;
+    // Whether a move to the tile is one of the kind wanted
+    let wanted = |to: usize| match board[to].color {
+        None => quiets,
+        Some(color) => captures && color != turn
+    };
+    // Adds a move of a piece other than a pawn or king, if it is wanted and legal
+    let add = |moves: &mut MoveList, from: usize, to: usize| {
+        if wanted(to) && legality.allows(from, to) {
+            moves.push(Move { from, to, promotion: None });
+        }
+    };
+
+    for from in 0..64 {
+        if board[from].color != Some(turn) {
+            continue;
+        }
+        // In double check only the king can move
+        if legality.checkers >= 2 && !matches!(board[from].piece, Some(Pieces::King { .. })) {
+            continue;
+        }
+        match board[from].piece {
+            Some(Pieces::Pawn { .. }) => generate_pawn_moves(position, from, kind, &legality, moves),
+            Some(Pieces::Knight) => {
+                for (dx, dy) in KNIGHT_OFFSETS {
+                    if let Some(to) = step(from, dx, dy) {
+                        add(moves, from, to);
+                    }
+                }
+            },
+            Some(Pieces::King { .. }) => {
+                for (dx, dy) in KING_OFFSETS {
+                    if let Some(to) = step(from, dx, dy).filter(|&to| wanted(to) && legality.king_can_move_to(to, turn.opposite())) {
+                        moves.push(Move { from, to, promotion: None });
+                    }
+                }
+                if quiets && legality.checkers == 0 {
+                    generate_castling(position, from, &legality, moves);
+                }
+            },
+            Some(piece) => {
+                let directions : &[(i32, i32)] = match piece {
+                    Pieces::Rook { .. } => &ROOK_DIRECTIONS,
+                    Pieces::Bishop => &BISHOP_DIRECTIONS,
+                    _ => &KING_OFFSETS
+                };
+                // Sliding pieces move along a ray up to and including the
+                // first occupied tile
+                for &(dx, dy) in directions {
+                    let mut ray = step(from, dx, dy);
+                    while let Some(to) = ray {
+                        add(moves, from, to);
+                        if board[to].piece.is_some() {
+                            break;
+                        }
+                        ray = step(to, dx, dy);
+                    }
+                }
+            },
+            None => ()
+        }
+    }
+}
+
+fn generate_pawn_moves(position: &Position, from: usize, kind: MoveKind, legality: &Legality, moves: &mut MoveList) {
+    let (board, turn) = (position.board(), position.turn());
+    let (forwards, start_row) = if turn == Colors::White { (-1, 6) } else { (1, 1) };
+    // Adds the move, or the promotions, of the kind wanted, once the move is
+    // known to be legal
+    let add = |moves: &mut MoveList, to: usize, capture: bool| {
+        if to >> 3 == 0 || to >> 3 == 7 {
+            // Pawns reaching the final row must promote
+            for promotion in [Pieces::Queen, Pieces::Rook { has_moved: true }, Pieces::Bishop, Pieces::Knight] {
+                let noisy = capture || promotion == Pieces::Queen;
+                if kind == MoveKind::All || noisy == (kind == MoveKind::Captures) {
+                    moves.push(Move { from, to, promotion: Some(promotion) });
+                }
+            }
+        } else if kind == MoveKind::All || capture == (kind == MoveKind::Captures) {
+            moves.push(Move { from, to, promotion: None });
+        }
+    };
+
+    if let Some(to) = step(from, 0, forwards).filter(|&to| board[to].piece.is_none()) {
+        if legality.allows(from, to) {
+            add(moves, to, false);
+        }
+        // A double move needs both tiles in front to be empty
+        if let Some(to) = step(to, 0, forwards).filter(|&to| from >> 3 == start_row && board[to].piece.is_none()) {
+            if legality.allows(from, to) {
+                add(moves, to, false);
+            }
+        }
+    }
+    for dx in [-1, 1] {
+        let to = match step(from, dx, forwards) {
+            Some(to) => to,
+            None => continue
+        };
+        if board[to].color == Some(turn.opposite()) {
+            if legality.allows(from, to) {
+                add(moves, to, true);
+            }
+            continue;
+        }
+        // The pawn that has just double moved past the tile is beside this one
+        let taken = match step(from, dx, 0) {
+            Some(taken) if position.en_passant() == Some(to) => taken,
+            _ => continue
+        };
+        if legality.allows_en_passant(board, from, to, taken) {
+            add(moves, to, true);
+        }
+    }
+}
+
+/// Castling moves of a king out of check with the right to castle, which
+/// can not pass through or end on an attacked tile
+fn generate_castling(position: &Position, from: usize, legality: &Legality, moves: &mut MoveList) {
+    let (board, turn) = (position.board(), position.turn());
+    // (king side, rook index, tiles that must be empty, tile the king passes over, king target)
+    let sides : [(bool, usize, &[usize], usize, usize); 2] = match (turn, from) {
+        (Colors::White, 0o74) => [(true, 0o77, &[0o75, 0o76], 0o75, 0o76), (false, 0o70, &[0o71, 0o72, 0o73], 0o73, 0o72)],
+        (Colors::Black, 0o04) => [(true, 0o07, &[0o05, 0o06], 0o05, 0o06), (false, 0o00, &[0o01, 0o02, 0o03], 0o03, 0o02)],
+        _ => return
+    };
+    for (king_side, rook, between, passed, to) in sides {
+        if position.castling().allows(turn, king_side)
+            && board[rook].color == Some(turn)
+            && matches!(board[rook].piece, Some(Pieces::Rook { .. }))
+            && between.iter().all(|&index| board[index].piece.is_none())
+            && legality.king_can_move_to(passed, turn.opposite())
+            && legality.king_can_move_to(to, turn.opposite()) {
+            moves.push(Move { from, to, promotion: None });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::perft::perft;
+    use crate::{generate_legal_moves, make_move, STARTING_FEN};
+
+    fn perft_fen(fen: &str, depth: u32) -> u64 {
+        perft(&Position::from_fen(fen).unwrap(), depth)
+    }
+
+    /// Perft with the make-and-test generator of the board functions, which
+    /// finds the legal moves another way
+    fn make_and_test_perft(board: &[Tile; 64], turn: Colors, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        generate_legal_moves(board, turn).into_iter().map(|mv| {
+            let mut child = *board;
+            make_move(&mut child, mv);
+            make_and_test_perft(&child, turn.opposite(), depth - 1)
+        }).sum()
+    }
+
+    fn assert_matches_make_and_test(fen: &str, depth: u32) {
+        let position = Position::from_fen(fen).unwrap();
+        assert_eq!(perft(&position, depth), make_and_test_perft(position.board(), position.turn(), depth), "{}", fen);
+    }
+
+    #[test]
+    fn perft_of_the_starting_position() {
+        assert_eq!(perft_fen(STARTING_FEN, 4), 197281);
+    }
+
+    #[test]
+    fn perft_of_kiwipete() {
+        assert_eq!(perft_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", 3), 97862);
+    }
+
+    #[test]
+    fn perft_of_en_passant_pins_along_the_row() {
+        assert_eq!(perft_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - -", 5), 674624);
+    }
+
+    #[test]
+    fn only_the_king_moves_in_double_check() {
+        // The rook and knight both check, so Kd1, Kd2 and Kf1 are all
+        let fen = "4r1k1/8/8/8/8/3n4/8/R3K3 w Q - 0 1";
+        assert_eq!(perft_fen(fen, 1), 3);
+        assert_matches_make_and_test(fen, 4);
+    }
+
+    #[test]
+    fn pinned_piece_gives_discovered_check() {
+        // The bishop is pinned to its king along the long diagonal and shields
+        // the black king from the rook, so its six moves along the pin all
+        // give check, beside eight rook moves and three king moves
+        let fen = "b3k3/8/8/8/4B3/8/8/4R2K w - - 0 1";
+        assert_eq!(perft_fen(fen, 1), 17);
+        assert_matches_make_and_test(fen, 4);
+        assert_eq!(perft_fen("8/8/1P2K3/8/2n5/1q6/8/5k2 b - - 0 1", 5), 1004658);
+    }
+
+    /// Checks that the captures and quiet moves of every position of the
+    /// move tree to the depth are its moves, each once
+    fn assert_captures_and_quiets_are_all_moves(position: &Position, depth: u32) {
+        let (mut all, mut captures, mut quiets) = (MoveList::new(), MoveList::new(), MoveList::new());
+        generate_moves(position, &mut all);
+        generate_captures(position, &mut captures);
+        generate_quiets(position, &mut quiets);
+        assert_eq!(captures.len() + quiets.len(), all.len(), "{}", position.to_fen());
+        for mv in all.iter() {
+            assert!(captures.contains(mv) != quiets.contains(mv), "{}", position.to_fen());
+        }
+        if depth > 0 {
+            for &mv in all.iter() {
+                let mut child = *position;
+                child.play_move(mv);
+                assert_captures_and_quiets_are_all_moves(&child, depth - 1);
+            }
+        }
+    }
+
+    #[test]
+    fn captures_and_quiets_are_all_moves() {
+        for fen in [
+            STARTING_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1"
+        ] {
+            assert_captures_and_quiets_are_all_moves(&Position::from_fen(fen).unwrap(), 2);
+        }
+    }
+}
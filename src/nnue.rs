@@ -0,0 +1,268 @@
+// Efficiently updatable neural network evaluation
+//
+// A small network that can stand in for the handcrafted evaluation. Its
+// inputs are the 768 (colour, piece, tile) features, seen from both sides of
+// the board, feeding one hidden layer per side that is kept in an
+// accumulator. A move only changes a few features, so rather than summing
+// the hidden layer from scratch each accumulator is its parent's with those
+// features taken out and put back in. The search copies the board for every
+// move, so it keeps one accumulator per ply: playing a move fills in the
+// next ply from the current one, and unmaking it is simply going back to the
+// current ply.
+//
+// The hidden layers of the side to move and the other side are put through
+// a squared clipped ReLU and combined by the output layer into a score. All
+// weights are 16 bit integers, the hidden layer scaled by QA and the output
+// weights by QB.
+//
+// A weights file holds little endian 16 bit integers in this order, which is
+// the layout of the simple perspective networks most trainers produce, and
+// may be padded with up to 64 bytes:
+//
+//     feature weights  [768][HIDDEN_SIZE]
+//     feature biases   [HIDDEN_SIZE]
+//     output weights   [2 * HIDDEN_SIZE], side to move first
+//     output bias
+//
+// Features are indexed (own or enemy colour) * 384 + piece * 64 + tile, with
+// pieces ordered pawn, knight, bishop, rook, queen, king and tiles from a1 to
+// h8, the board flipped vertically for Black.
+
+use std::fs;
+
+use crate::{Colors, Pieces, Tile};
+
+pub const HIDDEN_SIZE : usize = 256;
+const INPUT_SIZE : usize = 768;
+const QA : i64 = 255;
+const QB : i64 = 64;
+// Converts the network output to centipawns
+const SCALE : i64 = 400;
+
+pub struct Network {
+    feature_weights : Vec<i16>,
+    feature_biases : Vec<i16>,
+    output_weights : Vec<i16>,
+    output_bias : i16
+}
+
+/// The hidden layer for each side of the board, indexed by colour
+#[derive(Debug, Copy, Clone)]
+pub struct Accumulator {
+    values : [[i16; HIDDEN_SIZE]; 2]
+}
+impl Default for Accumulator {
+    fn default() -> Accumulator {
+        Accumulator {
+            values : [[0; HIDDEN_SIZE]; 2]
+        }
+    }
+}
+
+/// Index of a piece in the feature set
+#[inline]
+fn feature_piece(piece: Pieces) -> usize {
+    match piece {
+        Pieces::Pawn { .. } => 0,
+        Pieces::Knight => 1,
+        Pieces::Bishop => 2,
+        Pieces::Rook { .. } => 3,
+        Pieces::Queen => 4,
+        Pieces::King { .. } => 5
+    }
+}
+
+/// Index of the feature for a tile's occupant, seen from the given side, or
+/// None when the tile is empty
+#[inline]
+fn feature_index(tile: &Tile, index: usize, perspective: Colors) -> Option<usize> {
+    let (piece, color) = (tile.piece?, tile.color?);
+    // The board is indexed from a8, features from a1
+    let tile_index = match perspective {
+        Colors::White => index ^ 0o70,
+        Colors::Black => index
+    };
+    let side = if color == perspective { 0 } else { 1 };
+    Some(side * 384 + feature_piece(piece) * 64 + tile_index)
+}
+
+/// Squared clipped ReLU
+#[inline]
+fn screlu(value: i16) -> i64 {
+    let value = (value as i64).clamp(0, QA);
+    value * value
+}
+
+impl Network {
+    /// Loads a network from a weights file
+    pub fn load(path: &str) -> Result<Network, String> {
+        let bytes = fs::read(path).map_err(|error| format!("can not read {}: {}", path, error))?;
+        let expected = 2 * (INPUT_SIZE * HIDDEN_SIZE + HIDDEN_SIZE + 2 * HIDDEN_SIZE + 1);
+        if bytes.len() < expected || bytes.len() >= expected + 64 {
+            return Err(format!("{} is {} bytes, expected a network of {} bytes", path, bytes.len(), expected));
+        }
+
+        let mut values = bytes.chunks_exact(2).map(|pair| i16::from_le_bytes([pair[0], pair[1]]));
+        let mut take = |count: usize| -> Vec<i16> { values.by_ref().take(count).collect() };
+        let feature_weights = take(INPUT_SIZE * HIDDEN_SIZE);
+        let feature_biases = take(HIDDEN_SIZE);
+        let output_weights = take(2 * HIDDEN_SIZE);
+        let output_bias = take(1)[0];
+
+        Ok(Network {
+            feature_weights,
+            feature_biases,
+            output_weights,
+            output_bias
+        })
+    }
+
+    fn add_feature(&self, values: &mut [i16; HIDDEN_SIZE], feature: usize) {
+        let weights = &self.feature_weights[feature * HIDDEN_SIZE..(feature + 1) * HIDDEN_SIZE];
+        for (value, weight) in values.iter_mut().zip(weights) {
+            *value = value.wrapping_add(*weight);
+        }
+    }
+
+    fn remove_feature(&self, values: &mut [i16; HIDDEN_SIZE], feature: usize) {
+        let weights = &self.feature_weights[feature * HIDDEN_SIZE..(feature + 1) * HIDDEN_SIZE];
+        for (value, weight) in values.iter_mut().zip(weights) {
+            *value = value.wrapping_sub(*weight);
+        }
+    }
+
+    /// Sums the accumulator for a board from scratch
+    pub fn refresh(&self, accumulator: &mut Accumulator, board: &[Tile; 64]) {
+        for perspective in [Colors::White, Colors::Black] {
+            let values = &mut accumulator.values[perspective as usize];
+            values.copy_from_slice(&self.feature_biases);
+            for (index, tile) in board.iter().enumerate() {
+                if let Some(feature) = feature_index(tile, index, perspective) {
+                    self.add_feature(values, feature);
+                }
+            }
+        }
+    }
+
+    /// Fills in the accumulator of the board after a move from the
+    /// accumulator of the board before it, updating only the tiles the move
+    /// changed. This covers captures, castling, en passant and promotions
+    /// alike.
+    pub fn update(&self, accumulator: &mut Accumulator, parent: &Accumulator, before: &[Tile; 64], after: &[Tile; 64]) {
+        *accumulator = *parent;
+        for index in 0..64 {
+            let (old, new) = (&before[index], &after[index]);
+            // Moved flags are not features, only the occupant's colour and kind
+            let old_kind = old.piece.map(feature_piece);
+            let new_kind = new.piece.map(feature_piece);
+            if old_kind == new_kind && old.color == new.color {
+                continue;
+            }
+            for perspective in [Colors::White, Colors::Black] {
+                let values = &mut accumulator.values[perspective as usize];
+                if let Some(feature) = feature_index(old, index, perspective) {
+                    self.remove_feature(values, feature);
+                }
+                if let Some(feature) = feature_index(new, index, perspective) {
+                    self.add_feature(values, feature);
+                }
+            }
+        }
+    }
+
+    /// Scores the position in centipawns from the point of view of `turn`
+    pub fn evaluate(&self, accumulator: &Accumulator, turn: Colors) -> i32 {
+        let us = &accumulator.values[turn as usize];
+        let them = &accumulator.values[turn.opposite() as usize];
+        let (our_weights, their_weights) = self.output_weights.split_at(HIDDEN_SIZE);
+
+        let mut output : i64 = 0;
+        for (value, weight) in us.iter().zip(our_weights) {
+            output += screlu(*value) * *weight as i64;
+        }
+        for (value, weight) in them.iter().zip(their_weights) {
+            output += screlu(*value) * *weight as i64;
+        }
+        // The squared activations carry an extra factor of QA
+        output /= QA;
+        output += self.output_bias as i64;
+        (output * SCALE / (QA * QB)) as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::Position;
+    use crate::square::play_uci_move;
+
+    const NETWORK_SIZE : usize = 2 * (INPUT_SIZE * HIDDEN_SIZE + HIDDEN_SIZE + 2 * HIDDEN_SIZE + 1);
+
+    /// A network with arbitrary weights, enough to tell features apart
+    fn random_network() -> Network {
+        let mut state : u64 = 0x2545_F491_4F6C_DD1D;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % 255) as i16 - 127
+        };
+        Network {
+            feature_weights : (0..INPUT_SIZE * HIDDEN_SIZE).map(|_| next()).collect(),
+            feature_biases : (0..HIDDEN_SIZE).map(|_| next()).collect(),
+            output_weights : (0..2 * HIDDEN_SIZE).map(|_| next()).collect(),
+            output_bias : next()
+        }
+    }
+
+    fn refreshed(network: &Network, board: &[Tile; 64]) -> Accumulator {
+        let mut accumulator = Accumulator::default();
+        network.refresh(&mut accumulator, board);
+        accumulator
+    }
+
+    #[test]
+    fn updates_match_refreshing() {
+        let network = random_network();
+        // En passant, castling on both sides, promotions with and without a
+        // capture, and captures by and of every kind of piece
+        let mut position = Position::from_fen("r3k2r/1P3ppp/8/3pP3/8/8/PPP2PPP/R3K2R w KQkq d6 0 1").unwrap();
+        let mut accumulator = refreshed(&network, position.board());
+        for mv in ["e5d6", "e8g8", "b7a8q", "f8a8", "e1c1", "a8a2", "d6d7", "a2b2", "c1b2", "g8f8", "d7d8n", "f8e7"] {
+            // Every other move from here agrees too
+            for other in position.legal_moves() {
+                let mut after = position;
+                after.play_move(other);
+                let mut updated = Accumulator::default();
+                network.update(&mut updated, &accumulator, position.board(), after.board());
+                assert_eq!(updated.values, refreshed(&network, after.board()).values, "{:?} in {}", other, position.to_fen());
+            }
+            let before = *position.board();
+            play_uci_move(&mut position, mv).unwrap();
+            let parent = accumulator;
+            network.update(&mut accumulator, &parent, &before, position.board());
+            assert_eq!(accumulator.values, refreshed(&network, position.board()).values, "after {}", mv);
+            assert_eq!(network.evaluate(&accumulator, position.turn()), network.evaluate(&refreshed(&network, position.board()), position.turn()));
+        }
+    }
+
+    /// Writes a file of the given size into the temporary directory and
+    /// tries to load it
+    fn load_sized(name: &str, size: usize) -> Result<Network, String> {
+        let path = std::env::temp_dir().join(format!("chess_engine_{}_{}.nnue", name, std::process::id()));
+        fs::write(&path, vec![0u8; size]).unwrap();
+        let loaded = Network::load(path.to_str().unwrap());
+        let _ = fs::remove_file(&path);
+        loaded
+    }
+
+    #[test]
+    fn loads_only_networks_of_the_right_size() {
+        assert!(load_sized("exact", NETWORK_SIZE).is_ok());
+        assert!(load_sized("padded", NETWORK_SIZE + 63).is_ok());
+        assert!(load_sized("short", NETWORK_SIZE - 2).is_err());
+        assert!(load_sized("long", NETWORK_SIZE + 64).is_err());
+        assert!(load_sized("empty", 0).is_err());
+        assert!(Network::load("no such file.nnue").is_err());
+    }
+}
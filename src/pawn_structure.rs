@@ -0,0 +1,311 @@
+// Pawn structure evaluation
+//
+// Pawns are scored for being doubled, isolated, backward, connected or
+// passed. Pawn structure only changes on pawn moves and captures, so scores
+// are cached in a pawn hash table keyed by a hash of the pawns alone. Passed
+// pawns are also scored by how close the kings are to them in the end game,
+// which depends on more than the pawns, so that part is worked out after the
+// cache from the passed pawns it stores.
+//
+// Scores are (middle game, end game) pairs from White's point of view.
+
+use crate::eval_params::EvalParams;
+use crate::zobrist::hash_pawns;
+use crate::{get_array_index, get_render_coords, Colors, Pieces, Tile};
+
+const PAWN_TABLE_ENTRIES : usize = 1 << 14;
+
+#[derive(Debug, Copy, Clone, Default)]
+struct PawnEntry {
+    key : u64,
+    score : [i32; 2],
+    // Board indexes of the passed pawns of both colours as a bit set
+    passed : u64
+}
+
+/// Cache of pawn structure scores, one per search thread
+pub struct PawnHashTable {
+    entries : Vec<PawnEntry>
+}
+impl PawnHashTable {
+    pub fn new() -> PawnHashTable {
+        PawnHashTable {
+            entries : vec![PawnEntry::default(); PAWN_TABLE_ENTRIES]
+        }
+    }
+}
+impl Default for PawnHashTable {
+    fn default() -> PawnHashTable {
+        PawnHashTable::new()
+    }
+}
+
+/// Rank of a row from the point of view of a colour, 0 being its back rank
+#[inline]
+fn relative_rank(row: usize, color: Colors) -> usize {
+    match color {
+        Colors::White => 7 - row,
+        Colors::Black => row
+    }
+}
+
+/// Row a pawn of the colour moves onto when it advances from the given row
+#[inline]
+fn forward(row: usize, color: Colors) -> usize {
+    match color {
+        Colors::White => row - 1,
+        Colors::Black => row + 1
+    }
+}
+
+/// Whether a row is in front of another for a pawn of the colour
+#[inline]
+fn is_ahead(row: usize, of: usize, color: Colors) -> bool {
+    match color {
+        Colors::White => row < of,
+        Colors::Black => row > of
+    }
+}
+
+/// Scores every pawn on the board, without the cache
+fn score_pawns(board: &[Tile; 64], params: &EvalParams) -> PawnEntry {
+    // Rows holding pawns for each colour and file
+    let mut rows : [[Vec<usize>; 8]; 2] = Default::default();
+    for (index, tile) in board.iter().enumerate() {
+        if let (Some(Pieces::Pawn { .. }), Some(color)) = (tile.piece, tile.color) {
+            let [x, y] = get_render_coords(index);
+            rows[color as usize][x].push(y);
+        }
+    }
+
+    let mut entry = PawnEntry::default();
+    for color in [Colors::White, Colors::Black] {
+        let sign = if color == Colors::White { 1 } else { -1 };
+        let own = &rows[color as usize];
+        let enemy = &rows[color.opposite() as usize];
+        let adjacent_files = |file: usize| (file.saturating_sub(1)..=(file + 1).min(7)).filter(move |&x| x != file);
+
+        for file in 0..8 {
+            // Each extra pawn on a file is doubled
+            if own[file].len() > 1 {
+                let count = own[file].len() as i32 - 1;
+                entry.score[0] += sign * params.doubled_pawn[0] * count;
+                entry.score[1] += sign * params.doubled_pawn[1] * count;
+            }
+
+            let isolated = adjacent_files(file).all(|x| own[x].is_empty());
+            for &row in &own[file] {
+                let rank = relative_rank(row, color);
+                let mut score : [i32; 2] = [0, 0];
+
+                if isolated {
+                    score[0] += params.isolated_pawn[0];
+                    score[1] += params.isolated_pawn[1];
+                } else {
+                    // Backward pawns have no pawn beside or behind them on the
+                    // adjacent files and can not advance safely
+                    let unsupported = adjacent_files(file).all(|x| own[x].iter().all(|&other| is_ahead(other, row, color)));
+                    // Enemy pawns attacking the stop tile stand two rows ahead
+                    let stop_attacked = rank < 6 && {
+                        let attacker_row = forward(forward(row, color), color);
+                        adjacent_files(file).any(|x| enemy[x].contains(&attacker_row))
+                    };
+                    if unsupported && stop_attacked {
+                        score[0] += params.backward_pawn[0];
+                        score[1] += params.backward_pawn[1];
+                    }
+                }
+
+                // Connected pawns defend each other or stand side by side
+                let behind = match color {
+                    Colors::White => row + 1,
+                    Colors::Black => row.wrapping_sub(1)
+                };
+                let connected = adjacent_files(file).any(|x| own[x].contains(&row) || own[x].contains(&behind));
+                if connected {
+                    score[0] += params.connected_pawn[rank];
+                    score[1] += params.connected_pawn[rank];
+                }
+
+                // Passed pawns have no enemy pawn in front of them on their own
+                // or the adjacent files, and no own pawn in front on the file
+                let blocked_by_own = own[file].iter().any(|&other| is_ahead(other, row, color));
+                let passed = !blocked_by_own && (file.saturating_sub(1)..=(file + 1).min(7))
+                    .all(|x| enemy[x].iter().all(|&other| !is_ahead(other, row, color)));
+                if passed {
+                    score[0] += params.passed_pawn[0][rank];
+                    score[1] += params.passed_pawn[1][rank];
+                    entry.passed |= 1 << get_array_index(file, row);
+                }
+
+                entry.score[0] += sign * score[0];
+                entry.score[1] += sign * score[1];
+            }
+        }
+    }
+    entry
+}
+
+/// Scores the pawn structure, using the cached score when the same pawns have
+/// been seen before. Returns the score and the passed pawns as a bit set.
+pub fn evaluate_pawns(board: &[Tile; 64], params: &EvalParams, table: Option<&mut PawnHashTable>) -> ([i32; 2], u64) {
+    let table = match table {
+        Some(table) => table,
+        None => {
+            let entry = score_pawns(board, params);
+            return (entry.score, entry.passed);
+        }
+    };
+    let key = hash_pawns(board);
+    let slot = key as usize & (PAWN_TABLE_ENTRIES - 1);
+    if table.entries[slot].key != key {
+        table.entries[slot] = PawnEntry { key, ..score_pawns(board, params) };
+    }
+    let entry = table.entries[slot];
+    (entry.score, entry.passed)
+}
+
+/// Number of king moves between two tiles
+#[inline]
+fn distance(a: usize, b: usize) -> i32 {
+    let [ax, ay] = get_render_coords(a);
+    let [bx, by] = get_render_coords(b);
+    ax.abs_diff(bx).max(ay.abs_diff(by)) as i32
+}
+
+/// End game bonus for passed pawns the enemy king is far from and the own
+/// king is close to, from White's point of view
+pub fn passed_pawn_king_proximity(board: &[Tile; 64], params: &EvalParams, passed: u64, kings: [Option<usize>; 2]) -> i32 {
+    let mut score : i32 = 0;
+    let mut remaining = passed;
+    while remaining != 0 {
+        let index = remaining.trailing_zeros() as usize;
+        remaining &= remaining - 1;
+
+        let color = match board[index].color {
+            Some(color) => color,
+            None => continue
+        };
+        let (own_king, enemy_king) = match (kings[color as usize], kings[color.opposite() as usize]) {
+            (Some(own_king), Some(enemy_king)) => (own_king, enemy_king),
+            _ => continue
+        };
+        let [x, y] = get_render_coords(index);
+        let rank = relative_rank(y, color) as i32;
+        let stop = get_array_index(x, forward(y, color));
+        // Only pawns that have started advancing are worth racing for
+        let weight = (rank - 2).max(0);
+        let bonus = weight * (params.enemy_king_distance * distance(enemy_king, stop) - params.own_king_distance * distance(own_king, stop));
+        score += if color == Colors::White { bonus } else { -bonus };
+    }
+    score
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval_weights::DEFAULT_PARAMS;
+    use crate::position::Position;
+
+    /// The default weights with every pawn structure term taken out, so a
+    /// test can put back only the one it looks at
+    fn without_pawn_terms() -> EvalParams {
+        let mut params = DEFAULT_PARAMS;
+        params.doubled_pawn = [0, 0];
+        params.isolated_pawn = [0, 0];
+        params.backward_pawn = [0, 0];
+        params.connected_pawn = [0; 8];
+        params.passed_pawn = [[0; 8]; 2];
+        params
+    }
+
+    fn score(fen: &str, params: &EvalParams) -> [i32; 2] {
+        evaluate_pawns(Position::from_fen(fen).unwrap().board(), params, None).0
+    }
+
+    #[test]
+    fn scores_doubled_pawns() {
+        let mut params = without_pawn_terms();
+        params.doubled_pawn = [-10, -20];
+        assert_eq!(score("4k3/8/8/8/8/4P3/4P3/4K3 w - - 0 1", &params), [-10, -20]);
+        assert_eq!(score("4k3/8/8/8/4P3/4P3/4P3/4K3 w - - 0 1", &params), [-20, -40]);
+        // Black's doubled pawns count for White
+        assert_eq!(score("4k3/2p5/2p5/8/8/8/8/4K3 w - - 0 1", &params), [10, 20]);
+        assert_eq!(score("4k3/8/8/8/8/8/3PP3/4K3 w - - 0 1", &params), [0, 0]);
+    }
+
+    #[test]
+    fn scores_isolated_pawns() {
+        let mut params = without_pawn_terms();
+        params.isolated_pawn = [-5, -7];
+        assert_eq!(score("4k3/8/8/8/8/8/P1P5/4K3 w - - 0 1", &params), [-10, -14]);
+        assert_eq!(score("4k3/8/8/8/8/8/PPP5/4K3 w - - 0 1", &params), [0, 0]);
+        assert_eq!(score("4k3/7p/8/8/8/8/PPP5/4K3 w - - 0 1", &params), [5, 7]);
+    }
+
+    #[test]
+    fn scores_backward_pawns() {
+        let mut params = without_pawn_terms();
+        params.backward_pawn = [-8, -12];
+        // The c3 pawn has its neighbour ahead of it and d5 guards its stop tile
+        assert_eq!(score("4k3/8/8/3p4/1P6/2P5/8/4K3 w - - 0 1", &params), [-8, -12]);
+        // Without the guard it can catch up
+        assert_eq!(score("4k3/8/8/8/1P6/2P5/8/4K3 w - - 0 1", &params), [0, 0]);
+        // With a neighbour behind it the pawn is not backward
+        assert_eq!(score("4k3/8/8/3p4/1P6/2P5/1P6/4K3 w - - 0 1", &params), [0, 0]);
+    }
+
+    #[test]
+    fn scores_connected_pawns_by_rank() {
+        let mut params = without_pawn_terms();
+        params.connected_pawn = [0, 10, 20, 30, 40, 50, 60, 70];
+        // d3 is defended by c2, which is not defended itself
+        assert_eq!(score("4k3/8/8/8/8/3P4/2P5/4K3 w - - 0 1", &params), [20, 20]);
+        // Side by side pawns both count
+        assert_eq!(score("4k3/8/8/8/8/2PP4/8/4K3 w - - 0 1", &params), [40, 40]);
+        assert_eq!(score("4k3/8/2pp4/8/8/8/8/4K3 w - - 0 1", &params), [-40, -40]);
+        assert_eq!(score("4k3/8/8/8/8/2P1P3/8/4K3 w - - 0 1", &params), [0, 0]);
+    }
+
+    #[test]
+    fn scores_passed_pawns_by_rank() {
+        let mut params = without_pawn_terms();
+        params.passed_pawn = [[0, 5, 10, 15, 20, 25, 30, 0], [0, 10, 20, 30, 40, 50, 60, 0]];
+        let position = Position::from_fen("4k3/8/8/3P4/8/8/8/4K3 w - - 0 1").unwrap();
+        let (scores, passed) = evaluate_pawns(position.board(), &params, None);
+        assert_eq!(scores, [20, 40]);
+        assert_eq!(passed, 1 << 0o33);
+        // A pawn on the next file in front stops it, and is stopped itself
+        assert_eq!(score("4k3/8/4p3/3P4/8/8/8/4K3 w - - 0 1", &params), [0, 0]);
+        // A pawn behind it on the next file does not, and is passed too
+        assert_eq!(score("4k3/8/8/3P4/4p3/8/8/4K3 w - - 0 1", &params), [0, 0]);
+        // Only the front pawn of a doubled pair is passed
+        assert_eq!(score("4k3/8/8/3P4/3P4/8/8/4K3 w - - 0 1", &params), [20, 40]);
+    }
+
+    #[test]
+    fn cached_scores_match_recomputing() {
+        let mut table = PawnHashTable::new();
+        let mut position = Position::default();
+        let (mut hits, mut state) = (0, 0x9E37_79B9_7F4A_7C15u64);
+        for _ in 0..400 {
+            let moves = position.legal_moves();
+            if moves.is_empty() {
+                position = Position::default();
+                continue;
+            }
+            let key = hash_pawns(position.board());
+            if table.entries[key as usize & (PAWN_TABLE_ENTRIES - 1)].key == key {
+                hits += 1;
+            }
+            let cached = evaluate_pawns(position.board(), &DEFAULT_PARAMS, Some(&mut table));
+            assert_eq!(cached, evaluate_pawns(position.board(), &DEFAULT_PARAMS, None), "{}", position.to_fen());
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            position.play_move(moves[state as usize % moves.len()]);
+        }
+        // Most moves are not pawn moves, so most lookups hit
+        assert!(hits > 100, "{} hits", hits);
+    }
+}
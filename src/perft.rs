@@ -0,0 +1,48 @@
+// Move generation testing
+//
+// Perft counts the leaf nodes of the legal move tree to a fixed depth. The
+// counts of well known positions are published, so any difference points
+// straight at a bug in move generation or in playing moves. The count is
+// also split by the first move, which narrows a difference down to a line.
+
+use std::time::Instant;
+
+use crate::movegen::{generate_moves, MoveList};
+use crate::position::Position;
+use crate::square::move_to_uci;
+
+/// Number of leaf nodes of the legal move tree at the given depth
+pub fn perft(position: &Position, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let mut moves = MoveList::new();
+    generate_moves(position, &mut moves);
+    // The moves at the last ply are counted without being played
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+    moves.iter().map(|&mv| {
+        let mut child = *position;
+        child.play_move(mv);
+        perft(&child, depth - 1)
+    }).sum()
+}
+
+/// Prints the perft count of each first move and the total
+pub fn run(position: &Position, depth: u32) {
+    let start = Instant::now();
+    let mut total : u64 = 0;
+    let mut moves = MoveList::new();
+    generate_moves(position, &mut moves);
+    for &mv in moves.iter() {
+        let mut child = *position;
+        child.play_move(mv);
+        let nodes = perft(&child, depth.saturating_sub(1));
+        println!("{}: {}", move_to_uci(mv), nodes);
+        total += nodes;
+    }
+    let millis = start.elapsed().as_millis().max(1);
+    println!();
+    println!("{} nodes in {}ms, {} nodes per second", total, millis, total as u128 * 1000 / millis);
+}
@@ -0,0 +1,692 @@
+// Portable Game Notation
+//
+// Reads the games of a PGN file: the tag pairs, the moves of the main line
+// in Standard Algebraic Notation with their comments and numeric annotation
+// glyphs, and the result. Variations are checked for balance but otherwise
+// skipped, as are move numbers, `;` comments and `%` escape lines.
+//
+// Games are written in export format, with moves in SAN and optional
+// `[%eval]` and `[%emt]` comments for the evaluation after each move and the
+// time spent on it.
+//
+// Errors carry the line and column they were found at, counted from 1. A
+// game with an error is left out, and reading picks up again at the next tag
+// that starts a line after a blank one, so one broken game does not lose the
+// rest of a large collection. Moves are only checked for legality when a
+// game is replayed on the board.
+
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::san::{move_to_san, parse_san, parse_san_strict};
+use crate::search::{is_mate_score, MATE_SCORE};
+use crate::position::Position;
+use crate::{Colors, Move, STARTING_FEN};
+
+/// A problem found in PGN text, at a line and column counted from 1
+#[derive(Debug, Clone)]
+pub struct PgnError {
+    pub line : usize,
+    pub column : usize,
+    pub message : String
+}
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.message)
+    }
+}
+
+/// One move of the main line as written, with where it was written
+#[derive(Debug, Clone, Default)]
+pub struct PgnMove {
+    pub san : String,
+    pub line : usize,
+    pub column : usize,
+    // Numeric annotation glyphs, with "!" and "?" suffixes turned into theirs
+    pub nags : Vec<u8>,
+    pub comment : Option<String>
+}
+
+/// One game read from PGN
+#[derive(Debug, Clone, Default)]
+pub struct PgnGame {
+    pub tags : Vec<(String, String)>,
+    pub moves : Vec<PgnMove>,
+    // "1-0", "0-1", "1/2-1/2" or "*"
+    pub result : String,
+    // Where the game starts in the text
+    pub line : usize,
+    pub column : usize
+}
+
+/// A game played out on the board: every position from the first, and the
+/// moves between them
+pub struct ReplayedGame {
+    pub positions : Vec<Position>,
+    pub moves : Vec<Move>
+}
+
+impl PgnGame {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(tag, _)| tag == name).map(|(_, value)| value.as_str())
+    }
+
+    /// Score of the game for White, or None when it is unfinished
+    pub fn white_score(&self) -> Option<f64> {
+        match self.result.as_str() {
+            "1-0" => Some(1.0),
+            "0-1" => Some(0.0),
+            "1/2-1/2" => Some(0.5),
+            _ => None
+        }
+    }
+
+    /// Plays the moves of the game from its starting position, the FEN tag
+    /// when there is one, failing at the first move that is not legal, or
+    /// when `strict` is set, not written exactly as SAN
+    pub fn replay(&self, strict: bool) -> Result<ReplayedGame, PgnError> {
+        let parse = if strict { parse_san_strict } else { parse_san };
+        let fen = self.tag("FEN").unwrap_or(STARTING_FEN);
+        let mut position = Position::from_fen(fen).map_err(|message| PgnError {
+            line : self.line,
+            column : self.column,
+            message : format!("the FEN tag \"{}\" is not a position: {}", fen, message)
+        })?;
+        let mut replayed = ReplayedGame {
+            positions : vec![position],
+            moves : vec![]
+        };
+        for pgn_move in self.moves.iter() {
+            let mv = parse(&position, &pgn_move.san).map_err(|message| PgnError {
+                line : pgn_move.line,
+                column : pgn_move.column,
+                message
+            })?;
+            position.play_move(mv);
+            replayed.positions.push(position);
+            replayed.moves.push(mv);
+        }
+        Ok(replayed)
+    }
+}
+
+/// Characters of PGN text with the line and column of the next one
+struct Reader<'a> {
+    chars : Peekable<Chars<'a>>,
+    line : usize,
+    column : usize,
+    // Whether the line before the current one held only whitespace
+    after_blank_line : bool,
+    line_is_blank : bool
+}
+impl<'a> Reader<'a> {
+    fn new(text: &'a str) -> Reader<'a> {
+        Reader {
+            chars : text.chars().peekable(),
+            line : 1,
+            column : 1,
+            after_blank_line : true,
+            line_is_blank : true
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let character = self.chars.next()?;
+        if character == '\n' {
+            self.after_blank_line = self.line_is_blank;
+            self.line_is_blank = true;
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.line_is_blank &= character.is_whitespace();
+            self.column += 1;
+        }
+        Some(character)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.next();
+        }
+    }
+
+    fn error(&self, line: usize, column: usize, message: String) -> PgnError {
+        PgnError { line, column, message }
+    }
+
+    /// Skips to the next tag that could start a game
+    fn skip_to_next_game(&mut self) {
+        while let Some(character) = self.peek() {
+            if character == '[' && self.column == 1 && self.after_blank_line {
+                return;
+            }
+            self.next();
+        }
+    }
+}
+
+/// Reads every game of a PGN text, with the errors of the games that could
+/// not be read
+pub fn read_games(text: &str) -> (Vec<PgnGame>, Vec<PgnError>) {
+    let mut reader = Reader::new(text);
+    let mut games : Vec<PgnGame> = vec![];
+    let mut errors : Vec<PgnError> = vec![];
+    loop {
+        match read_game(&mut reader) {
+            Ok(Some(game)) => games.push(game),
+            Ok(None) => break,
+            Err(error) => {
+                errors.push(error);
+                reader.skip_to_next_game();
+            }
+        }
+    }
+    (games, errors)
+}
+
+/// Reads the next game, or None at the end of the text
+fn read_game(reader: &mut Reader) -> Result<Option<PgnGame>, PgnError> {
+    reader.skip_whitespace();
+    let mut game = PgnGame {
+        line : reader.line,
+        column : reader.column,
+        ..PgnGame::default()
+    };
+    // Where each variation being skipped was opened
+    let mut variations : Vec<(usize, usize)> = vec![];
+
+    loop {
+        reader.skip_whitespace();
+        let (line, column) = (reader.line, reader.column);
+        let character = match reader.peek() {
+            Some(character) => character,
+            None if game.tags.is_empty() && game.moves.is_empty() => return Ok(None),
+            None => return Err(reader.error(line, column, "the game has no result".to_string()))
+        };
+        match character {
+            '[' => {
+                if let Some(&(line, column)) = variations.last() {
+                    return Err(reader.error(line, column, "this variation is never closed".to_string()));
+                }
+                if !game.moves.is_empty() {
+                    return Err(reader.error(game.line, game.column, "the game has no result".to_string()));
+                }
+                game.tags.push(read_tag(reader)?);
+            },
+            '{' => {
+                reader.next();
+                let mut comment = String::new();
+                loop {
+                    match reader.next() {
+                        Some('}') => break,
+                        Some(character) => comment.push(character),
+                        None => return Err(reader.error(line, column, "this comment is never closed".to_string()))
+                    }
+                }
+                let comment = comment.split_whitespace().collect::<Vec<&str>>().join(" ");
+                if let Some(last) = game.moves.last_mut().filter(|_| variations.is_empty()) {
+                    last.comment = Some(match last.comment.take() {
+                        Some(previous) => previous + " " + &comment,
+                        None => comment
+                    });
+                }
+            },
+            ';' => {
+                while reader.next().is_some_and(|character| character != '\n') {}
+            },
+            '%' if column == 1 => {
+                while reader.next().is_some_and(|character| character != '\n') {}
+            },
+            '(' => {
+                if game.moves.is_empty() {
+                    return Err(reader.error(line, column, "a variation can not come before the first move".to_string()));
+                }
+                reader.next();
+                variations.push((line, column));
+            },
+            ')' => {
+                if variations.pop().is_none() {
+                    return Err(reader.error(line, column, "there is no variation to close".to_string()));
+                }
+                reader.next();
+            },
+            '$' => {
+                reader.next();
+                let mut number = String::new();
+                while let Some(digit) = reader.peek().filter(char::is_ascii_digit) {
+                    number.push(digit);
+                    reader.next();
+                }
+                let nag = number.parse::<u8>().map_err(|_| reader.error(line, column, format!("${} is not an annotation glyph", number)))?;
+                if let Some(last) = game.moves.last_mut().filter(|_| variations.is_empty()) {
+                    last.nags.push(nag);
+                }
+            },
+            '*' => {
+                reader.next();
+                return finish_game(reader, game, "*", &variations);
+            },
+            character if character.is_ascii_alphanumeric() => {
+                let mut token = String::new();
+                while let Some(character) = reader.peek() {
+                    if !character.is_ascii_alphanumeric() && !"=+#!?-/.".contains(character) {
+                        break;
+                    }
+                    token.push(character);
+                    reader.next();
+                }
+                if matches!(token.as_str(), "1-0" | "0-1" | "1/2-1/2") {
+                    return finish_game(reader, game, &token, &variations);
+                }
+                if let Some(pgn_move) = read_move(&token, line, column)? {
+                    if variations.is_empty() {
+                        game.moves.push(pgn_move);
+                    }
+                }
+            },
+            character => return Err(reader.error(line, column, format!("unexpected character '{}'", character)))
+        }
+    }
+}
+
+/// Ends a game at its result token
+fn finish_game(reader: &Reader, mut game: PgnGame, result: &str, variations: &[(usize, usize)]) -> Result<Option<PgnGame>, PgnError> {
+    if let Some(&(line, column)) = variations.last() {
+        return Err(reader.error(line, column, "this variation is never closed".to_string()));
+    }
+    game.result = result.to_string();
+    Ok(Some(game))
+}
+
+/// Reads a tag pair such as `[White "Carlsen, Magnus"]`
+fn read_tag(reader: &mut Reader) -> Result<(String, String), PgnError> {
+    let (line, column) = (reader.line, reader.column);
+    reader.next();
+    reader.skip_whitespace();
+    let mut name = String::new();
+    while let Some(character) = reader.peek().filter(|&character| character.is_ascii_alphanumeric() || character == '_') {
+        name.push(character);
+        reader.next();
+    }
+    if name.is_empty() {
+        return Err(reader.error(line, column, "the tag has no name".to_string()));
+    }
+    reader.skip_whitespace();
+    if reader.peek() != Some('"') {
+        return Err(reader.error(reader.line, reader.column, format!("the value of the {} tag must be in quotes", name)));
+    }
+    reader.next();
+    let mut value = String::new();
+    loop {
+        match reader.next() {
+            Some('"') => break,
+            Some('\\') => match reader.next() {
+                Some(character @ ('"' | '\\')) => value.push(character),
+                _ => return Err(reader.error(line, column, format!("the value of the {} tag has a bad escape", name)))
+            },
+            Some('\n') | None => return Err(reader.error(line, column, format!("the value of the {} tag is never closed", name))),
+            Some(character) => value.push(character)
+        }
+    }
+    reader.skip_whitespace();
+    if reader.next() != Some(']') {
+        return Err(reader.error(line, column, format!("the {} tag is never closed", name)));
+    }
+    Ok((name, value))
+}
+
+/// Reads a movetext token, which is a move possibly led by its number, or
+/// just a move number
+fn read_move(token: &str, line: usize, column: usize) -> Result<Option<PgnMove>, PgnError> {
+    // "0-0" is castling rather than a move number
+    let (number, san) = if token.starts_with("0-0") {
+        ("", token)
+    } else {
+        token.split_at(token.find(|character: char| !character.is_ascii_digit()).unwrap_or(token.len()))
+    };
+    let san = if number.is_empty() { san } else { san.trim_start_matches('.') };
+    let column = column + token.len() - san.len();
+    let san = san.trim_end_matches("e.p.");
+    if san.is_empty() {
+        return Ok(None);
+    }
+
+    let annotation_start = san.find(['!', '?']).unwrap_or(san.len());
+    let (san, annotation) = san.split_at(annotation_start);
+    let nags = match annotation {
+        "" => vec![],
+        "!" => vec![1],
+        "?" => vec![2],
+        "!!" => vec![3],
+        "??" => vec![4],
+        "!?" => vec![5],
+        "?!" => vec![6],
+        _ => return Err(PgnError { line, column, message: format!("{} is not an annotation", annotation) })
+    };
+    let well_formed = san.starts_with(['K', 'Q', 'R', 'B', 'N', 'O', '0', 'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h'])
+        && san.chars().all(|character| "KQRBNOabcdefgh0123456789x=-+#".contains(character));
+    if !well_formed {
+        return Err(PgnError { line, column, message: format!("{} is not a move", san) });
+    }
+    Ok(Some(PgnMove {
+        san : san.to_string(),
+        line,
+        column,
+        nags,
+        comment : None
+    }))
+}
+
+/// Reads a PGN file and replays one of its games, counted from 1
+pub fn load_game(path: &str, number: usize) -> Result<(PgnGame, ReplayedGame), String> {
+    let text = std::fs::read_to_string(path).map_err(|error| format!("can not read {}: {}", path, error))?;
+    let (games, errors) = read_games(&text);
+    for error in errors.iter() {
+        eprintln!("{}:{}", path, error);
+    }
+    let game = number.checked_sub(1).and_then(|index| games.get(index))
+        .ok_or_else(|| format!("{} has {} games that could be read, not {}", path, games.len(), number))?;
+    let replayed = game.replay(false).map_err(|error| format!("{}:{}", path, error))?;
+    Ok((game.clone(), replayed))
+}
+
+/// Reads and replays every game of a PGN file, printing each problem with
+/// where it is, and fails when there are any
+pub fn check_file(path: &str, strict: bool) -> Result<(), String> {
+    let text = std::fs::read_to_string(path).map_err(|error| format!("can not read {}: {}", path, error))?;
+    let (games, mut errors) = read_games(&text);
+    let mut moves = 0;
+    for game in games.iter() {
+        match game.replay(strict) {
+            Ok(replayed) => moves += replayed.moves.len(),
+            Err(error) => errors.push(error)
+        }
+    }
+    errors.sort_by_key(|error| (error.line, error.column));
+    for error in errors.iter() {
+        println!("{}:{}:{}: {}", path, error.line, error.column, error.message);
+    }
+    println!("{} games read, {} moves played, {} problems", games.len(), moves, errors.len());
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("{} has {} problems", path, errors.len()))
+    }
+}
+
+/// A move played in a game to be written out, with its comment, the time
+/// spent on it and the evaluation after it in centipawns for White
+#[derive(Debug, Clone)]
+pub struct RecordedMove {
+    pub mv : Move,
+    pub comment : Option<String>,
+    pub elapsed : Option<Duration>,
+    pub eval : Option<i32>
+}
+
+/// Today's date as a PGN date tag value, such as "2024.03.17"
+pub fn date_tag() -> String {
+    let days = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs() / 86400) as i64;
+    // Civil date from days since 1970, counting years from March
+    let era_days = days + 719468;
+    let era = era_days.div_euclid(146097);
+    let day_of_era = era_days.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+/// The result of a game that has ended in the last of its positions, by mate,
+/// stalemate, the fifty move rule, threefold repetition or insufficient
+/// material, or None while it goes on
+pub fn game_result(positions: &[Position]) -> Option<&'static str> {
+    let last = positions.last()?;
+    if last.legal_moves().is_empty() {
+        return Some(match (last.is_in_check(), last.turn()) {
+            (false, _) => "1/2-1/2",
+            (true, Colors::White) => "0-1",
+            (true, Colors::Black) => "1-0"
+        });
+    }
+    // The hash leaves out the clocks, so equal hashes are the same position
+    let repetitions = positions.iter().filter(|position| position.hash() == last.hash()).count();
+    if last.halfmove_clock() >= 100 || repetitions >= 3 || last.is_insufficient_material() {
+        return Some("1/2-1/2");
+    }
+    None
+}
+
+/// Formats a score for a `%eval` comment, in pawns or as moves to mate
+fn format_eval(score: i32) -> String {
+    if is_mate_score(score) {
+        let moves = (MATE_SCORE - score.abs() + 1) / 2;
+        format!("#{}", if score > 0 { moves } else { -moves })
+    } else {
+        format!("{:.2}", score as f64 / 100.0)
+    }
+}
+
+/// Writes a game as PGN in export format: the Seven Tag Roster first, with
+/// "?" for any that are missing, then the other tags, then the moves in SAN
+/// wrapped to fewer than 80 columns. Games that do not start from the usual
+/// position get SetUp and FEN tags.
+pub fn write_pgn(tags: &[(String, String)], start: &Position, moves: &[RecordedMove], result: &str) -> String {
+    const ROSTER : [&str; 7] = ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+    let escape = |value: &str| value.replace('\\', "\\\\").replace('"', "\\\"");
+    let tag = |name: &str| tags.iter().find(|(tag, _)| tag == name).map(|(_, value)| value.clone());
+    let mut text = String::new();
+
+    for name in ROSTER {
+        let value = match name {
+            "Result" => result.to_string(),
+            "Date" => tag(name).unwrap_or_else(|| "????.??.??".to_string()),
+            _ => tag(name).unwrap_or_else(|| "?".to_string())
+        };
+        text.push_str(&format!("[{} \"{}\"]\n", name, escape(&value)));
+    }
+    let fen = tag("FEN").unwrap_or_else(|| start.to_fen());
+    // The hash covers everything but the clocks
+    if start.hash() != Position::default().hash() {
+        text.push_str("[SetUp \"1\"]\n");
+        text.push_str(&format!("[FEN \"{}\"]\n", escape(&fen)));
+    }
+    for (name, value) in tags.iter() {
+        if !ROSTER.contains(&name.as_str()) && name != "SetUp" && name != "FEN" {
+            text.push_str(&format!("[{} \"{}\"]\n", name, escape(value)));
+        }
+    }
+    text.push('\n');
+
+    // Movetext tokens, wrapped into lines afterwards
+    let mut tokens : Vec<String> = vec![];
+    let mut move_number : u32 = fen.split_whitespace().nth(5).and_then(|number| number.parse().ok()).unwrap_or(1);
+    let mut position = *start;
+    // Black's move needs its number again after a comment
+    let mut needs_number = true;
+    for recorded in moves.iter() {
+        if position.turn() == Colors::White {
+            tokens.push(format!("{}.", move_number));
+        } else if needs_number {
+            tokens.push(format!("{}...", move_number));
+        }
+        tokens.push(move_to_san(&position, recorded.mv));
+
+        let mut comment : Vec<String> = vec![];
+        if let Some(eval) = recorded.eval {
+            comment.push(format!("[%eval {}]", format_eval(eval)));
+        }
+        if let Some(elapsed) = recorded.elapsed {
+            let seconds = elapsed.as_secs();
+            comment.push(format!("[%emt {}:{:02}:{:02}]", seconds / 3600, seconds / 60 % 60, seconds % 60));
+        }
+        if let Some(text) = &recorded.comment {
+            comment.push(text.replace('}', ")"));
+        }
+        needs_number = !comment.is_empty();
+        if needs_number {
+            tokens.push(format!("{{{}}}", comment.join(" ")));
+        }
+
+        if position.turn() == Colors::Black {
+            move_number += 1;
+        }
+        position.play_move(recorded.mv);
+    }
+    tokens.push(result.to_string());
+
+    let mut line = String::new();
+    for token in tokens {
+        // Comments are split on spaces so that long ones wrap too
+        for word in token.split(' ') {
+            if !line.is_empty() && line.len() + 1 + word.len() >= 80 {
+                text.push_str(&line);
+                text.push('\n');
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(word);
+        }
+    }
+    text.push_str(&line);
+    text.push_str("\n\n");
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The line, column and message of the first error reading the text
+    fn first_error(text: &str) -> (usize, usize, String) {
+        let (_, errors) = read_games(text);
+        let error = errors.first().expect("the text has an error");
+        (error.line, error.column, error.message.clone())
+    }
+
+    /// The line, column and message of the error replaying the only game of
+    /// the text
+    fn replay_error(text: &str) -> (usize, usize, String) {
+        let (games, errors) = read_games(text);
+        assert!(errors.is_empty());
+        let error = games[0].replay(false).err().expect("the game can not be replayed");
+        (error.line, error.column, error.message)
+    }
+
+    #[test]
+    fn reports_where_reading_failed() {
+        assert_eq!(first_error("[Event \"Test\"]\n\n1. e4 {never closed\n"), (3, 7, "this comment is never closed".to_string()));
+        assert_eq!(first_error("1. e4 e5 2. Nf3 (2. Nc3 Nf6 *"), (1, 17, "this variation is never closed".to_string()));
+        assert_eq!(first_error("1. e4 e5 ) *"), (1, 10, "there is no variation to close".to_string()));
+        assert_eq!(first_error("[White Carlsen]\n\n*"), (1, 8, "the value of the White tag must be in quotes".to_string()));
+        assert_eq!(first_error("[White \"Carlsen\n\n*"), (1, 1, "the value of the White tag is never closed".to_string()));
+        assert_eq!(first_error("1. e4 e5!x *"), (1, 7, "!x is not an annotation".to_string()));
+        assert_eq!(first_error("1. e4 e5 2. Zz4 *"), (1, 13, "Zz4 is not a move".to_string()));
+        assert_eq!(first_error("1. e4 $x *"), (1, 7, "$ is not an annotation glyph".to_string()));
+        // Found at the end of the text
+        assert_eq!(first_error("\n  1. e4 e5"), (2, 11, "the game has no result".to_string()));
+    }
+
+    #[test]
+    fn reports_where_replaying_failed() {
+        assert_eq!(replay_error("1. e4 e5\n2. Ke3 Nc6 1-0"), (2, 4, "Ke3 is not legal here".to_string()));
+        assert_eq!(replay_error("1. e3 e6 2. Nc3 Nc6 3.Ne2 *"), (1, 23, "Ne2 is ambiguous here".to_string()));
+        let (line, column, message) = replay_error("[Event \"Test\"]\n[FEN \"hello\"]\n\n*");
+        assert_eq!((line, column), (1, 1));
+        assert!(message.starts_with("the FEN tag \"hello\" is not a position"), "{}", message);
+    }
+
+    /// Plays the moves, given in UCI, from the position
+    fn recorded_moves(start: &Position, moves: &[&str]) -> Vec<RecordedMove> {
+        let mut position = *start;
+        moves.iter().enumerate().map(|(i, uci)| {
+            let mv = crate::square::parse_uci_move(&position, uci).expect("the move is legal");
+            position.play_move(mv);
+            RecordedMove {
+                mv,
+                comment : (i == 1).then(|| "a long comment that {needs} to wrap onto the next line of the movetext".to_string()),
+                elapsed : Some(Duration::from_secs(3700 * i as u64)),
+                eval : Some(if i == 3 { MATE_SCORE - 3 } else { 20 * i as i32 - 30 })
+            }
+        }).collect()
+    }
+
+    #[test]
+    fn written_games_read_back_and_replay() {
+        let tags = vec![("White".to_string(), "Morphy \"Paul\"".to_string()), ("Annotator".to_string(), "Me".to_string())];
+        let starts = [
+            Position::default(),
+            Position::from_fen("r3k2r/1P3ppp/8/3pP3/8/8/PPP2PPP/R3K2R w KQkq d6 0 12").unwrap(),
+            Position::from_fen("4k3/8/8/8/8/8/8/4K2R b K - 3 40").unwrap()
+        ];
+        let lines : [&[&str]; 3] = [
+            &["e2e4", "e7e5", "g1f3", "b8c6", "f1b5", "a7a6", "b5a4", "g8f6", "e1g1"],
+            &["e5d6", "e8g8", "b7a8q", "f8a8", "e1c1"],
+            &["e8d7", "e1g1", "d7e6"]
+        ];
+        for (start, line) in starts.iter().zip(lines) {
+            let moves = recorded_moves(start, line);
+            let text = write_pgn(&tags, start, &moves, "1-0");
+            assert!(text.lines().all(|line| line.len() < 80), "{}", text);
+
+            let (games, errors) = read_games(&text);
+            assert!(errors.is_empty(), "{:?}\n{}", errors, text);
+            assert_eq!(games.len(), 1);
+            let game = &games[0];
+            assert_eq!(game.result, "1-0");
+            assert_eq!(game.tag("White"), Some("Morphy \"Paul\""));
+            assert_eq!(game.tag("Annotator"), Some("Me"));
+            assert_eq!(game.tag("Event"), Some("?"));
+            assert_eq!(game.tag("FEN").is_some(), start.hash() != Position::default().hash());
+
+            let replayed = game.replay(true).expect("the game replays");
+            assert_eq!(replayed.moves, moves.iter().map(|recorded| recorded.mv).collect::<Vec<Move>>());
+            assert_eq!(replayed.positions[0].to_fen(), start.to_fen());
+        }
+    }
+
+    #[test]
+    fn finds_how_games_end() {
+        let ended = |fen: &str| game_result(&[Position::from_fen(fen).unwrap()]);
+        assert_eq!(ended(STARTING_FEN), None);
+        assert_eq!(ended("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1"), None);
+        assert_eq!(ended("R5k1/5ppp/8/8/8/8/8/6K1 b - - 1 1"), Some("1-0"));
+        assert_eq!(ended("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1"), Some("1/2-1/2"));
+        assert_eq!(ended("7k/8/8/8/8/8/8/KQ6 w - - 100 80"), Some("1/2-1/2"));
+        assert_eq!(ended("7k/8/8/8/8/8/8/KN6 w - - 0 80"), Some("1/2-1/2"));
+        assert_eq!(ended("7k/8/8/8/8/8/8/KR6 w - - 0 80"), None);
+
+        // The knights go out and back twice to repeat the start a third time
+        let mut positions = vec![Position::default()];
+        for (i, uci) in ["g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1", "f6g8"].iter().enumerate() {
+            assert_eq!(game_result(&positions), None, "after {} plies", i);
+            let mut position = *positions.last().unwrap();
+            position.play_move(crate::square::parse_uci_move(&position, uci).unwrap());
+            positions.push(position);
+        }
+        assert_eq!(game_result(&positions), Some("1/2-1/2"));
+    }
+
+    #[test]
+    fn reading_carries_on_after_a_broken_game() {
+        let text = "[Event \"Broken\"]\n\n1. e4 e5 ) *\n\n[Event \"Fine\"]\n\n1. d4 d5 1/2-1/2\n";
+        let (games, errors) = read_games(text);
+        assert_eq!(errors.len(), 1);
+        assert_eq!((errors[0].line, errors[0].column), (3, 10));
+        assert_eq!(games.len(), 1);
+        assert_eq!(games[0].tag("Event"), Some("Fine"));
+        assert_eq!(games[0].moves.len(), 2);
+        assert_eq!((games[0].moves[1].line, games[0].moves[1].column), (7, 7));
+    }
+}
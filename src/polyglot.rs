@@ -0,0 +1,220 @@
+// Polyglot opening books
+//
+// A Polyglot book is a file of 16 byte entries sorted by position key, each
+// holding the key, a move and its weight, all big endian. The key is a
+// Zobrist hash made with the standard Polyglot random keys, see
+// `polyglot_keys`, so that books made by any tool work here. Moves found in
+// the book are played straight away, picked at random in proportion to their
+// weights, or always the heaviest.
+
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::polyglot_keys::RANDOM_KEYS;
+use crate::position::Position;
+use crate::{Colors, Move, Pieces, Tile};
+
+const ENTRY_SIZE : usize = 16;
+// Offsets of the castling, en passant and turn keys
+const CASTLING_KEYS : usize = 768;
+const EN_PASSANT_KEYS : usize = 772;
+const TURN_KEY : usize = 780;
+
+/// Polyglot key of the position
+pub fn polyglot_key(position: &Position) -> u64 {
+    let (board, turn) = (position.board(), position.turn());
+    let mut key : u64 = 0;
+    for (index, tile) in board.iter().enumerate() {
+        let (piece, color) = match (tile.piece, tile.color) {
+            (Some(piece), Some(color)) => (piece, color),
+            _ => continue
+        };
+        let role = match piece {
+            Pieces::Pawn { .. } => 0,
+            Pieces::Knight => 1,
+            Pieces::Bishop => 2,
+            Pieces::Rook { .. } => 3,
+            Pieces::Queen => 4,
+            Pieces::King { .. } => 5
+        };
+        let kind = 2 * role + (color == Colors::White) as usize;
+        // Board indexes run from a8, Polyglot squares from a1
+        key ^= RANDOM_KEYS[64 * kind + (index ^ 0o70)];
+    }
+
+    // (key offset, colour, king side)
+    let rights : [(usize, Colors, bool); 4] = [
+        (0, Colors::White, true),
+        (1, Colors::White, false),
+        (2, Colors::Black, true),
+        (3, Colors::Black, false)
+    ];
+    for (offset, color, king_side) in rights {
+        if position.castling().allows(color, king_side) {
+            key ^= RANDOM_KEYS[CASTLING_KEYS + offset];
+        }
+    }
+
+    // The en passant file only counts when a pawn can actually take, from
+    // beside the pawn that passed over the en passant tile
+    if let Some(target) = position.en_passant() {
+        let pawn = if turn == Colors::White { target + 8 } else { target - 8 };
+        let file = pawn & 7;
+        let can_take = [file.checked_sub(1), Some(file + 1).filter(|&file| file < 8)].into_iter().flatten().any(|file| {
+            let tile = board[(pawn & !7) + file];
+            tile.color == Some(turn) && matches!(tile.piece, Some(Pieces::Pawn { .. }))
+        });
+        if can_take {
+            key ^= RANDOM_KEYS[EN_PASSANT_KEYS + file];
+        }
+    }
+
+    if turn == Colors::White {
+        key ^= RANDOM_KEYS[TURN_KEY];
+    }
+    key
+}
+
+/// Opening book read from a Polyglot file
+pub struct Book {
+    // Key, move and weight of each entry, sorted by key
+    entries : Vec<(u64, u16, u16)>,
+    random_state : u64
+}
+impl Book {
+    pub fn load(path: &str) -> Result<Book, String> {
+        let data = fs::read(path).map_err(|error| format!("can not read {}: {}", path, error))?;
+        if data.len() % ENTRY_SIZE != 0 {
+            return Err(format!("{} is not a Polyglot book", path));
+        }
+        let entries : Vec<(u64, u16, u16)> = data.chunks_exact(ENTRY_SIZE).map(|entry| {
+            let key = u64::from_be_bytes([entry[0], entry[1], entry[2], entry[3], entry[4], entry[5], entry[6], entry[7]]);
+            (key, u16::from_be_bytes([entry[8], entry[9]]), u16::from_be_bytes([entry[10], entry[11]]))
+        }).collect();
+        if !entries.is_sorted_by_key(|&(key, _, _)| key) {
+            return Err(format!("{} is not sorted by position key", path));
+        }
+        let seed = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_nanos() as u64);
+        Ok(Book {
+            entries,
+            random_state : seed | 1
+        })
+    }
+
+    /// Number of entries in the book
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the book has no entries
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The book moves of the position that are legal, with their weights
+    pub fn moves(&self, position: &Position) -> Vec<(Move, u16)> {
+        let key = polyglot_key(position);
+        let start = self.entries.partition_point(|&(entry_key, _, _)| entry_key < key);
+        let legal_moves = position.legal_moves();
+        self.entries[start..].iter()
+            .take_while(|&&(entry_key, _, _)| entry_key == key)
+            .filter_map(|&(_, mv, weight)| {
+                let mv = decode_move(position.board(), mv);
+                legal_moves.contains(&mv).then_some((mv, weight))
+            })
+            .collect()
+    }
+
+    /// Picks a book move for the position, the heaviest when `best` is set
+    /// and otherwise at random in proportion to the weights
+    pub fn choose(&mut self, position: &Position, best: bool) -> Option<Move> {
+        let moves = self.moves(position);
+        if best {
+            return moves.iter().max_by_key(|&&(_, weight)| weight).map(|&(mv, _)| mv);
+        }
+        let total : u64 = moves.iter().map(|&(_, weight)| weight as u64).sum();
+        if total == 0 {
+            return moves.first().map(|&(mv, _)| mv);
+        }
+        self.random_state ^= self.random_state << 13;
+        self.random_state ^= self.random_state >> 7;
+        self.random_state ^= self.random_state << 17;
+        let mut pick = self.random_state % total;
+        for (mv, weight) in moves {
+            if pick < weight as u64 {
+                return Some(mv);
+            }
+            pick -= weight as u64;
+        }
+        None
+    }
+}
+
+/// Turns a move on the board into a Polyglot move
+pub fn encode_move(board: &[Tile; 64], mv: Move) -> u16 {
+    let mut to = mv.to;
+    // Castling is written as the king taking its own rook
+    if matches!(board[mv.from].piece, Some(Pieces::King { .. })) && mv.from.abs_diff(mv.to) == 2 {
+        to = if mv.to > mv.from { mv.from + 3 } else { mv.from - 4 };
+    }
+    let square = |index: usize| (((7 - (index >> 3)) << 3) | (index & 7)) as u16;
+    let promotion : u16 = match mv.promotion {
+        Some(Pieces::Knight) => 1,
+        Some(Pieces::Bishop) => 2,
+        Some(Pieces::Rook { .. }) => 3,
+        Some(Pieces::Queen) => 4,
+        _ => 0
+    };
+    promotion << 12 | square(mv.from) << 6 | square(to)
+}
+
+/// Turns a Polyglot move into a move on the board. Castling is stored as the
+/// king taking its own rook.
+fn decode_move(board: &[Tile; 64], mv: u16) -> Move {
+    let square = |file: u16, rank: u16| (((7 - rank) << 3) | file) as usize;
+    let from = square((mv >> 6) & 7, (mv >> 9) & 7);
+    let mut to = square(mv & 7, (mv >> 3) & 7);
+    let promotion = match (mv >> 12) & 7 {
+        1 => Some(Pieces::Knight),
+        2 => Some(Pieces::Bishop),
+        3 => Some(Pieces::Rook { has_moved: true }),
+        4 => Some(Pieces::Queen),
+        _ => None
+    };
+    let castling = matches!(board[from].piece, Some(Pieces::King { has_moved: false }))
+        && board[to].color == board[from].color
+        && matches!(board[to].piece, Some(Pieces::Rook { .. }));
+    if castling {
+        to = if to > from { from + 2 } else { from - 2 };
+    }
+    Move { from, to, promotion }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::square::play_uci_move;
+
+    /// The key after each line of the Polyglot book format description
+    #[test]
+    fn matches_the_published_keys() {
+        let lines : [(&str, u64); 9] = [
+            ("", 0x463b96181691fc9c),
+            ("e2e4", 0x823c9b50fd114196),
+            ("e2e4 d7d5", 0x0756b94461c50fb0),
+            ("e2e4 d7d5 e4e5", 0x662fafb965db29d4),
+            ("e2e4 d7d5 e4e5 f7f5", 0x22a48b5a8e47ff78),
+            ("e2e4 d7d5 e4e5 f7f5 e1e2", 0x652a607ca3f242c1),
+            ("e2e4 d7d5 e4e5 f7f5 e1e2 e8f7", 0x00fdd303c946bdd9),
+            ("a2a4 b7b5 h2h4 b5b4 c2c4", 0x3c8123ea7b067637),
+            ("a2a4 b7b5 h2h4 b5b4 c2c4 b4c3 a1a3", 0x5c3f9b829b279560)
+        ];
+        for (moves, key) in lines {
+            let mut position = Position::default();
+            for mv in moves.split_whitespace() {
+                play_uci_move(&mut position, mv).unwrap();
+            }
+            assert_eq!(polyglot_key(&position), key, "after {}", moves);
+        }
+    }
+}
@@ -0,0 +1,482 @@
+// Positions
+//
+// A position is everything needed to carry a game on from it: the board, the
+// side to move, the castling rights, the en passant square, the two clocks of
+// FEN and the Zobrist hash, so a position can be handed around on its own
+// without anything else having to remember whose turn it is.
+//
+// The position owns this state. Move generation, see `movegen`, reads the
+// castling rights and en passant square from it, FEN is read into and written
+// out of its fields, and playing a move works the new rights, en passant
+// square and hash out from the move alone. The hash is kept up to date by
+// taking the keys of whatever the move changes out of it and putting the new
+// ones in, rather than hashing the whole board again.
+//
+// The pieces of the board still carry the `has_moved` and `en_passantable`
+// flags read by the board functions that came before positions, such as the
+// move highlighting of the window. Playing a move keeps them in step,
+// but nothing here reads them back except `from_board`, which turns a bare
+// board into a position.
+
+use std::fmt;
+
+use crate::movegen::{generate_moves, MoveList};
+use crate::zobrist::{castling_key, en_passant_key, piece_key, side_key};
+use crate::{en_passant_square, get_square_name, is_in_check, make_move, parse_square_name, read_full_fen_string, write_fen_placement, Colors, Move, Pieces, Tile, STARTING_FEN};
+
+// The king and rook tiles and the letter of each castling right, in the
+// order of FEN
+const CASTLING : [(usize, usize, char); 4] = [(0o74, 0o77, 'K'), (0o74, 0o70, 'Q'), (0o04, 0o07, 'k'), (0o04, 0o00, 'q')];
+
+/// Which sides may still castle, and to which side of the board
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct CastlingRights {
+    pub white_king_side : bool,
+    pub white_queen_side : bool,
+    pub black_king_side : bool,
+    pub black_queen_side : bool
+}
+impl CastlingRights {
+    /// The rights of a board, for each king and rook that have not moved
+    pub fn from_board(board: &[Tile; 64]) -> CastlingRights {
+        let unmoved = |color: Colors, king: usize, rook: usize| {
+            board[king].color == Some(color)
+                && board[king].piece == Some(Pieces::King { has_moved: false })
+                && board[rook].color == Some(color)
+                && board[rook].piece == Some(Pieces::Rook { has_moved: false })
+        };
+        CastlingRights {
+            white_king_side : unmoved(Colors::White, 0o74, 0o77),
+            white_queen_side : unmoved(Colors::White, 0o74, 0o70),
+            black_king_side : unmoved(Colors::Black, 0o04, 0o07),
+            black_queen_side : unmoved(Colors::Black, 0o04, 0o00)
+        }
+    }
+
+    /// Reads the castling field of FEN, such as "KQkq" or "-"
+    fn from_fen_field(field: &str) -> Result<CastlingRights, String> {
+        let mut rights = [false; 4];
+        if field != "-" {
+            for letter in field.chars() {
+                match CASTLING.iter().position(|&(_, _, right)| right == letter) {
+                    Some(right) if !rights[right] => rights[right] = true,
+                    _ => return Err(format!("the castling rights \"{}\" are not a set of K, Q, k and q", field))
+                }
+            }
+        }
+        Ok(CastlingRights::from_array(rights))
+    }
+
+    fn from_array(rights: [bool; 4]) -> CastlingRights {
+        CastlingRights {
+            white_king_side : rights[0],
+            white_queen_side : rights[1],
+            black_king_side : rights[2],
+            black_queen_side : rights[3]
+        }
+    }
+
+    /// The rights in the order of FEN: K, Q, k and q
+    fn to_array(self) -> [bool; 4] {
+        [self.white_king_side, self.white_queen_side, self.black_king_side, self.black_queen_side]
+    }
+
+    /// Whether a side may still castle towards the king side or queen side
+    pub fn allows(&self, color: Colors, king_side: bool) -> bool {
+        match (color, king_side) {
+            (Colors::White, true) => self.white_king_side,
+            (Colors::White, false) => self.white_queen_side,
+            (Colors::Black, true) => self.black_king_side,
+            (Colors::Black, false) => self.black_queen_side
+        }
+    }
+
+    /// The rights left after a move between the tiles, as a king or rook
+    /// loses its rights once it moves, and a rook once it is taken
+    fn after_move(self, from: usize, to: usize) -> CastlingRights {
+        let mut rights = self.to_array();
+        for (right, &(king, rook, _)) in CASTLING.iter().enumerate() {
+            if from == king || from == rook || to == rook {
+                rights[right] = false;
+            }
+        }
+        CastlingRights::from_array(rights)
+    }
+}
+/// Written as in FEN, such as "KQkq", or "-" when there are none
+impl fmt::Display for CastlingRights {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text : String = self.to_array().iter().zip(CASTLING.iter())
+            .filter(|&(&right, _)| right)
+            .map(|(_, &(_, _, letter))| letter)
+            .collect();
+        write!(f, "{}", if text.is_empty() { "-" } else { &text })
+    }
+}
+
+/// A board with the side to move and the rest of the state of the game
+#[derive(Debug, Copy, Clone)]
+pub struct Position {
+    board : [Tile; 64],
+    turn : Colors,
+    castling : CastlingRights,
+    en_passant : Option<usize>,
+    halfmove_clock : u32,
+    fullmove_number : u32,
+    hash : u64
+}
+impl Position {
+    /// Reads a position from FEN. The piece placement and side to move must
+    /// be there, while the castling rights, en passant square and clocks may
+    /// be left off, as in EPD, and then take their usual values of "-", "-",
+    /// 0 and 1
+    pub fn from_fen(fen: &str) -> Result<Position, String> {
+        let fields : Vec<&str> = fen.split_whitespace().collect();
+        if fields.len() > 6 {
+            return Err(format!("\"{}\" has more than the six fields of FEN", fen));
+        }
+        let placement = fields.first().copied().unwrap_or("");
+        if !is_valid_placement(placement) {
+            return Err(format!("\"{}\" is not eight rows of eight tiles", placement));
+        }
+        let turn = match fields.get(1).copied() {
+            Some("w") => Colors::White,
+            Some("b") => Colors::Black,
+            Some(other) => return Err(format!("the side to move is \"{}\" rather than w or b", other)),
+            None => return Err("the side to move is missing".to_string())
+        };
+        let castling = CastlingRights::from_fen_field(fields.get(2).copied().unwrap_or("-"))?;
+        let en_passant = match fields.get(3).copied().unwrap_or("-") {
+            "-" => None,
+            name => Some(parse_square_name(name).ok_or_else(|| format!("the en passant square \"{}\" is not a square", name))?)
+        };
+        let halfmove_clock = match fields.get(4) {
+            Some(clock) => clock.parse::<u32>().map_err(|_| format!("the halfmove clock \"{}\" is not a number", clock))?,
+            None => 0
+        };
+        let fullmove_number = match fields.get(5) {
+            Some(number) => number.parse::<u32>().ok().filter(|&number| number >= 1)
+                .ok_or_else(|| format!("the fullmove number \"{}\" is not a number from 1", number))?,
+            None => 1
+        };
+
+        // The flags of the pieces are set from the same fields
+        let (board, _) = read_full_fen_string(fen);
+        for color in [Colors::White, Colors::Black] {
+            let kings = board.iter().filter(|tile| tile.color == Some(color) && matches!(tile.piece, Some(Pieces::King { .. }))).count();
+            if kings != 1 {
+                return Err(format!("{:?} has {} kings rather than one", color, kings));
+            }
+        }
+        let back_ranks = board[0o00..0o10].iter().chain(board[0o70..0o100].iter());
+        if back_ranks.into_iter().any(|tile| matches!(tile.piece, Some(Pieces::Pawn { .. }))) {
+            return Err("a pawn stands on the first or last row".to_string());
+        }
+        for (&right, &(king, rook, letter)) in castling.to_array().iter().zip(CASTLING.iter()) {
+            let color = if letter.is_ascii_uppercase() { Colors::White } else { Colors::Black };
+            let in_place = board[king].color == Some(color)
+                && matches!(board[king].piece, Some(Pieces::King { .. }))
+                && board[rook].color == Some(color)
+                && matches!(board[rook].piece, Some(Pieces::Rook { .. }));
+            if right && !in_place {
+                return Err(format!("castling right {} needs the king and rook on their starting tiles", letter));
+            }
+        }
+        if let Some(square) = en_passant {
+            // The pawn that just moved two rows stands in front of the
+            // square, and the tile it came from behind it is empty
+            let (row, forwards) = if turn == Colors::White { (2, 0o10) } else { (5, -0o10) };
+            let (pawn, origin) = ((square as i32 + forwards) as usize, (square as i32 - forwards) as usize);
+            let passed = square >> 3 == row
+                && board[pawn].color == Some(turn.opposite())
+                && matches!(board[pawn].piece, Some(Pieces::Pawn { .. }))
+                && board[square].piece.is_none()
+                && board[origin].piece.is_none();
+            if !passed {
+                return Err(format!("no pawn can be taken en passant on {}", get_square_name(square)));
+            }
+        }
+        if is_in_check(&board, turn.opposite()) {
+            return Err("the side that just moved is in check".to_string());
+        }
+        Ok(Position::new(board, turn, castling, en_passant, halfmove_clock, fullmove_number))
+    }
+
+    /// A board with the side to move, with clocks as if the game started on
+    /// it, and the castling rights and en passant square its flags give
+    pub fn from_board(board: [Tile; 64], turn: Colors) -> Position {
+        Position::new(board, turn, CastlingRights::from_board(&board), en_passant_square(&board, turn), 0, 1)
+    }
+
+    fn new(board: [Tile; 64], turn: Colors, castling: CastlingRights, en_passant: Option<usize>, halfmove_clock: u32, fullmove_number: u32) -> Position {
+        let mut position = Position {
+            board,
+            turn,
+            castling,
+            en_passant,
+            halfmove_clock,
+            fullmove_number,
+            hash : 0
+        };
+        position.hash = position.hash_from_scratch();
+        position
+    }
+
+    /// The hash worked out from the whole position, which playing moves
+    /// keeps equal to the hash it updates
+    fn hash_from_scratch(&self) -> u64 {
+        let mut hash : u64 = 0;
+        for (index, tile) in self.board.iter().enumerate() {
+            if let (Some(piece), Some(color)) = (tile.piece, tile.color) {
+                hash ^= piece_key(piece, color, index);
+            }
+        }
+        if self.turn == Colors::Black {
+            hash ^= side_key();
+        }
+        for (right, &allowed) in self.castling.to_array().iter().enumerate() {
+            if allowed {
+                hash ^= castling_key(right);
+            }
+        }
+        if let Some(square) = self.en_passant {
+            hash ^= en_passant_key(square % 8);
+        }
+        hash
+    }
+
+    /// Writes the position as FEN
+    pub fn to_fen(&self) -> String {
+        format!(
+            "{} {} {} {} {} {}",
+            write_fen_placement(&self.board),
+            if self.turn == Colors::White { "w" } else { "b" },
+            self.castling,
+            self.en_passant.map_or(String::from("-"), get_square_name),
+            self.halfmove_clock,
+            self.fullmove_number
+        )
+    }
+
+    pub fn board(&self) -> &[Tile; 64] {
+        &self.board
+    }
+
+    pub fn turn(&self) -> Colors {
+        self.turn
+    }
+
+    pub fn castling(&self) -> CastlingRights {
+        self.castling
+    }
+
+    /// The tile a pawn can be taken on en passant, the one the pawn that just
+    /// moved two rows passed over
+    pub fn en_passant(&self) -> Option<usize> {
+        self.en_passant
+    }
+
+    /// Plies since the last capture or pawn move, for the fifty move rule
+    pub fn halfmove_clock(&self) -> u32 {
+        self.halfmove_clock
+    }
+
+    /// The number of the move, which goes up after each move of Black
+    pub fn fullmove_number(&self) -> u32 {
+        self.fullmove_number
+    }
+
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    /// Every legal move of the side to move
+    pub fn legal_moves(&self) -> Vec<Move> {
+        let mut moves = MoveList::new();
+        generate_moves(self, &mut moves);
+        moves.to_vec()
+    }
+
+    /// Whether the king of the side to move is attacked
+    pub fn is_in_check(&self) -> bool {
+        is_in_check(&self.board, self.turn)
+    }
+
+    /// Whether neither side has enough material left to mate
+    pub fn is_insufficient_material(&self) -> bool {
+        let mut minor_pieces = 0;
+        for tile in self.board.iter() {
+            match tile.piece {
+                Some(Pieces::King { .. }) | None => (),
+                Some(Pieces::Knight | Pieces::Bishop) => minor_pieces += 1,
+                Some(_) => return false
+            }
+        }
+        minor_pieces <= 1
+    }
+
+    /// Whether a move takes a piece or moves a pawn, which resets the fifty
+    /// move clock
+    pub fn resets_halfmove_clock(&self, mv: Move) -> bool {
+        self.board[mv.to].piece.is_some() || matches!(self.board[mv.from].piece, Some(Pieces::Pawn { .. }))
+    }
+
+    /// Plays a move of the side to move, which must be legal
+    pub fn play_move(&mut self, mv: Move) {
+        let (from, to, turn) = (mv.from, mv.to, self.turn);
+        let piece = self.board[from].piece.expect("a move starts from a piece");
+        self.halfmove_clock = if self.resets_halfmove_clock(mv) { 0 } else { self.halfmove_clock + 1 };
+        if turn == Colors::Black {
+            self.fullmove_number += 1;
+        }
+
+        // Take the pieces the move changes out of the hash and put them back
+        // where they end up
+        self.hash ^= piece_key(piece, turn, from) ^ piece_key(mv.promotion.unwrap_or(piece), turn, to);
+        if let Some(taken) = self.board[to].piece {
+            self.hash ^= piece_key(taken, turn.opposite(), to);
+        }
+        match piece {
+            // En passant takes the pawn beside the one moving
+            Pieces::Pawn { .. } if Some(to) == self.en_passant && from % 8 != to % 8 => {
+                let taken = from - from % 8 + to % 8;
+                self.hash ^= piece_key(piece, turn.opposite(), taken);
+            },
+            // Castling moves the rook over the king
+            Pieces::King { .. } if from.abs_diff(to) == 2 => {
+                let (rook_from, rook_to) = if to > from { (from + 3, from + 1) } else { (from - 4, from - 1) };
+                let rook = Pieces::Rook { has_moved: true };
+                self.hash ^= piece_key(rook, turn, rook_from) ^ piece_key(rook, turn, rook_to);
+            },
+            _ => ()
+        }
+
+        let castling = self.castling.after_move(from, to);
+        for (right, (&before, &after)) in self.castling.to_array().iter().zip(castling.to_array().iter()).enumerate() {
+            if before != after {
+                self.hash ^= castling_key(right);
+            }
+        }
+        self.castling = castling;
+
+        if let Some(square) = self.en_passant {
+            self.hash ^= en_passant_key(square % 8);
+        }
+        // A pawn moving two rows can be taken on the tile it passes over
+        self.en_passant = match piece {
+            Pieces::Pawn { .. } if from.abs_diff(to) == 0o20 => Some((from + to) / 2),
+            _ => None
+        };
+        if let Some(square) = self.en_passant {
+            self.hash ^= en_passant_key(square % 8);
+        }
+
+        self.hash ^= side_key();
+        make_move(&mut self.board, mv);
+        self.turn = turn.opposite();
+    }
+}
+/// The usual starting position
+impl Default for Position {
+    fn default() -> Position {
+        Position::from_fen(STARTING_FEN).expect("the starting position is valid FEN")
+    }
+}
+
+/// Whether the piece placement of a FEN string fills eight rows of eight
+/// tiles, which reading it depends on
+pub fn is_valid_placement(fen: &str) -> bool {
+    let placement = fen.split_whitespace().next().unwrap_or("");
+    let rows : Vec<&str> = placement.split('/').collect();
+    rows.len() == 8 && rows.iter().all(|row| {
+        let mut width = 0;
+        for character in row.chars() {
+            match character {
+                '1'..='8' => width += character as usize - '0' as usize,
+                'K' | 'Q' | 'R' | 'B' | 'N' | 'P' | 'k' | 'q' | 'r' | 'b' | 'n' | 'p' => width += 1,
+                _ => return false
+            }
+        }
+        width == 8
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zobrist::hash_board;
+
+    #[test]
+    fn reads_and_writes_fen() {
+        for fen in [
+            STARTING_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 3",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 13 40"
+        ] {
+            assert_eq!(Position::from_fen(fen).unwrap().to_fen(), fen);
+        }
+    }
+
+    /// Checks every position of the move tree to the depth against the
+    /// same position worked out from scratch from its board
+    fn check_kept_state(position: &Position, depth: u32) {
+        let board = position.board();
+        assert_eq!(position.hash(), position.hash_from_scratch(), "{}", position.to_fen());
+        assert_eq!(position.hash(), hash_board(board, position.turn()), "{}", position.to_fen());
+        assert_eq!(position.castling(), CastlingRights::from_board(board), "{}", position.to_fen());
+        assert_eq!(position.en_passant(), en_passant_square(board, position.turn()), "{}", position.to_fen());
+        if depth > 0 {
+            for mv in position.legal_moves() {
+                let mut child = *position;
+                child.play_move(mv);
+                check_kept_state(&child, depth - 1);
+            }
+        }
+    }
+
+    #[test]
+    fn playing_moves_keeps_the_state_and_hash() {
+        for fen in [
+            STARTING_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1"
+        ] {
+            check_kept_state(&Position::from_fen(fen).unwrap(), 3);
+        }
+    }
+
+    #[test]
+    fn missing_fields_take_their_usual_values() {
+        let position = Position::from_fen("4k3/8/8/8/8/8/8/4K3 b").unwrap();
+        assert_eq!(position.to_fen(), "4k3/8/8/8/8/8/8/4K3 b - - 0 1");
+    }
+
+    #[test]
+    fn rejects_fen_that_is_not_a_position() {
+        for fen in [
+            "",
+            "hello",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNRR w KQkq - 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBN w KQkq - 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP w KQkq - 0 1",
+            "4k3/8/8/8/8/8/8/4K3",
+            "4k3/8/8/8/8/8/8/4K3 x - - 0 1",
+            "8/8/8/8/8/8/8/4K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/3KK3 w - - 0 1",
+            "4k2P/8/8/8/8/8/8/4K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/4K3 w K - 0 1",
+            "r3k2r/8/8/8/8/8/8/R3K2R w KKq - 0 1",
+            "r3k2r/8/8/8/8/8/8/R3K2R w KX - 0 1",
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e6 0 1",
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq z9 0 1",
+            "4k3/8/8/8/8/8/8/4K3 w - - x 1",
+            "4k3/8/8/8/8/8/8/4K3 w - - 0 0",
+            "4k3/8/8/8/8/8/8/4K3 w - - 0 1 extra",
+            "4k3/8/8/8/8/8/8/4R2K w - - 0 1"
+        ] {
+            assert!(Position::from_fen(fen).is_err(), "{}", fen);
+        }
+    }
+}
@@ -0,0 +1,275 @@
+// Standard Algebraic Notation
+//
+// Moves are written the way PGN and most chess software write them: the
+// piece letter, the file, rank or tile it came from when another piece of
+// the same kind could make the same move, "x" for captures, the tile moved
+// to, "=" and the piece promoted to, and "+" or "#" for check or mate.
+// Castling is "O-O" or "O-O-O".
+//
+// Reading comes in two strengths. The strict parser only takes a move
+// written exactly as it would be written here, apart from annotations such
+// as "!?" and an "e.p." after en passant captures. The lenient parser takes
+// what people and other programs actually write: "0-0", "e8Q", "Ng1f3",
+// "Nf3+" for a move that is not check, a missing "x", a lower case piece
+// letter or an "e.p." after any move, with or without a space before it.
+// Both resolve the text against the legal moves of the position.
+
+use crate::position::Position;
+use crate::{get_square_name, parse_square_name, Move, Pieces, Tile};
+
+/// Letter of a piece in SAN, empty for pawns
+fn piece_letter(piece: Option<Pieces>) -> &'static str {
+    match piece {
+        Some(Pieces::King { .. }) => "K",
+        Some(Pieces::Queen) => "Q",
+        Some(Pieces::Rook { .. }) => "R",
+        Some(Pieces::Bishop) => "B",
+        Some(Pieces::Knight) => "N",
+        _ => ""
+    }
+}
+
+/// Whether a move is a king moving two tiles to castle
+fn is_castling(board: &[Tile; 64], mv: Move) -> bool {
+    matches!(board[mv.from].piece, Some(Pieces::King { .. })) && mv.from.abs_diff(mv.to) == 2
+}
+
+/// Whether a move is a pawn taking en passant, landing on an empty tile on
+/// another file
+fn is_en_passant(board: &[Tile; 64], mv: Move) -> bool {
+    matches!(board[mv.from].piece, Some(Pieces::Pawn { .. })) && mv.from & 7 != mv.to & 7 && board[mv.to].piece.is_none()
+}
+
+/// Writes a move in SAN, such as "Nbd7", "exd6", "O-O-O", "e8=Q+" or "Qxf7#"
+pub fn move_to_san(position: &Position, mv: Move) -> String {
+    let board = position.board();
+    let from_name = get_square_name(mv.from);
+    let piece = board[mv.from].piece;
+    let mut san = String::new();
+
+    if is_castling(board, mv) {
+        san.push_str(if mv.to > mv.from { "O-O" } else { "O-O-O" });
+    } else {
+        let capture = board[mv.to].piece.is_some() || is_en_passant(board, mv);
+        if let Some(Pieces::Pawn { .. }) = piece {
+            if capture {
+                san.push_str(&from_name[..1]);
+            }
+        } else {
+            san.push_str(piece_letter(piece));
+            // Other pieces of the same kind that can reach the same tile
+            let rivals : Vec<usize> = position.legal_moves().into_iter()
+                .filter(|other| other.to == mv.to && other.from != mv.from && piece_letter(board[other.from].piece) == piece_letter(piece))
+                .map(|other| other.from)
+                .collect();
+            if !rivals.is_empty() {
+                if rivals.iter().all(|&rival| rival & 7 != mv.from & 7) {
+                    san.push_str(&from_name[..1]);
+                } else if rivals.iter().all(|&rival| rival >> 3 != mv.from >> 3) {
+                    san.push_str(&from_name[1..]);
+                } else {
+                    san.push_str(&from_name);
+                }
+            }
+        }
+        if capture {
+            san.push('x');
+        }
+        san.push_str(&get_square_name(mv.to));
+        if let Some(promotion) = mv.promotion {
+            san.push('=');
+            san.push_str(piece_letter(Some(promotion)));
+        }
+    }
+
+    let mut after = *position;
+    after.play_move(mv);
+    if after.is_in_check() {
+        san.push(if after.legal_moves().is_empty() { '#' } else { '+' });
+    }
+    san
+}
+
+/// Removes annotations such as "!?" and an "e.p." from the end of a move,
+/// returning whether there was an "e.p."
+fn strip_annotations(san: &str) -> (&str, bool) {
+    let san = san.trim().trim_end_matches(['!', '?']);
+    match san.strip_suffix("e.p.") {
+        Some(rest) => (rest.trim_end(), true),
+        None => (san, false)
+    }
+}
+
+/// Finds the legal move a SAN string stands for, only taking moves written
+/// exactly as `move_to_san` writes them
+pub fn parse_san_strict(position: &Position, san: &str) -> Result<Move, String> {
+    let mv = parse_san(position, san)?;
+    let (written, en_passant) = strip_annotations(san);
+    let expected = move_to_san(position, mv);
+    if written != expected || (en_passant && !is_en_passant(position.board(), mv)) {
+        return Err(format!("{} should be written {}", san.trim(), expected));
+    }
+    Ok(mv)
+}
+
+/// Finds the legal move a SAN string stands for, taking the common ways
+/// moves are miswritten as long as the move is still clear
+pub fn parse_san(position: &Position, san: &str) -> Result<Move, String> {
+    let board = position.board();
+    let original = san.trim();
+    let (san, _) = strip_annotations(san);
+    let san = san.trim_end_matches(['+', '#']);
+    let moves = position.legal_moves();
+
+    let castling = match san.replace('0', "O").replace('-', "").as_str() {
+        "OO" => Some(true),
+        "OOO" => Some(false),
+        _ => None
+    };
+    if let Some(king_side) = castling {
+        return moves.into_iter()
+            .find(|&mv| is_castling(board, mv) && (mv.to > mv.from) == king_side)
+            .ok_or_else(|| format!("{} is not legal here", original));
+    }
+
+    let mut letters : Vec<char> = san.chars().filter(|&letter| !"x:-=".contains(letter)).collect();
+    // A piece letter after the final rank is the promotion
+    let promotion = match letters.as_slice() {
+        [.., '1' | '8', letter] if "QRBNqrbn".contains(*letter) => {
+            let promotion = match letter.to_ascii_uppercase() {
+                'Q' => Pieces::Queen,
+                'R' => Pieces::Rook { has_moved: true },
+                'B' => Pieces::Bishop,
+                _ => Pieces::Knight
+            };
+            letters.pop();
+            Some(promotion)
+        },
+        _ => None
+    };
+    // Lower case "b" is a pawn on the b file before it is a bishop
+    let piece = match letters.first() {
+        Some(&letter) if "KQRBNP".contains(letter) || "kqrn".contains(letter) => {
+            letters.remove(0);
+            letter.to_ascii_uppercase()
+        },
+        _ => 'P'
+    };
+
+    let not_a_move = || format!("{} is not a move", original);
+    if letters.len() < 2 || letters.len() > 4 {
+        return Err(not_a_move());
+    }
+    let destination : String = letters[letters.len() - 2..].iter().collect();
+    let to = parse_square_name(&destination).ok_or_else(not_a_move)?;
+    let (mut from_file, mut from_rank) = (None, None);
+    for &letter in letters[..letters.len() - 2].iter() {
+        match letter {
+            'a'..='h' if from_file.is_none() && from_rank.is_none() => from_file = Some(letter as usize - 'a' as usize),
+            '1'..='8' if from_rank.is_none() => from_rank = Some(7 - (letter as usize - '1' as usize)),
+            _ => return Err(not_a_move())
+        }
+    }
+
+    let candidates : Vec<Move> = moves.into_iter().filter(|mv| {
+        let letter = match piece_letter(board[mv.from].piece) {
+            "" => 'P',
+            letter => letter.chars().next().unwrap_or('P')
+        };
+        mv.to == to && letter == piece && mv.promotion == promotion
+            && from_file.is_none_or(|file| mv.from & 7 == file)
+            && from_rank.is_none_or(|rank| mv.from >> 3 == rank)
+    }).collect();
+    match candidates.as_slice() {
+        [mv] => Ok(*mv),
+        [] if original.starts_with('b') => parse_san(position, &format!("B{}", &original[1..]))
+            .map_err(|_| format!("{} is not legal here", original)),
+        [] => Err(format!("{} is not legal here", original)),
+        _ => Err(format!("{} is ambiguous here", original))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::square::parse_uci_move;
+    use crate::STARTING_FEN;
+
+    /// Writes the move given in coordinates in SAN and reads it back
+    fn san_of(fen: &str, uci: &str) -> String {
+        let position = Position::from_fen(fen).unwrap();
+        let mv = parse_uci_move(&position, uci).unwrap();
+        let san = move_to_san(&position, mv);
+        assert_eq!(parse_san_strict(&position, &san), Ok(mv), "{}", san);
+        san
+    }
+
+    #[test]
+    fn writes_disambiguation() {
+        let knights = "4k3/8/8/8/8/5N2/8/1N2K3 w - - 0 1";
+        assert_eq!(san_of(knights, "b1d2"), "Nbd2");
+        assert_eq!(san_of(knights, "f3d2"), "Nfd2");
+        let rooks = "4k3/8/8/R7/8/8/8/R3K3 w - - 0 1";
+        assert_eq!(san_of(rooks, "a1a3"), "R1a3");
+        assert_eq!(san_of(rooks, "a5a3"), "R5a3");
+        // Another queen shares the file and another the rank
+        assert_eq!(san_of("4k3/8/8/8/8/Q7/8/Q1Q1K3 w - - 0 1", "a1b2"), "Qa1b2");
+    }
+
+    #[test]
+    fn writes_promotions_castling_and_checks() {
+        let promotion = "3r3k/4P3/8/8/8/8/8/K7 w - - 0 1";
+        assert_eq!(san_of(promotion, "e7e8q"), "e8=Q+");
+        assert_eq!(san_of(promotion, "e7e8n"), "e8=N");
+        assert_eq!(san_of(promotion, "e7d8q"), "exd8=Q+");
+        assert_eq!(san_of(promotion, "e7d8n"), "exd8=N");
+        let castling = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        assert_eq!(san_of(castling, "e1g1"), "O-O");
+        assert_eq!(san_of(castling, "e1c1"), "O-O-O");
+        let scholars_mate = "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4";
+        assert_eq!(san_of(scholars_mate, "h5f7"), "Qxf7#");
+        assert_eq!(san_of(scholars_mate, "c4f7"), "Bxf7+");
+        assert_eq!(san_of("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3", "e5d6"), "exd6");
+    }
+
+    #[test]
+    fn every_move_reads_back() {
+        for fen in [
+            STARTING_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8"
+        ] {
+            let position = Position::from_fen(fen).unwrap();
+            for mv in position.legal_moves() {
+                let san = move_to_san(&position, mv);
+                assert_eq!(parse_san_strict(&position, &san), Ok(mv), "{} in {}", san, fen);
+            }
+        }
+    }
+
+    #[test]
+    fn reads_common_miswritings() {
+        let position = Position::from_fen("r3k2r/1P6/8/8/8/8/8/R3K1NR w KQkq - 0 1").unwrap();
+        for (written, uci) in [("0-0-0", "e1c1"), ("b8Q", "b7b8q"), ("Ng1f3", "g1f3"), ("nf3", "g1f3"), ("Nf3+", "g1f3"), ("Rxa8", "a1a8"), ("Ra8", "a1a8")] {
+            assert_eq!(parse_san(&position, written), parse_uci_move(&position, uci), "{}", written);
+            assert!(parse_san_strict(&position, written).is_err(), "{}", written);
+        }
+        assert!(parse_san(&position, "Nf4").is_err());
+        // The knight stands in the way
+        assert!(parse_san(&position, "O-O").is_err());
+    }
+
+    #[test]
+    fn reads_en_passant_suffix() {
+        let position = Position::from_fen("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3").unwrap();
+        let en_passant = parse_uci_move(&position, "e5d6");
+        for written in ["exd6 e.p.", "exd6e.p.", "exd6 e.p.!", "ed6 e.p."] {
+            assert_eq!(parse_san(&position, written), en_passant, "{}", written);
+        }
+        assert_eq!(parse_san_strict(&position, "exd6 e.p."), en_passant);
+        // Only en passant captures may say so when strict
+        assert!(parse_san(&position, "Nf3 e.p.").is_ok());
+        assert!(parse_san_strict(&position, "Nf3 e.p.").is_err());
+    }
+}
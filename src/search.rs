@@ -0,0 +1,711 @@
+// Iterative deepening alpha-beta search
+//
+// A position is small enough to copy, so moves are searched by copying the
+// position, playing the move on the copy and recursing, with the copy's hash
+// brought up to date by the move. Each iteration searches
+// one ply deeper than the last, trying the previous principal variation
+// first, until the depth, node or time limit is reached or the search is
+// told to stop.
+//
+// With more than one thread the search uses Lazy SMP: helper threads run
+// their own iterative deepening on the same board and share results only
+// through the transposition table. The main thread alone decides when to
+// stop and which move to play, so one thread behaves exactly as before.
+//
+// Repeated positions and positions past the fifty move rule are scored as
+// draws. Repetitions are looked for among the positions played in the game
+// before the root, given with the limits, and those on the search stack.
+//
+// Positions are scored by the handcrafted evaluation, or by a network when
+// one is given, see `nnue`, except for the endgames known exactly, see
+// `endgame`.
+//
+// With Syzygy tablebases, see `syzygy`, positions with few enough pieces are
+// scored by their result in the tables without searching any further. When
+// the root is in the tables the winning move closest to zeroing is played
+// straight away, and otherwise only the moves keeping the best result are
+// searched.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::endgame::evaluate_endgame;
+use crate::evaluation::{evaluate, piece_value};
+use crate::movegen::{generate_captures, generate_moves, MoveList};
+use crate::nnue::{Accumulator, Network};
+use crate::pawn_structure::PawnHashTable;
+use crate::syzygy::{piece_count, Tablebases, Wdl};
+use crate::time_management::TimeManager;
+use crate::transposition_table::{Bound, TableEntry, TranspositionTable};
+use crate::position::{CastlingRights, Position};
+use crate::{Colors, Move, Pieces, Tile};
+
+pub const MATE_SCORE : i32 = 30000;
+pub const INFINITY : i32 = 32000;
+pub const MAX_PLY : usize = 128;
+// Score of a position the tablebases know is won, above any evaluation but
+// below mate scores
+pub const TABLEBASE_WIN : i32 = MATE_SCORE - 2 * MAX_PLY as i32;
+
+// Plies without a capture or pawn move after which the game is drawn
+const FIFTY_MOVE_PLIES : u32 = 100;
+// How often, in nodes, the stop flag and clock are checked
+const CHECK_INTERVAL : u64 = 1024;
+
+pub struct SearchLimits {
+    pub depth : Option<u32>,
+    pub nodes : Option<u64>,
+    pub time : TimeManager,
+    // Number of threads to search with, including the main thread
+    pub threads : usize,
+    // Number of best root moves to find lines for
+    pub multi_pv : usize,
+    // Network to evaluate with instead of the handcrafted evaluation
+    pub network : Option<Arc<Network>>,
+    pub tablebases : Option<Arc<Tablebases>>,
+    // Hashes of the positions played in the game before the root, oldest
+    // first, for finding repetitions
+    pub history : Vec<u64>
+}
+impl SearchLimits {
+    /// Limits that never stop the search on their own
+    pub fn infinite() -> SearchLimits {
+        SearchLimits {
+            depth : None,
+            nodes : None,
+            time : TimeManager::infinite(),
+            threads : 1,
+            multi_pv : 1,
+            network : None,
+            tablebases : None,
+            history : vec![]
+        }
+    }
+}
+
+/// The score and principal variation of one root move
+#[derive(Debug, Clone)]
+pub struct AnalysisLine {
+    pub score : i32,
+    pub pv : Vec<Move>
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    pub best_move : Option<Move>,
+    pub depth : u32,
+    // The best root moves ranked from best to worst, the first being the
+    // principal variation of the best move
+    pub lines : Vec<AnalysisLine>,
+    pub nodes : u64,
+    pub elapsed : Duration
+}
+
+/// Score of a tablebase result at the given ply. Cursed wins and blessed
+/// losses are drawn by the fifty move rule.
+fn tablebase_score(wdl: Wdl, ply: usize) -> i32 {
+    match wdl {
+        Wdl::Win => TABLEBASE_WIN - ply as i32,
+        Wdl::Loss => -TABLEBASE_WIN + ply as i32,
+        Wdl::CursedWin | Wdl::Draw | Wdl::BlessedLoss => 0
+    }
+}
+
+/// Whether a score means a forced mate for one side
+pub fn is_mate_score(score: i32) -> bool {
+    score.abs() >= MATE_SCORE - MAX_PLY as i32
+}
+
+/// Whether a score is a mate or a tablebase result, which count plies from
+/// the root
+fn is_decisive_score(score: i32) -> bool {
+    score.abs() >= TABLEBASE_WIN - MAX_PLY as i32
+}
+
+// Mate and tablebase scores are stored relative to the node rather than the
+// root, as the same position can be reached at different plies
+fn score_to_table(score: i32, ply: usize) -> i32 {
+    if is_decisive_score(score) {
+        score + score.signum() * ply as i32
+    } else {
+        score
+    }
+}
+
+fn score_from_table(score: i32, ply: usize) -> i32 {
+    if is_decisive_score(score) {
+        score - score.signum() * ply as i32
+    } else {
+        score
+    }
+}
+
+/// Whether a move takes a piece, including en passant
+pub fn is_capture(board: &[Tile; 64], mv: Move) -> bool {
+    if board[mv.to].piece.is_some() {
+        return true;
+    }
+    // A pawn moving diagonally onto an empty tile takes en passant
+    matches!(board[mv.from].piece, Some(Pieces::Pawn { .. })) && mv.from % 8 != mv.to % 8
+}
+
+/// Scores a move for ordering, captures of valuable pieces by cheap ones first
+fn order_score(board: &[Tile; 64], mv: Move) -> i32 {
+    let mut score = 0;
+    if is_capture(board, mv) {
+        let victim = board[mv.to].piece.map_or(piece_value(Pieces::Pawn { has_moved: true, en_passantable: false }), piece_value);
+        let attacker = board[mv.from].piece.map_or(0, piece_value);
+        score += 10 * victim - attacker / 10 + 100000;
+    }
+    if let Some(promotion) = mv.promotion {
+        score += piece_value(promotion);
+    }
+    score
+}
+
+struct Searcher<'a> {
+    table : &'a TranspositionTable,
+    stop : &'a AtomicBool,
+    // Nodes searched by every thread, added to in batches
+    total_nodes : &'a AtomicU64,
+    node_limit : Option<u64>,
+    time : TimeManager,
+    // Nodes searched by this thread not yet added to the total
+    nodes : u64,
+    aborted : bool,
+    // Principal variation of the previous iteration, searched first
+    previous_pv : Vec<Move>,
+    // Whether the current node lies on the previous principal variation
+    following_pv : bool,
+    // Best line found from each ply
+    pv_table : Vec<Vec<Move>>,
+    // Number of lines to search, and the root moves already given a line
+    // in this iteration
+    multi_pv : usize,
+    excluded_root_moves : Vec<Move>,
+    pawn_table : PawnHashTable,
+    network : Option<&'a Network>,
+    // Accumulator of the network for the board at each ply
+    accumulators : Vec<Accumulator>,
+    tablebases : Option<&'a Tablebases>,
+    // Root moves keeping the tablebase result, or empty to search them all
+    root_moves : Vec<Move>,
+    // Hashes of the game's positions before the root followed by those of
+    // the nodes on the search stack above the current one
+    hashes : Vec<u64>
+}
+impl<'a> Searcher<'a> {
+    fn new(table: &'a TranspositionTable, stop: &'a AtomicBool, total_nodes: &'a AtomicU64, node_limit: Option<u64>, time: TimeManager, network: Option<&'a Network>, tablebases: Option<&'a Tablebases>) -> Searcher<'a> {
+        Searcher {
+            table,
+            stop,
+            total_nodes,
+            node_limit,
+            time,
+            nodes : 0,
+            aborted : false,
+            previous_pv : vec![],
+            following_pv : false,
+            pv_table : vec![vec![]; MAX_PLY + 1],
+            multi_pv : 1,
+            excluded_root_moves : vec![],
+            pawn_table : PawnHashTable::new(),
+            network,
+            accumulators : if network.is_some() { vec![Accumulator::default(); MAX_PLY + 1] } else { vec![] },
+            tablebases,
+            root_moves : vec![],
+            hashes : vec![]
+        }
+    }
+
+    /// Plays a move on a copy of the position, bringing the network's
+    /// accumulator for the next ply up to date
+    fn play(&mut self, position: &Position, mv: Move, ply: usize) -> Position {
+        let mut after = *position;
+        after.play_move(mv);
+        if let Some(network) = self.network {
+            let (parent, child) = self.accumulators.split_at_mut(ply + 1);
+            network.update(&mut child[0], &parent[ply], position.board(), after.board());
+        }
+        after
+    }
+
+    /// Static evaluation of the board at the given ply
+    fn evaluate(&mut self, board: &[Tile; 64], turn: Colors, ply: usize) -> i32 {
+        if let Some(score) = evaluate_endgame(board, turn) {
+            return score;
+        }
+        match self.network {
+            // Keep the network's scores clear of mate scores
+            Some(network) => network.evaluate(&self.accumulators[ply], turn).clamp(-MATE_SCORE / 2, MATE_SCORE / 2),
+            None => evaluate(board, turn, &mut self.pawn_table)
+        }
+    }
+
+    /// Score of the position from the tablebases, or None when it is not in
+    /// them. The tables know nothing of castling, and their results only
+    /// hold with the fifty move count at zero, so other positions are left
+    /// to the search.
+    fn probe_tablebases(&self, position: &Position, ply: usize) -> Option<i32> {
+        let tablebases = self.tablebases?;
+        let board = position.board();
+        if piece_count(board) > tablebases.max_pieces || position.halfmove_clock() != 0 || position.castling() != CastlingRights::default() {
+            return None;
+        }
+        Some(tablebase_score(tablebases.probe_wdl(board, position.turn())?, ply))
+    }
+
+    /// Whether the position has been seen before, in the game or on the
+    /// search stack. Only positions since the last capture or pawn move and
+    /// with the same side to move can be the same.
+    fn is_repetition(&self, position: &Position) -> bool {
+        let hash = position.hash();
+        self.hashes.iter().rev().take(position.halfmove_clock() as usize).skip(1).step_by(2).any(|&earlier| earlier == hash)
+    }
+
+    fn node_count(&self) -> u64 {
+        self.total_nodes.load(Ordering::Relaxed) + self.nodes
+    }
+
+    /// Checks the stop flag and the node and time limits every so often
+    fn should_abort(&mut self) -> bool {
+        if self.aborted {
+            return true;
+        }
+        if self.nodes >= CHECK_INTERVAL {
+            let total = self.total_nodes.fetch_add(self.nodes, Ordering::Relaxed) + self.nodes;
+            self.nodes = 0;
+            let out_of_nodes = self.node_limit.is_some_and(|nodes| total >= nodes);
+            if self.stop.load(Ordering::Relaxed) || out_of_nodes || self.time.hard_limit_reached() {
+                self.aborted = true;
+            }
+        }
+        self.aborted
+    }
+
+    /// Orders moves in place, putting the hinted move first
+    fn order_moves(&self, board: &[Tile; 64], moves: &mut [Move], hint: Option<Move>) {
+        moves.sort_by_cached_key(|&mv| {
+            if Some(mv) == hint {
+                i32::MIN
+            } else {
+                -order_score(board, mv)
+            }
+        });
+    }
+
+    fn negamax(&mut self, position: &Position, depth: u32, ply: usize, mut alpha: i32, beta: i32) -> i32 {
+        self.pv_table[ply].clear();
+        self.nodes += 1;
+        if self.should_abort() {
+            return 0;
+        }
+
+        // A repetition can be repeated again, and past the fifty move rule
+        // either side can claim the draw unless the last move mated
+        if ply > 0 && (self.is_repetition(position) || position.halfmove_clock() >= FIFTY_MOVE_PLIES && !position.legal_moves().is_empty()) {
+            return 0;
+        }
+
+        let board = position.board();
+        let in_check = position.is_in_check();
+        // Search one ply deeper when in check so that mates are not missed
+        let depth = if in_check { depth + 1 } else { depth };
+        if depth == 0 || ply >= MAX_PLY {
+            return self.quiescence(position, ply, alpha, beta);
+        }
+
+        // A null window means this node is not on the principal variation, so
+        // an earlier result can be trusted without losing the line
+        let hash = position.hash();
+        let entry = self.table.probe(hash);
+        if let Some(entry) = entry {
+            let score = score_from_table(entry.score, ply);
+            if ply > 0 && beta - alpha == 1 && entry.depth >= depth {
+                match entry.bound {
+                    Bound::Exact => return score,
+                    Bound::Lower if score >= beta => return score,
+                    Bound::Upper if score <= alpha => return score,
+                    _ => ()
+                }
+            }
+        }
+
+        // The tables know the result exactly, however deep the search
+        if ply > 0 {
+            if let Some(score) = self.probe_tablebases(position, ply) {
+                self.table.store(hash, TableEntry {
+                    best_move : None,
+                    score : score_to_table(score, ply),
+                    depth,
+                    bound : Bound::Exact
+                });
+                return score;
+            }
+        }
+
+        let mut moves = MoveList::new();
+        generate_moves(position, &mut moves);
+        if moves.is_empty() {
+            // Checkmate is scored by distance so that shorter mates are preferred
+            return if in_check { -MATE_SCORE + ply as i32 } else { 0 };
+        }
+        if ply == 0 {
+            moves.retain(|mv| !self.excluded_root_moves.contains(mv) && (self.root_moves.is_empty() || self.root_moves.contains(mv)));
+        }
+        let on_pv = self.following_pv;
+        let pv_move = if on_pv { self.previous_pv.get(ply).copied() } else { None };
+        let hint = pv_move.or(entry.and_then(|entry| entry.best_move));
+        self.order_moves(board, &mut moves, hint);
+
+        let original_alpha = alpha;
+        let mut best_move : Option<Move> = None;
+        self.hashes.push(hash);
+        for (i, &mv) in moves.iter().enumerate() {
+            let after = self.play(position, mv, ply);
+            self.following_pv = on_pv && Some(mv) == pv_move;
+            // Principal variation search, the first move gets the full window
+            // and the rest only have to prove they are no better
+            let mut score;
+            if i == 0 {
+                score = -self.negamax(&after, depth - 1, ply + 1, -beta, -alpha);
+            } else {
+                score = -self.negamax(&after, depth - 1, ply + 1, -alpha - 1, -alpha);
+                if score > alpha && score < beta {
+                    score = -self.negamax(&after, depth - 1, ply + 1, -beta, -alpha);
+                }
+            }
+            if self.aborted {
+                self.hashes.pop();
+                return 0;
+            }
+            if score > alpha {
+                alpha = score;
+                best_move = Some(mv);
+                // The best line from here is this move followed by the child's line
+                let (parent, child) = self.pv_table.split_at_mut(ply + 1);
+                parent[ply].clear();
+                parent[ply].push(mv);
+                parent[ply].extend_from_slice(&child[0]);
+                if alpha >= beta {
+                    break;
+                }
+            }
+        }
+        self.hashes.pop();
+        self.following_pv = false;
+
+        // A root searched without some of its moves has no true score
+        if ply > 0 || self.excluded_root_moves.is_empty() {
+            let bound = if alpha >= beta {
+                Bound::Lower
+            } else if alpha > original_alpha {
+                Bound::Exact
+            } else {
+                Bound::Upper
+            };
+            self.table.store(hash, TableEntry {
+                best_move : best_move.or(hint),
+                score : score_to_table(alpha, ply),
+                depth,
+                bound
+            });
+        }
+
+        alpha
+    }
+
+    /// Searches captures only until the position is quiet, so that the static
+    /// evaluation is never taken in the middle of an exchange
+    fn quiescence(&mut self, position: &Position, ply: usize, mut alpha: i32, beta: i32) -> i32 {
+        self.nodes += 1;
+        if self.should_abort() {
+            return 0;
+        }
+
+        let board = position.board();
+        let stand_pat = self.evaluate(board, position.turn(), ply);
+        if stand_pat >= beta || ply >= MAX_PLY {
+            return stand_pat;
+        }
+        alpha = alpha.max(stand_pat);
+
+        let mut moves = MoveList::new();
+        generate_captures(position, &mut moves);
+        self.order_moves(board, &mut moves, None);
+
+        for &mv in moves.iter() {
+            let after = self.play(position, mv, ply);
+            let score = -self.quiescence(&after, ply + 1, -beta, -alpha);
+            if self.aborted {
+                return 0;
+            }
+            if score > alpha {
+                alpha = score;
+                if alpha >= beta {
+                    break;
+                }
+            }
+        }
+
+        alpha
+    }
+
+    /// Searches each of the best root moves in turn, excluding the moves
+    /// already searched from the root, and ranks the lines found
+    fn search_lines(&mut self, position: &Position, depth: u32, previous_lines: &[AnalysisLine]) -> Vec<AnalysisLine> {
+        let mut lines : Vec<AnalysisLine> = vec![];
+        if let Some(network) = self.network {
+            network.refresh(&mut self.accumulators[0], position.board());
+        }
+        let root_move_count = if self.root_moves.is_empty() { position.legal_moves().len() } else { self.root_moves.len() };
+        self.excluded_root_moves.clear();
+
+        for line in 0..self.multi_pv.max(1) {
+            self.previous_pv = previous_lines.get(line).map_or(vec![], |line| line.pv.clone());
+            self.following_pv = true;
+            let score = self.negamax(position, depth, 0, -INFINITY, INFINITY);
+            if self.aborted {
+                break;
+            }
+            let pv : Vec<Move> = self.pv_table[0].clone();
+            let first_move = pv.first().copied();
+            lines.push(AnalysisLine { score, pv });
+            // Stop once every root move has a line, or there are none at all
+            match first_move {
+                Some(mv) if self.excluded_root_moves.len() + 1 < root_move_count => {
+                    self.excluded_root_moves.push(mv)
+                },
+                _ => break
+            }
+        }
+
+        self.excluded_root_moves.clear();
+        // Later lines can come out better than earlier ones once the
+        // transposition table knows more
+        lines.sort_by_key(|line| -line.score);
+        lines
+    }
+
+    /// Searches one ply deeper each iteration from `start_depth` until
+    /// `max_depth`, the limits or the stop flag end the search. Only the
+    /// main thread reports and manages time.
+    fn iterative_deepening(&mut self, position: &Position, start_depth: u32, max_depth: u32, mut report: Option<&mut dyn FnMut(&SearchResult)>) -> SearchResult {
+        // Fall back to any legal move in case the first iteration is cut short
+        let mut result = SearchResult {
+            best_move : position.legal_moves().first().copied(),
+            depth : 0,
+            lines : vec![],
+            nodes : 0,
+            elapsed : Duration::ZERO
+        };
+
+        for depth in start_depth..=max_depth {
+            let iteration_start = self.time.elapsed();
+            let lines = self.search_lines(position, depth, &result.lines);
+            if self.aborted {
+                break;
+            }
+            let best_move = lines[0].pv.first().copied();
+
+            let best_move_changed = result.best_move != best_move;
+            result = SearchResult {
+                best_move : best_move.or(result.best_move),
+                depth,
+                lines,
+                nodes : self.node_count(),
+                elapsed : self.time.elapsed()
+            };
+            if let Some(report) = report.as_mut() {
+                report(&result);
+            }
+
+            // The first few iterations change their mind too often to be useful
+            if depth >= 4 {
+                self.time.update_best_move_stability(best_move_changed);
+            }
+            let iteration_time = self.time.elapsed() - iteration_start;
+            if !self.time.should_start_iteration(iteration_time) {
+                break;
+            }
+        }
+
+        result.nodes = self.node_count();
+        result.elapsed = self.time.elapsed();
+        result
+    }
+}
+
+/// Runs iterative deepening on the position for the side to move. `report`
+/// is called with the result of every completed iteration of the main thread.
+pub fn search(position: &Position, limits: SearchLimits, table: &TranspositionTable, stop: &AtomicBool, mut report: impl FnMut(&SearchResult)) -> SearchResult {
+    let max_depth = limits.depth.unwrap_or(MAX_PLY as u32).clamp(1, MAX_PLY as u32);
+    let total_nodes = AtomicU64::new(0);
+    // Helpers run until the main thread has finished
+    let helpers_stop = AtomicBool::new(false);
+    let network = limits.network.as_deref();
+    let tablebases = limits.tablebases.as_deref();
+    let (board, turn) = (position.board(), position.turn());
+
+    // A won root is played from the tables, reaching zeroing as soon as
+    // possible so the fifty move rule never gets in the way
+    let mut root_moves : Vec<Move> = vec![];
+    let ranked = tablebases
+        .filter(|tablebases| piece_count(board) <= tablebases.max_pieces && position.castling() == CastlingRights::default())
+        .and_then(|tablebases| tablebases.rank_root_moves(board, turn));
+    if let Some(ranked) = ranked {
+        let best_rank = ranked.iter().map(|&(_, rank, _)| rank).max();
+        if let Some((mv, _, _)) = ranked.iter().filter(|&&(_, rank, _)| rank > 0 && Some(rank) == best_rank).min_by_key(|&&(_, _, dtz)| dtz) {
+            let result = SearchResult {
+                best_move : Some(*mv),
+                depth : 1,
+                lines : vec![AnalysisLine { score : TABLEBASE_WIN, pv : vec![*mv] }],
+                nodes : 0,
+                elapsed : limits.time.elapsed()
+            };
+            report(&result);
+            return result;
+        }
+        root_moves = ranked.iter().filter(|&&(_, rank, _)| Some(rank) == best_rank).map(|&(mv, _, _)| mv).collect();
+    }
+
+    thread::scope(|scope| {
+        for helper in 1..limits.threads.max(1) {
+            let (total_nodes, helpers_stop, root_moves, history) = (&total_nodes, &helpers_stop, &root_moves, &limits.history);
+            scope.spawn(move || {
+                let mut searcher = Searcher::new(table, helpers_stop, total_nodes, None, TimeManager::infinite(), network, tablebases);
+                searcher.root_moves = root_moves.clone();
+                searcher.hashes = history.to_vec();
+                // Starting every other helper a ply deeper spreads the threads
+                // over different depths of the tree
+                let start_depth = (1 + helper as u32 % 2).min(max_depth);
+                searcher.iterative_deepening(position, start_depth, max_depth, None);
+            });
+        }
+
+        let mut searcher = Searcher::new(table, stop, &total_nodes, limits.nodes, limits.time, network, tablebases);
+        searcher.multi_pv = limits.multi_pv;
+        searcher.root_moves = root_moves.clone();
+        searcher.hashes = limits.history.clone();
+        let result = searcher.iterative_deepening(position, 1, max_depth, Some(&mut report));
+        helpers_stop.store(true, Ordering::Relaxed);
+        result
+    })
+}
+
+/// Formats a score for display in pawns, or as moves to mate
+pub fn format_score(score : i32) -> String {
+    if is_mate_score(score) {
+        let moves = (MATE_SCORE - score.abs() + 1) / 2;
+        format!("{}#{}", if score > 0 { "" } else { "-" }, moves)
+    } else {
+        format!("{:+.2}", score as f64 / 100f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stores_decisive_scores_relative_to_the_node() {
+        for score in [MATE_SCORE - 7, TABLEBASE_WIN - 7] {
+            assert_eq!(score_from_table(score_to_table(score, 7), 12), score - 5);
+            assert_eq!(score_from_table(score_to_table(-score, 7), 12), -score + 5);
+        }
+        assert_eq!(score_from_table(score_to_table(250, 7), 12), 250);
+    }
+
+    #[test]
+    fn finds_repetitions_since_the_last_pawn_move() {
+        let (table, stop, total_nodes) = (TranspositionTable::new(1), AtomicBool::new(false), AtomicU64::new(0));
+        let mut searcher = Searcher::new(&table, &stop, &total_nodes, None, TimeManager::infinite(), None, None);
+        let mut position = Position::default();
+        // Nf3 Nf6 Ng1 Ng8 comes back to the start
+        for (from, to) in [(0o76, 0o55), (0o06, 0o25), (0o55, 0o76), (0o25, 0o06)] {
+            searcher.hashes.push(position.hash());
+            let mv = position.legal_moves().into_iter().find(|mv| mv.from == from && mv.to == to).unwrap();
+            position.play_move(mv);
+        }
+        assert!(searcher.is_repetition(&position));
+        // The same placement with the other side to move is not the same
+        searcher.hashes.pop();
+        assert!(!searcher.is_repetition(&position));
+        assert!(!searcher.is_repetition(&Position::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 3").unwrap()));
+    }
+
+    #[test]
+    fn scores_the_fifty_move_rule_as_a_draw() {
+        let table = TranspositionTable::new(1);
+        let mut limits = SearchLimits::infinite();
+        limits.depth = Some(3);
+        // A queen up, but with no mate in one every move ends the game drawn
+        let position = Position::from_fen("7k/8/8/8/8/8/8/KQ6 w - - 99 80").unwrap();
+        let result = search(&position, limits, &table, &AtomicBool::new(false), |_| {});
+        assert_eq!(result.lines[0].score, 0);
+    }
+
+    const MIDDLE_GAME : &str = "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4";
+
+    /// Searches the middle game position on a fresh table
+    fn search_fresh(limits: SearchLimits, stop: &AtomicBool) -> SearchResult {
+        let position = Position::from_fen(MIDDLE_GAME).unwrap();
+        search(&position, limits, &TranspositionTable::new(1), stop, |_| {})
+    }
+
+    #[test]
+    fn one_thread_is_deterministic() {
+        for (depth, nodes) in [(Some(5), None), (None, Some(20000))] {
+            let run = || {
+                let mut limits = SearchLimits::infinite();
+                (limits.depth, limits.nodes) = (depth, nodes);
+                let result = search_fresh(limits, &AtomicBool::new(false));
+                (result.best_move, result.lines[0].score, result.nodes, result.depth)
+            };
+            assert_eq!(run(), run());
+        }
+    }
+
+    #[test]
+    fn several_threads_find_a_legal_move_and_stop() {
+        let legal_moves = Position::from_fen(MIDDLE_GAME).unwrap().legal_moves();
+        let mut limits = SearchLimits::infinite();
+        limits.threads = 4;
+        limits.depth = Some(5);
+        // The helpers are stopped once the main thread reaches the depth
+        let result = search_fresh(limits, &AtomicBool::new(false));
+        assert_eq!(result.depth, 5);
+        assert!(result.best_move.is_some_and(|mv| legal_moves.contains(&mv)));
+
+        // Without limits every thread runs until told to stop
+        let stop = AtomicBool::new(false);
+        let result = thread::scope(|scope| {
+            let searching = scope.spawn(|| {
+                let mut limits = SearchLimits::infinite();
+                limits.threads = 4;
+                search_fresh(limits, &stop)
+            });
+            thread::sleep(Duration::from_millis(200));
+            stop.store(true, Ordering::Relaxed);
+            searching.join().unwrap()
+        });
+        assert!(result.best_move.is_some_and(|mv| legal_moves.contains(&mv)));
+    }
+
+    #[test]
+    fn searches_with_the_tablebases() {
+        let tablebases = Tablebases::open(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/syzygy"));
+        let table = TranspositionTable::new(1);
+        let mut limits = SearchLimits::infinite();
+        limits.depth = Some(3);
+        limits.tablebases = Some(Arc::new(tablebases));
+        // Taking the knight reaches a won KQvK, with the mate too deep to see
+        let position = Position::from_fen("7k/8/8/8/8/8/1n6/KQ6 w - - 0 1").unwrap();
+        let result = search(&position, limits, &table, &AtomicBool::new(false), |_| {});
+        let score = result.lines[0].score;
+        assert_eq!(result.best_move.map(|mv| (mv.from, mv.to)), Some((0o71, 0o61)));
+        assert!(score >= TABLEBASE_WIN - MAX_PLY as i32 && !is_mate_score(score), "{}", score);
+    }
+}
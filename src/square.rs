@@ -0,0 +1,174 @@
+// Squares and long algebraic moves
+//
+// The board is indexed from 0 for a8 to 63 for h1, row by row from the top
+// as it is drawn, so that `get_array_index` and `get_render_coords` map
+// straight onto the window. A `Square` wraps one of those indexes and knows
+// its name, such as "e4", with files a to h from left to right and ranks 1
+// to 8 from the bottom up.
+//
+// Moves are written in long algebraic notation as UCI uses it: the tile
+// moved from, the tile moved to and the piece promoted to in lower case, as
+// in "e2e4" and "e7e8q". Castling is written as the king's move, "e1g1".
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::position::Position;
+use crate::{get_array_index, get_render_coords, Move, Pieces};
+
+/// One tile of the board by its board index
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Square(pub usize);
+
+impl Square {
+    /// The square on the file, 0 for a, and rank, 0 for the first rank
+    pub fn new(file: usize, rank: usize) -> Option<Square> {
+        (file < 8 && rank < 8).then(|| Square(get_array_index(file, 7 - rank)))
+    }
+
+    pub fn index(self) -> usize {
+        self.0
+    }
+
+    /// File of the square, 0 for a to 7 for h
+    pub fn file(self) -> usize {
+        get_render_coords(self.0)[0]
+    }
+
+    /// Rank of the square, 0 for the first rank to 7 for the eighth
+    pub fn rank(self) -> usize {
+        7 - get_render_coords(self.0)[1]
+    }
+}
+
+impl FromStr for Square {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Square, String> {
+        let invalid = || format!("{} is not a square", name);
+        match name.as_bytes() {
+            &[file, rank] => Square::new(file.wrapping_sub(b'a') as usize, rank.wrapping_sub(b'1') as usize).ok_or_else(invalid),
+            _ => Err(invalid())
+        }
+    }
+}
+
+impl fmt::Display for Square {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", (b'a' + self.file() as u8) as char, self.rank() + 1)
+    }
+}
+
+/// Writes a move in long algebraic notation, such as "e2e4" or "e7e8q"
+pub fn move_to_uci(mv: Move) -> String {
+    let promotion = match mv.promotion {
+        Some(Pieces::Queen) => "q",
+        Some(Pieces::Rook { .. }) => "r",
+        Some(Pieces::Bishop) => "b",
+        Some(Pieces::Knight) => "n",
+        _ => ""
+    };
+    format!("{}{}{}", Square(mv.from), Square(mv.to), promotion)
+}
+
+/// Reads a move in long algebraic notation, checking that it is one of the
+/// legal moves of the position
+pub fn parse_uci_move(position: &Position, string: &str) -> Result<Move, String> {
+    if !string.is_ascii() || !(4..=5).contains(&string.len()) {
+        return Err(format!("{} is not a move", string));
+    }
+    let from : Square = string[0..2].parse()?;
+    let to : Square = string[2..4].parse()?;
+    let promotion = match &string[4..] {
+        "" => None,
+        "q" => Some(Pieces::Queen),
+        "r" => Some(Pieces::Rook { has_moved: true }),
+        "b" => Some(Pieces::Bishop),
+        "n" => Some(Pieces::Knight),
+        other => return Err(format!("{} is not a piece to promote to", other))
+    };
+
+    if position.board()[from.index()].color != Some(position.turn()) {
+        return Err(format!("{} has no piece of the side to move on it", from));
+    }
+    let moves = position.legal_moves();
+    let mv = Move { from: from.index(), to: to.index(), promotion };
+    if moves.contains(&mv) {
+        return Ok(mv);
+    }
+    if promotion.is_none() && moves.iter().any(|other| other.from == mv.from && other.to == mv.to) {
+        return Err(format!("{} needs a piece to promote to", string));
+    }
+    Err(format!("{} is not legal here", string))
+}
+
+/// Plays a move written in long algebraic notation if it is legal
+pub fn play_uci_move(position: &mut Position, string: &str) -> Result<Move, String> {
+    let mv = parse_uci_move(position, string)?;
+    position.play_move(mv);
+    Ok(mv)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn squares_read_back_as_written() {
+        for index in 0..64 {
+            let square = Square(index);
+            assert_eq!(square.to_string().parse::<Square>(), Ok(square));
+        }
+        assert_eq!(Square(0).to_string(), "a8");
+        assert_eq!(Square(63).to_string(), "h1");
+        assert_eq!("e4".parse::<Square>().map(|square| (square.file(), square.rank())), Ok((4, 3)));
+        assert_eq!(Square::new(4, 3), Some(Square(36)));
+        assert_eq!(Square::new(8, 0), None);
+        for name in ["", "e", "e9", "e0", "i4", "E4", "4e", "e44", "é4"] {
+            assert_eq!(name.parse::<Square>(), Err(format!("{} is not a square", name)));
+        }
+    }
+
+    #[test]
+    fn reads_promotions_and_castling() {
+        let position = Position::from_fen("r3k2r/1P6/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        for (string, promotion) in [("b7b8q", Pieces::Queen), ("b7b8r", Pieces::Rook { has_moved: true }), ("b7b8b", Pieces::Bishop), ("b7b8n", Pieces::Knight), ("b7a8n", Pieces::Knight)] {
+            let mv = parse_uci_move(&position, string).unwrap();
+            assert_eq!(mv.promotion, Some(promotion));
+            assert_eq!(move_to_uci(mv), string);
+        }
+        assert_eq!(parse_uci_move(&position, "b7b8"), Err("b7b8 needs a piece to promote to".to_string()));
+        assert_eq!(parse_uci_move(&position, "b7b8k"), Err("k is not a piece to promote to".to_string()));
+        assert_eq!(parse_uci_move(&position, "b7b8Q"), Err("Q is not a piece to promote to".to_string()));
+
+        for (string, from, to) in [("e1g1", "e1", "g1"), ("e1c1", "e1", "c1")] {
+            let mv = parse_uci_move(&position, string).unwrap();
+            assert_eq!((Square(mv.from).to_string().as_str(), Square(mv.to).to_string().as_str(), mv.promotion), (from, to, None));
+            assert_eq!(move_to_uci(mv), string);
+        }
+        let mut castled = position;
+        assert!(play_uci_move(&mut castled, "e1g1").is_ok());
+        assert_eq!(castled.to_fen(), "r3k2r/1P6/8/8/8/8/8/R4RK1 b kq - 1 1");
+    }
+
+    #[test]
+    fn rejects_moves_that_are_malformed_or_not_legal() {
+        let position = Position::default();
+        let error = |string: &str| parse_uci_move(&position, string).unwrap_err();
+        assert_eq!(error(""), " is not a move");
+        assert_eq!(error("e2"), "e2 is not a move");
+        assert_eq!(error("e2e4qq"), "e2e4qq is not a move");
+        assert_eq!(error("é2e4"), "é2e4 is not a move");
+        assert_eq!(error("e2e9"), "e9 is not a square");
+        assert_eq!(error("z2e4"), "z2 is not a square");
+        assert_eq!(error("e2e4x"), "x is not a piece to promote to");
+        assert_eq!(error("e4e5"), "e4 has no piece of the side to move on it");
+        assert_eq!(error("e7e5"), "e7 has no piece of the side to move on it");
+        assert_eq!(error("e2e5"), "e2e5 is not legal here");
+        assert_eq!(error("e1g1"), "e1g1 is not legal here");
+        assert_eq!(error("e2e4q"), "e2e4q is not legal here");
+        let mut unchanged = position;
+        assert!(play_uci_move(&mut unchanged, "e2e5").is_err());
+        assert_eq!(unchanged.to_fen(), position.to_fen());
+    }
+}
@@ -0,0 +1,1549 @@
+// Syzygy endgame tablebase probing
+//
+// Syzygy tablebases hold the result of every position with few enough
+// pieces. For each material balance there are two files: the .rtbw file
+// gives the win, draw or loss (WDL) of a position and the .rtbz file its
+// distance to zeroing (DTZ), the number of plies to the next capture or pawn
+// move on the way to the best result. Every file found in the SyzygyPath
+// directories is read into memory the first time a position needs it.
+//
+// Files store each position once, with White as the side with the stronger
+// material, the leading piece mirrored into the a1-d1-d4 triangle, or for
+// tables with pawns the leading pawn onto files a to d, and the rest of the
+// pieces packed into an index with binomial coefficients. The values are
+// compressed with recursive pairing and then canonical Huffman codes, in
+// blocks that are found through a sparse index.
+//
+// To compress better, the tables leave out positions where a capture is the
+// best move, storing whatever value packs best instead, so probing always
+// tries the captures as well. See `probe_wdl` and `probe_dtz`.
+//
+// Squares here are numbered from a1 to h8, as in the files, rather than as
+// the board is indexed.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::mem;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use crate::search::is_capture;
+use crate::tablebase::{self, Tablebase};
+use crate::{generate_legal_moves, is_in_check, make_move, Colors, Move, Pieces, Tile};
+
+// Most pieces any Syzygy table has
+const MAX_PIECES : usize = 7;
+
+const WDL_MAGIC : [u8; 4] = [0x71, 0xE8, 0x23, 0x5D];
+const DTZ_MAGIC : [u8; 4] = [0xD7, 0x66, 0x0C, 0xA5];
+
+// Flags of each compressed table
+const FLAG_STM : u8 = 1;
+const FLAG_MAPPED : u8 = 2;
+const FLAG_WIN_PLIES : u8 = 4;
+const FLAG_LOSS_PLIES : u8 = 8;
+const FLAG_WIDE : u8 = 16;
+const FLAG_SINGLE_VALUE : u8 = 128;
+
+// Added to the piece codes in the files for Black
+const BLACK : u8 = 8;
+
+/// Result of a position for the side to move. Cursed wins and blessed losses
+/// are wins and losses that the fifty move rule turns into draws.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Wdl {
+    Loss,
+    BlessedLoss,
+    Draw,
+    CursedWin,
+    Win
+}
+impl Wdl {
+    fn from_value(value: i32) -> Option<Wdl> {
+        match value {
+            -2 => Some(Wdl::Loss),
+            -1 => Some(Wdl::BlessedLoss),
+            0 => Some(Wdl::Draw),
+            1 => Some(Wdl::CursedWin),
+            2 => Some(Wdl::Win),
+            _ => None
+        }
+    }
+
+    fn value(self) -> i32 {
+        self as i32 - 2
+    }
+
+    /// The same result for the other side
+    fn opposite(self) -> Wdl {
+        match self {
+            Wdl::Loss => Wdl::Win,
+            Wdl::BlessedLoss => Wdl::CursedWin,
+            Wdl::Draw => Wdl::Draw,
+            Wdl::CursedWin => Wdl::BlessedLoss,
+            Wdl::Win => Wdl::Loss
+        }
+    }
+}
+
+/// Distance to zeroing of the move just before a capture or pawn move that
+/// reaches a position with the given result
+fn dtz_before_zeroing(wdl: Wdl) -> i32 {
+    match wdl {
+        Wdl::Win => 1,
+        Wdl::CursedWin => 101,
+        Wdl::BlessedLoss => -101,
+        Wdl::Loss => -1,
+        Wdl::Draw => 0
+    }
+}
+
+#[inline]
+fn file_of(square: usize) -> usize {
+    square & 7
+}
+
+#[inline]
+fn rank_of(square: usize) -> usize {
+    square >> 3
+}
+
+/// How far above the a1-h8 diagonal a square is, negative below it
+#[inline]
+fn off_diagonal(square: usize) -> i32 {
+    rank_of(square) as i32 - file_of(square) as i32
+}
+
+/// Lookup tables used to turn a position into an index
+struct Encoding {
+    // Squares a2 to h7 numbered so that the leading pawn has the highest
+    map_pawns : [usize; 64],
+    // Squares below the a1-h8 diagonal numbered 0 to 27
+    map_b1h1h7 : [usize; 64],
+    // Squares of the a1-d1-d4 triangle numbered 0 to 9, the diagonal last
+    map_a1d1d4 : [usize; 64],
+    // Every legal placement of two kings with the first in the triangle
+    map_kk : [[usize; 64]; 10],
+    // Ways of choosing k of n squares, indexed [k][n]
+    binomial : [[u64; 64]; MAX_PIECES],
+    // Index of the leading pawns by the square of the leading one, and the
+    // number of indexes for each file, indexed by the number of leading pawns
+    lead_pawn_index : [[u64; 64]; 6],
+    lead_pawns_size : [[u64; 4]; 6]
+}
+
+static ENCODING : OnceLock<Encoding> = OnceLock::new();
+
+fn build_encoding() -> Encoding {
+    let mut encoding = Encoding {
+        map_pawns : [0; 64],
+        map_b1h1h7 : [0; 64],
+        map_a1d1d4 : [0; 64],
+        map_kk : [[0; 64]; 10],
+        binomial : [[0; 64]; MAX_PIECES],
+        lead_pawn_index : [[0; 64]; 6],
+        lead_pawns_size : [[0; 4]; 6]
+    };
+
+    let mut code = 0;
+    for square in 0..64 {
+        if off_diagonal(square) < 0 {
+            encoding.map_b1h1h7[square] = code;
+            code += 1;
+        }
+    }
+
+    let mut diagonal : Vec<usize> = vec![];
+    code = 0;
+    for square in 0..=27 {
+        if off_diagonal(square) < 0 && file_of(square) <= 3 {
+            encoding.map_a1d1d4[square] = code;
+            code += 1;
+        } else if off_diagonal(square) == 0 && file_of(square) <= 3 {
+            diagonal.push(square);
+        }
+    }
+    for square in diagonal {
+        encoding.map_a1d1d4[square] = code;
+        code += 1;
+    }
+
+    // With the first king on the diagonal the second can not be above it,
+    // and placements with both on the diagonal come last
+    let mut both_on_diagonal : Vec<(usize, usize)> = vec![];
+    code = 0;
+    for index in 0..10 {
+        for first in 0..=27 {
+            // b1 is the only square of the triangle numbered 0, the other
+            // squares numbered 0 are outside it
+            if encoding.map_a1d1d4[first] != index || (index == 0 && first != 1) {
+                continue;
+            }
+            for second in 0..64 {
+                let distance = file_of(first).abs_diff(file_of(second)).max(rank_of(first).abs_diff(rank_of(second)));
+                if distance <= 1 || (off_diagonal(first) == 0 && off_diagonal(second) > 0) {
+                    continue;
+                }
+                if off_diagonal(first) == 0 && off_diagonal(second) == 0 {
+                    both_on_diagonal.push((index, second));
+                } else {
+                    encoding.map_kk[index][second] = code;
+                    code += 1;
+                }
+            }
+        }
+    }
+    for (index, second) in both_on_diagonal {
+        encoding.map_kk[index][second] = code;
+        code += 1;
+    }
+
+    encoding.binomial[0][0] = 1;
+    for n in 1..64 {
+        for k in 0..MAX_PIECES.min(n + 1) {
+            encoding.binomial[k][n] = if k > 0 { encoding.binomial[k - 1][n - 1] } else { 0 }
+                + if k < n { encoding.binomial[k][n - 1] } else { 0 };
+        }
+    }
+
+    // Pawns can not stand on the first or last rank, leaving 48 squares
+    let mut available = 47;
+    for lead_pawns in 1..=5 {
+        for file in 0..4 {
+            let mut index = 0;
+            for rank in 1..=6 {
+                let square = rank * 8 + file;
+                if lead_pawns == 1 {
+                    encoding.map_pawns[square] = available;
+                    encoding.map_pawns[square ^ 7] = available - 1;
+                    available = available.saturating_sub(2);
+                }
+                encoding.lead_pawn_index[lead_pawns][square] = index;
+                index += encoding.binomial[lead_pawns - 1][encoding.map_pawns[square]];
+            }
+            encoding.lead_pawns_size[lead_pawns][file] = index;
+        }
+    }
+
+    encoding
+}
+
+fn read_u16_le(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(offset..offset + 2)?.try_into().ok()?))
+}
+
+fn read_u32_le(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+fn read_u32_be(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+fn read_u64_be(data: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(data.get(offset..offset + 8)?.try_into().ok()?))
+}
+
+/// Decoding information for one compressed table of a file. Positions are
+/// offsets into the file.
+#[derive(Debug, Clone, Default)]
+struct PairsData {
+    flags : u8,
+    max_symbol_length : u8,
+    min_symbol_length : u8,
+    block_count : usize,
+    block_size : usize,
+    // There is a sparse index entry about every span values
+    span : usize,
+    lowest_symbol : usize,
+    symbol_tree : usize,
+    block_lengths : usize,
+    block_lengths_size : usize,
+    sparse_index : usize,
+    sparse_index_size : usize,
+    data : usize,
+    // The lowest code of each symbol length, padded to 64 bits
+    base : Vec<u64>,
+    // Number of values, less one, each symbol expands to
+    symbol_lengths : Vec<u8>,
+    // Pieces in the order they are encoded, which defines the groups
+    pieces : [u8; MAX_PIECES],
+    group_index : [u64; MAX_PIECES + 1],
+    group_length : [usize; MAX_PIECES + 1],
+    // Where the values of each result start in the DTZ map
+    map_index : [usize; 4]
+}
+
+/// The left and right halves of a symbol in the pairing tree
+fn symbol_pair(data: &[u8], tree: usize, symbol: usize) -> Option<(usize, usize)> {
+    let bytes = data.get(tree + 3 * symbol..tree + 3 * symbol + 3)?;
+    let left = ((bytes[1] as usize & 0xF) << 8) | bytes[0] as usize;
+    let right = ((bytes[2] as usize) << 4) | (bytes[1] as usize >> 4);
+    Some((left, right))
+}
+
+/// Works out how many values a symbol expands to from its pair
+fn set_symbol_length(data: &[u8], pairs: &mut PairsData, symbol: usize, visited: &mut [bool]) -> Option<u8> {
+    visited[symbol] = true;
+    let (left, right) = symbol_pair(data, pairs.symbol_tree, symbol)?;
+    // Symbols that are values rather than pairs have no right half
+    if right == 0xFFF {
+        return Some(0);
+    }
+    for half in [left, right] {
+        if !*visited.get(half)? {
+            pairs.symbol_lengths[half] = set_symbol_length(data, pairs, half, visited)?;
+        }
+    }
+    Some(pairs.symbol_lengths[left].wrapping_add(pairs.symbol_lengths[right]).wrapping_add(1))
+}
+
+/// Reads the sizes and Huffman code of a table, returning the offset after it
+fn set_sizes(data: &[u8], pairs: &mut PairsData, mut offset: usize) -> Option<usize> {
+    pairs.flags = *data.get(offset)?;
+    offset += 1;
+    if pairs.flags & FLAG_SINGLE_VALUE != 0 {
+        // The one value every position has
+        pairs.min_symbol_length = *data.get(offset)?;
+        return Some(offset + 1);
+    }
+
+    let group_count = pairs.group_length.iter().position(|&length| length == 0)?;
+    let table_size = pairs.group_index[group_count];
+
+    pairs.block_size = 1 << *data.get(offset)?;
+    pairs.span = 1 << *data.get(offset + 1)?;
+    pairs.sparse_index_size = (table_size as usize).div_ceil(pairs.span);
+    let padding = *data.get(offset + 2)? as usize;
+    pairs.block_count = read_u32_le(data, offset + 3)? as usize;
+    // Padded so that the sparse index never points past the end
+    pairs.block_lengths_size = pairs.block_count + padding;
+    pairs.max_symbol_length = *data.get(offset + 7)?;
+    pairs.min_symbol_length = *data.get(offset + 8)?;
+    offset += 9;
+    pairs.lowest_symbol = offset;
+
+    // Longer codes have lower values, so the lowest code of each length can
+    // be worked out from the next longer one
+    let lengths = (pairs.max_symbol_length as usize + 1).checked_sub(pairs.min_symbol_length as usize)?;
+    pairs.base = vec![0; lengths];
+    for i in (0..lengths.saturating_sub(1)).rev() {
+        let lowest = read_u16_le(data, pairs.lowest_symbol + 2 * i)? as u64;
+        let next_lowest = read_u16_le(data, pairs.lowest_symbol + 2 * i + 2)? as u64;
+        pairs.base[i] = (pairs.base[i + 1] + lowest).wrapping_sub(next_lowest) / 2;
+    }
+    for (i, base) in pairs.base.iter_mut().enumerate() {
+        *base = base.checked_shl(64 - i as u32 - pairs.min_symbol_length as u32).unwrap_or(0);
+    }
+    offset += 2 * lengths;
+
+    let symbol_count = read_u16_le(data, offset)? as usize;
+    offset += 2;
+    pairs.symbol_tree = offset;
+    pairs.symbol_lengths = vec![0; symbol_count];
+    let mut visited = vec![false; symbol_count];
+    for symbol in 0..symbol_count {
+        if !visited[symbol] {
+            pairs.symbol_lengths[symbol] = set_symbol_length(data, pairs, symbol, &mut visited)?;
+        }
+    }
+
+    Some(offset + 3 * symbol_count + (symbol_count & 1))
+}
+
+/// Decompresses the value stored at an index of a table
+fn decompress_pairs(data: &[u8], pairs: &PairsData, index: u64) -> Option<i32> {
+    if pairs.flags & FLAG_SINGLE_VALUE != 0 {
+        return Some(pairs.min_symbol_length as i32);
+    }
+
+    // The sparse index gives the block and offset of every span'th value,
+    // from which the block holding the index is found
+    let entry = pairs.sparse_index + 6 * (index as usize / pairs.span);
+    let mut block = read_u32_le(data, entry)? as usize;
+    let mut offset = read_u16_le(data, entry + 4)? as i64;
+    offset += (index as usize % pairs.span) as i64 - (pairs.span / 2) as i64;
+
+    let block_length = |block: usize| read_u16_le(data, pairs.block_lengths + 2 * block).map(|length| length as i64);
+    while offset < 0 {
+        block = block.checked_sub(1)?;
+        offset += block_length(block)? + 1;
+    }
+    while offset > block_length(block)? {
+        offset -= block_length(block)? + 1;
+        block += 1;
+    }
+
+    // Read symbols from the start of the block until the one covering the
+    // offset
+    let mut position = pairs.data + block * pairs.block_size;
+    let mut buffer = read_u64_be(data, position)?;
+    position += 8;
+    let mut buffer_size = 64;
+    let min_length = pairs.min_symbol_length as usize;
+    let mut symbol;
+    loop {
+        let mut length = 0;
+        while buffer < *pairs.base.get(length)? {
+            length += 1;
+        }
+        symbol = ((buffer - pairs.base[length]) >> (64 - length - min_length)) as usize;
+        symbol += read_u16_le(data, pairs.lowest_symbol + 2 * length)? as usize;
+        let values = *pairs.symbol_lengths.get(symbol)? as i64 + 1;
+        if offset < values {
+            break;
+        }
+        offset -= values;
+        length += min_length;
+        buffer <<= length;
+        buffer_size -= length;
+        if buffer_size <= 32 {
+            buffer_size += 32;
+            buffer |= (read_u32_be(data, position)? as u64) << (64 - buffer_size);
+            position += 4;
+        }
+    }
+
+    // Expand the symbol down to the single value at the offset
+    while *pairs.symbol_lengths.get(symbol)? != 0 {
+        let (left, right) = symbol_pair(data, pairs.symbol_tree, symbol)?;
+        let left_values = *pairs.symbol_lengths.get(left)? as i64 + 1;
+        if offset < left_values {
+            symbol = left;
+        } else {
+            offset -= left_values;
+            symbol = right;
+        }
+    }
+    Some(symbol_pair(data, pairs.symbol_tree, symbol)?.0 as i32)
+}
+
+/// Material of a table, worked out from its name
+#[derive(Debug, Clone)]
+struct TableInfo {
+    piece_count : usize,
+    has_pawns : bool,
+    // Whether any side has exactly one of a piece other than the king
+    has_unique_pieces : bool,
+    // Pawns of the leading colour and of the other colour
+    pawn_count : [usize; 2],
+    // Whether both sides have the same material
+    symmetric : bool
+}
+impl TableInfo {
+    fn from_name(name: &str) -> Option<TableInfo> {
+        let (white, black) = name.split_once('v')?;
+        let valid = |side: &str| side.starts_with('K') && side[1..].chars().all(|piece| "QRBNP".contains(piece));
+        if !valid(white) || !valid(black) || white.len() + black.len() > MAX_PIECES {
+            return None;
+        }
+        let has_unique_pieces = [white, black].iter()
+            .any(|side| "QRBNP".chars().any(|piece| side.matches(piece).count() == 1));
+        let pawns = [white.matches('P').count(), black.matches('P').count()];
+        // The side with fewer pawns leads, as that compresses better
+        let white_leads = pawns[1] == 0 || (pawns[0] > 0 && pawns[1] >= pawns[0]);
+        Some(TableInfo {
+            piece_count : white.len() + black.len(),
+            has_pawns : pawns[0] + pawns[1] > 0,
+            has_unique_pieces,
+            pawn_count : if white_leads { pawns } else { [pawns[1], pawns[0]] },
+            symmetric : white == black
+        })
+    }
+}
+
+/// A table file read into memory
+struct Table {
+    data : Vec<u8>,
+    // Indexed by file times two plus side to move
+    pairs : Vec<PairsData>,
+    // Number of sides to move the table holds, WDL tables usually store both
+    sides : usize,
+    // Start of the DTZ value map
+    map : usize
+}
+impl Table {
+    fn pairs(&self, side: usize, file: usize) -> &PairsData {
+        &self.pairs[file * 2 + side % self.sides]
+    }
+}
+
+/// Works out the order the groups of pieces are encoded in, and how many
+/// indexes each group takes
+fn set_groups(info: &TableInfo, encoding: &Encoding, pairs: &mut PairsData, order: [usize; 2], file: usize) {
+    // Without pawns the leading group is three unique pieces, or the kings
+    let mut first_length : i32 = if info.has_pawns { 0 } else if info.has_unique_pieces { 3 } else { 2 };
+    let mut n = 0;
+    pairs.group_length[0] = 1;
+    for i in 1..info.piece_count {
+        first_length -= 1;
+        if first_length > 0 || pairs.pieces[i] == pairs.pieces[i - 1] {
+            pairs.group_length[n] += 1;
+        } else {
+            n += 1;
+            pairs.group_length[n] = 1;
+        }
+    }
+    n += 1;
+    pairs.group_length[n] = 0;
+
+    // The leading group and remaining pawns can be encoded at any position
+    // among the other groups, as given by the order
+    let pawns_on_both_sides = info.has_pawns && info.pawn_count[1] > 0;
+    let mut next = if pawns_on_both_sides { 2 } else { 1 };
+    let mut free_squares = 64 - pairs.group_length[0] - if pawns_on_both_sides { pairs.group_length[1] } else { 0 };
+    let mut index : u64 = 1;
+    let mut k = 0;
+    while next < n || k == order[0] || k == order[1] {
+        if k == order[0] {
+            pairs.group_index[0] = index;
+            index *= if info.has_pawns {
+                encoding.lead_pawns_size[pairs.group_length[0]][file]
+            } else if info.has_unique_pieces {
+                31332
+            } else {
+                462
+            };
+        } else if k == order[1] {
+            pairs.group_index[1] = index;
+            index *= encoding.binomial[pairs.group_length[1]][48 - pairs.group_length[0]];
+        } else {
+            pairs.group_index[next] = index;
+            index *= encoding.binomial[pairs.group_length[next]][free_squares];
+            free_squares -= pairs.group_length[next];
+            next += 1;
+        }
+        k += 1;
+    }
+    pairs.group_index[n] = index;
+}
+
+/// Reads the order of the pieces of each table from the start of a file,
+/// returning the tables with their groups set and the offset after it
+fn read_piece_order(data: &[u8], info: &TableInfo, dtz: bool) -> Option<(Vec<PairsData>, usize)> {
+    let encoding = ENCODING.get_or_init(build_encoding);
+    let sides = if !dtz && !info.symmetric { 2 } else { 1 };
+    let files = if info.has_pawns { 4 } else { 1 };
+    let mut pairs : Vec<PairsData> = vec![PairsData::default(); 8];
+    let pawns_on_both_sides = info.has_pawns && info.pawn_count[1] > 0;
+
+    // The first byte holds flags already known from the name
+    let mut offset = 5;
+    for file in 0..files {
+        let first = *data.get(offset)? as usize;
+        let second = if pawns_on_both_sides { *data.get(offset + 1)? as usize } else { 0xFF };
+        let orders = [[first & 0xF, second & 0xF], [first >> 4, second >> 4]];
+        offset += 1 + pawns_on_both_sides as usize;
+        for k in 0..info.piece_count {
+            let byte = *data.get(offset)?;
+            for side in 0..sides {
+                pairs[file * 2 + side].pieces[k] = if side == 1 { byte >> 4 } else { byte & 0xF };
+            }
+            offset += 1;
+        }
+        for side in 0..sides {
+            set_groups(info, encoding, &mut pairs[file * 2 + side], orders[side], file);
+        }
+    }
+    Some((pairs, offset + (offset & 1)))
+}
+
+/// Reads a table file, checking its magic number
+fn load_table(path: &PathBuf, info: &TableInfo, dtz: bool) -> Option<Table> {
+    let data = fs::read(path).ok()?;
+    let magic = if dtz { DTZ_MAGIC } else { WDL_MAGIC };
+    if data.get(0..4)? != magic {
+        println!("info string corrupted tablebase {}", path.display());
+        return None;
+    }
+    let sides = if !dtz && !info.symmetric { 2 } else { 1 };
+    let files = if info.has_pawns { 4 } else { 1 };
+    let (mut pairs, mut offset) = read_piece_order(&data, info, dtz)?;
+
+    for file in 0..files {
+        for side in 0..sides {
+            offset = set_sizes(&data, &mut pairs[file * 2 + side], offset)?;
+        }
+    }
+
+    // DTZ values are numbered by how common they are, the map turns them
+    // back into distances
+    let map = offset;
+    if dtz {
+        for file in 0..files {
+            let table = &mut pairs[file * 2];
+            if table.flags & FLAG_MAPPED == 0 {
+                continue;
+            }
+            if table.flags & FLAG_WIDE != 0 {
+                offset += offset & 1;
+                for i in 0..4 {
+                    table.map_index[i] = (offset - map) / 2 + 1;
+                    offset += 2 * read_u16_le(&data, offset)? as usize + 2;
+                }
+            } else {
+                for i in 0..4 {
+                    table.map_index[i] = offset - map + 1;
+                    offset += *data.get(offset)? as usize + 1;
+                }
+            }
+        }
+        offset += offset & 1;
+    }
+
+    for file in 0..files {
+        for side in 0..sides {
+            pairs[file * 2 + side].sparse_index = offset;
+            offset += 6 * pairs[file * 2 + side].sparse_index_size;
+        }
+    }
+    for file in 0..files {
+        for side in 0..sides {
+            pairs[file * 2 + side].block_lengths = offset;
+            offset += 2 * pairs[file * 2 + side].block_lengths_size;
+        }
+    }
+    for file in 0..files {
+        for side in 0..sides {
+            // Compressed data starts on a 64 byte boundary
+            offset = (offset + 0x3F) & !0x3F;
+            pairs[file * 2 + side].data = offset;
+            offset += pairs[file * 2 + side].block_count * pairs[file * 2 + side].block_size;
+        }
+    }
+
+    Some(Table {
+        data,
+        pairs,
+        sides,
+        map
+    })
+}
+
+/// The WDL and DTZ files of one material balance
+struct TableFiles {
+    info : TableInfo,
+    wdl_path : PathBuf,
+    dtz_path : Option<PathBuf>,
+    wdl : OnceLock<Option<Table>>,
+    dtz : OnceLock<Option<Table>>
+}
+
+/// Result of looking a position up in a DTZ table, which only stores one side
+/// to move for most material
+enum DtzLookup {
+    Dtz(i32),
+    OtherSideToMove
+}
+
+/// Piece code of a tile's occupant in the files
+fn piece_code(tile: &Tile) -> Option<u8> {
+    let code = match tile.piece? {
+        Pieces::Pawn { .. } => 1,
+        Pieces::Knight => 2,
+        Pieces::Bishop => 3,
+        Pieces::Rook { .. } => 4,
+        Pieces::Queen => 5,
+        Pieces::King { .. } => 6
+    };
+    Some(if tile.color? == Colors::Black { code + BLACK } else { code })
+}
+
+/// Name of the material of one side, as in the file names
+fn side_material(board: &[Tile; 64], color: Colors) -> String {
+    let mut material = String::from("K");
+    for (code, letter) in [(5, 'Q'), (4, 'R'), (3, 'B'), (2, 'N'), (1, 'P')] {
+        let count = board.iter().filter(|tile| tile.color == Some(color) && piece_code(tile).map(|piece| piece & 7) == Some(code)).count();
+        material.extend(std::iter::repeat_n(letter, count));
+    }
+    material
+}
+
+/// Number of pieces on the board, kings included
+pub fn piece_count(board: &[Tile; 64]) -> usize {
+    board.iter().filter(|tile| tile.piece.is_some()).count()
+}
+
+/// Finds where a table stores a position, as the side to move of the
+/// table, the file of the leading pawn and the index
+fn position_index(board: &[Tile; 64], turn: Colors, info: &TableInfo, table: &Table, black_stronger: bool) -> Option<(usize, usize, u64)> {
+    let encoding = ENCODING.get_or_init(build_encoding);
+
+    // Tables are stored with White as the stronger side, and symmetric
+    // tables with White to move only, so the board may need flipping
+    let flip = black_stronger || (info.symmetric && turn == Colors::Black);
+    let (flip_color, flip_squares) = if flip { (BLACK, 0o70) } else { (0, 0) };
+    let side = (flip as usize) ^ (turn as usize);
+
+    // Board indexes run from a8, the files from a1
+    let occupied : Vec<(usize, u8)> = (0..64)
+        .filter_map(|square| piece_code(&board[square ^ 0o70]).map(|piece| (square, piece)))
+        .collect();
+
+    let mut squares : Vec<usize> = vec![];
+    let mut pieces : Vec<u8> = vec![];
+    let mut file = 0;
+
+    // Tables with pawns are split by the file of the leading pawn, the one
+    // with the highest pawn square number
+    let lead_piece = if info.has_pawns { Some(table.pairs(0, 0).pieces[0] ^ flip_color) } else { None };
+    if let Some(lead_piece) = lead_piece {
+        for &(square, piece) in &occupied {
+            if piece == lead_piece {
+                squares.push(square ^ flip_squares);
+                pieces.push(piece ^ flip_color);
+            }
+        }
+        let mut lead = 0;
+        for i in 1..squares.len() {
+            if encoding.map_pawns[squares[i]] > encoding.map_pawns[squares[lead]] {
+                lead = i;
+            }
+        }
+        squares.swap(0, lead);
+        file = file_of(squares[0]).min(7 - file_of(squares[0]));
+    }
+    let lead_pawns = squares.len();
+
+    for &(square, piece) in &occupied {
+        if Some(piece) == lead_piece {
+            continue;
+        }
+        squares.push(square ^ flip_squares);
+        pieces.push(piece ^ flip_color);
+    }
+    if squares.len() != info.piece_count {
+        return None;
+    }
+
+    let pairs = table.pairs(side, file);
+
+    // Put the pieces in the order the table encodes them
+    for i in lead_pawns..squares.len().saturating_sub(1) {
+        for j in i + 1..squares.len() {
+            if pairs.pieces[i] == pieces[j] {
+                pieces.swap(i, j);
+                squares.swap(i, j);
+                break;
+            }
+        }
+    }
+
+    // Mirror the leading piece onto files a to d
+    if file_of(squares[0]) > 3 {
+        for square in squares.iter_mut() {
+            *square ^= 7;
+        }
+    }
+
+    let mut index : u64;
+    if info.has_pawns {
+        index = encoding.lead_pawn_index[lead_pawns][squares[0]];
+        squares[1..lead_pawns].sort_by_key(|&square| encoding.map_pawns[square]);
+        for (i, &square) in squares.iter().enumerate().take(lead_pawns).skip(1) {
+            index += encoding.binomial[i][encoding.map_pawns[square]];
+        }
+    } else {
+        // Mirror the leading piece onto ranks 1 to 4, then below the a1-h8
+        // diagonal
+        if rank_of(squares[0]) > 3 {
+            for square in squares.iter_mut() {
+                *square ^= 0o70;
+            }
+        }
+        for i in 0..pairs.group_length[0] {
+            if off_diagonal(squares[i]) == 0 {
+                continue;
+            }
+            if off_diagonal(squares[i]) > 0 {
+                for square in squares[i..].iter_mut() {
+                    *square = ((*square >> 3) | (*square << 3)) & 63;
+                }
+            }
+            break;
+        }
+
+        if info.has_unique_pieces {
+            let adjust1 = (squares[1] > squares[0]) as usize;
+            let adjust2 = (squares[2] > squares[0]) as usize + (squares[2] > squares[1]) as usize;
+            index = if off_diagonal(squares[0]) != 0 {
+                ((encoding.map_a1d1d4[squares[0]] * 63 + squares[1] - adjust1) * 62 + squares[2] - adjust2) as u64
+            } else if off_diagonal(squares[1]) != 0 {
+                ((6 * 63 + rank_of(squares[0]) * 28 + encoding.map_b1h1h7[squares[1]]) * 62 + squares[2] - adjust2) as u64
+            } else if off_diagonal(squares[2]) != 0 {
+                (6 * 63 * 62 + 4 * 28 * 62
+                    + rank_of(squares[0]) * 7 * 28
+                    + (rank_of(squares[1]) - adjust1) * 28
+                    + encoding.map_b1h1h7[squares[2]]) as u64
+            } else {
+                (6 * 63 * 62 + 4 * 28 * 62 + 4 * 7 * 28
+                    + rank_of(squares[0]) * 7 * 6
+                    + (rank_of(squares[1]) - adjust1) * 6
+                    + (rank_of(squares[2]) - adjust2)) as u64
+            };
+        } else {
+            index = encoding.map_kk[encoding.map_a1d1d4[squares[0]]][squares[1]] as u64;
+        }
+    }
+
+    // Each remaining group is encoded by its squares in ascending order,
+    // skipping the squares taken by the groups before it
+    index *= pairs.group_index[0];
+    let mut group_start = pairs.group_length[0];
+    let mut remaining_pawns = info.has_pawns && info.pawn_count[1] > 0;
+    let mut next = 1;
+    while pairs.group_length[next] != 0 {
+        let group_end = group_start + pairs.group_length[next];
+        squares[group_start..group_end].sort_unstable();
+        let mut n : u64 = 0;
+        for i in 0..pairs.group_length[next] {
+            let square = squares[group_start + i];
+            let adjust = squares[..group_start].iter().filter(|&&other| square > other).count();
+            n += encoding.binomial[i + 1][(square - adjust).checked_sub(if remaining_pawns { 8 } else { 0 })?];
+        }
+        remaining_pawns = false;
+        index += n * pairs.group_index[next];
+        group_start = group_end;
+        next += 1;
+    }
+    Some((side, file, index))
+}
+
+/// Finds the index of a position in a table and decompresses its value.
+/// Returns the file of the leading pawn with the value, or Some(None) when a
+/// DTZ table holds the other side to move.
+fn probe_table(board: &[Tile; 64], turn: Colors, info: &TableInfo, table: &Table, black_stronger: bool, dtz: bool) -> Option<Option<(usize, i32)>> {
+    let (side, file, index) = position_index(board, turn, info, table, black_stronger)?;
+
+    // DTZ tables only hold one side to move unless both sides are the same
+    if dtz {
+        let flags = table.pairs(side, file).flags;
+        let both_sides_stored = info.symmetric && !info.has_pawns;
+        if (flags & FLAG_STM) as usize != side && !both_sides_stored {
+            return Some(None);
+        }
+    }
+
+    let value = decompress_pairs(&table.data, table.pairs(side, file), index)?;
+    Some(Some((file, value)))
+}
+
+/// Tablebase files found in the SyzygyPath directories
+pub struct Tablebases {
+    tables : HashMap<String, TableFiles>,
+    // Most pieces of any table found
+    pub max_pieces : usize
+}
+impl Tablebases {
+    /// Finds the tables in a list of directories separated as in PATH
+    pub fn open(paths: &str) -> Tablebases {
+        let mut tables : HashMap<String, TableFiles> = HashMap::new();
+        for directory in env::split_paths(paths) {
+            let entries = match fs::read_dir(&directory) {
+                Ok(entries) => entries,
+                Err(_) => continue
+            };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.extension().and_then(|extension| extension.to_str()) != Some("rtbw") {
+                    continue;
+                }
+                let name = match path.file_stem().and_then(|stem| stem.to_str()) {
+                    Some(name) => name.to_string(),
+                    None => continue
+                };
+                let info = match TableInfo::from_name(&name) {
+                    Some(info) => info,
+                    None => continue
+                };
+                let dtz_path = path.with_extension("rtbz");
+                tables.insert(name, TableFiles {
+                    info,
+                    dtz_path : if dtz_path.exists() { Some(dtz_path) } else { None },
+                    wdl_path : path,
+                    wdl : OnceLock::new(),
+                    dtz : OnceLock::new()
+                });
+            }
+        }
+        let max_pieces = tables.values().map(|files| files.info.piece_count).max().unwrap_or(0);
+        Tablebases {
+            tables,
+            max_pieces
+        }
+    }
+
+    /// Number of tables found
+    pub fn len(&self) -> usize {
+        self.tables.len()
+    }
+
+    /// Whether no tables were found
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    /// The files for the material on the board, and whether Black is the
+    /// side stored as White
+    fn find(&self, board: &[Tile; 64]) -> Option<(&TableFiles, bool)> {
+        let white = side_material(board, Colors::White);
+        let black = side_material(board, Colors::Black);
+        if let Some(files) = self.tables.get(&format!("{}v{}", white, black)) {
+            return Some((files, false));
+        }
+        self.tables.get(&format!("{}v{}", black, white)).map(|files| (files, true))
+    }
+
+    /// Looks the position up in the WDL table, without trying captures
+    fn probe_wdl_table(&self, board: &[Tile; 64], turn: Colors) -> Option<Wdl> {
+        if piece_count(board) == 2 {
+            return Some(Wdl::Draw);
+        }
+        let (files, black_stronger) = self.find(board)?;
+        let table = files.wdl.get_or_init(|| load_table(&files.wdl_path, &files.info, false)).as_ref()?;
+        let (_, value) = probe_table(board, turn, &files.info, table, black_stronger, false)??;
+        Wdl::from_value(value - 2)
+    }
+
+    /// Looks the position up in the DTZ table, without trying captures
+    fn probe_dtz_table(&self, board: &[Tile; 64], turn: Colors, wdl: Wdl) -> Option<DtzLookup> {
+        let (files, black_stronger) = self.find(board)?;
+        let path = files.dtz_path.as_ref()?;
+        let table = files.dtz.get_or_init(|| load_table(path, &files.info, true)).as_ref()?;
+        let (file, value) = match probe_table(board, turn, &files.info, table, black_stronger, true)? {
+            Some(found) => found,
+            None => return Some(DtzLookup::OtherSideToMove)
+        };
+
+        // Turn the stored value back into a distance in plies
+        const WDL_MAP : [usize; 5] = [1, 3, 0, 2, 0];
+        let pairs = table.pairs(0, file);
+        let mut value = value as usize;
+        if pairs.flags & FLAG_MAPPED != 0 {
+            let map_index = pairs.map_index[WDL_MAP[(wdl.value() + 2) as usize]];
+            value = if pairs.flags & FLAG_WIDE != 0 {
+                read_u16_le(&table.data, table.map + 2 * (map_index + value))? as usize
+            } else {
+                *table.data.get(table.map + map_index + value)? as usize
+            };
+        }
+        let in_moves = match wdl {
+            Wdl::Win => pairs.flags & FLAG_WIN_PLIES == 0,
+            Wdl::Loss => pairs.flags & FLAG_LOSS_PLIES == 0,
+            Wdl::CursedWin | Wdl::BlessedLoss => true,
+            Wdl::Draw => false
+        };
+        if in_moves {
+            value *= 2;
+        }
+        Some(DtzLookup::Dtz(value as i32 + 1))
+    }
+
+    /// Tries the captures, and with `zeroing_moves` the pawn moves too, then
+    /// looks the position up. Returns the result and whether the best move
+    /// is one of the moves tried, in which case the stored value may be
+    /// meaningless.
+    fn search(&self, board: &[Tile; 64], turn: Colors, zeroing_moves: bool) -> Option<(Wdl, bool)> {
+        let moves = generate_legal_moves(board, turn);
+        let mut best = Wdl::Loss;
+        let mut tried = 0;
+        for &mv in &moves {
+            let pawn_move = matches!(board[mv.from].piece, Some(Pieces::Pawn { .. }));
+            let tried_move = is_capture(board, mv) || (zeroing_moves && pawn_move);
+            if !tried_move {
+                continue;
+            }
+            tried += 1;
+            let mut after : [Tile; 64] = *board;
+            make_move(&mut after, mv);
+            let value = self.search(&after, turn.opposite(), false)?.0.opposite();
+            if value > best {
+                best = value;
+                if value == Wdl::Win {
+                    return Some((value, true));
+                }
+            }
+        }
+
+        // Once every move has been tried the table is not needed, and may
+        // even be wrong, as it knows nothing of en passant
+        let every_move_tried = tried > 0 && tried == moves.len();
+        let value = if every_move_tried { best } else { self.probe_wdl_table(board, turn)? };
+        if best >= value {
+            return Some((best, best > Wdl::Draw || every_move_tried));
+        }
+        Some((value, false))
+    }
+
+    /// Win, draw or loss for the side to move, or None when the position is
+    /// not in the tables
+    pub fn probe_wdl(&self, board: &[Tile; 64], turn: Colors) -> Option<Wdl> {
+        self.search(board, turn, false).map(|(wdl, _)| wdl)
+    }
+
+    /// Distance to zeroing in plies for the side to move, positive when
+    /// winning and negative when losing, or None when the position is not in
+    /// the tables
+    pub fn probe_dtz(&self, board: &[Tile; 64], turn: Colors) -> Option<i32> {
+        let (wdl, zeroing_best) = self.search(board, turn, true)?;
+        if wdl == Wdl::Draw {
+            return Some(0);
+        }
+        if zeroing_best {
+            return Some(dtz_before_zeroing(wdl));
+        }
+        let cursed = matches!(wdl, Wdl::CursedWin | Wdl::BlessedLoss);
+        if let DtzLookup::Dtz(dtz) = self.probe_dtz_table(board, turn, wdl)? {
+            return Some((dtz + if cursed { 100 } else { 0 }) * wdl.value().signum());
+        }
+
+        // The table holds the other side to move, so look one move ahead for
+        // the move that reaches zeroing soonest
+        let mut best = i32::MAX;
+        for mv in generate_legal_moves(board, turn) {
+            let zeroing = is_capture(board, mv) || matches!(board[mv.from].piece, Some(Pieces::Pawn { .. }));
+            let mut after : [Tile; 64] = *board;
+            make_move(&mut after, mv);
+            let mut dtz = if zeroing {
+                -dtz_before_zeroing(self.probe_wdl(&after, turn.opposite())?)
+            } else {
+                -self.probe_dtz(&after, turn.opposite())?
+            };
+            // A mating move is always the best
+            if dtz == 1 && is_in_check(&after, turn.opposite()) && generate_legal_moves(&after, turn.opposite()).is_empty() {
+                best = 1;
+            }
+            if !zeroing {
+                dtz += dtz.signum();
+            }
+            if dtz < best && dtz.signum() == wdl.value().signum() {
+                best = dtz;
+            }
+        }
+        // Without legal moves the position is mate
+        Some(if best == i32::MAX { -1 } else { best })
+    }
+
+    /// Ranks every root move by its tablebase result, higher being better.
+    /// Wins that reach zeroing within the fifty move rule rank 1000, slower
+    /// ones less, and losses the other way round. Returns the moves with
+    /// their rank and distance to zeroing.
+    pub fn rank_root_moves(&self, board: &[Tile; 64], turn: Colors) -> Option<Vec<(Move, i32, i32)>> {
+        let mut ranked : Vec<(Move, i32, i32)> = vec![];
+        for mv in generate_legal_moves(board, turn) {
+            let zeroing = is_capture(board, mv) || matches!(board[mv.from].piece, Some(Pieces::Pawn { .. }));
+            let mut after : [Tile; 64] = *board;
+            make_move(&mut after, mv);
+            let mut dtz = if zeroing {
+                dtz_before_zeroing(self.probe_wdl(&after, turn.opposite())?.opposite())
+            } else {
+                let dtz = -self.probe_dtz(&after, turn.opposite())?;
+                dtz + dtz.signum()
+            };
+            if dtz == 2 && is_in_check(&after, turn.opposite()) && generate_legal_moves(&after, turn.opposite()).is_empty() {
+                dtz = 1;
+            }
+            let rank = if dtz > 0 {
+                if dtz <= 99 { 1000 } else { 1000 - dtz }
+            } else if dtz < 0 {
+                if -dtz * 2 < 100 { -1000 } else { -1000 - dtz }
+            } else {
+                0
+            };
+            ranked.push((mv, rank, dtz));
+        }
+        Some(ranked)
+    }
+}
+
+
+// Writing tables
+//
+// Generated tables can be written out as Syzygy files, small ones at least,
+// which is how the test fixtures were made. The values are compressed as
+// the files expect: the most common pairs of neighbouring symbols are
+// repeatedly replaced by new symbols, the symbols get canonical Huffman
+// codes, and the codes are packed into blocks. DTZ values are numbered by
+// how common they are, with a map back to the distances.
+
+// Block size and sparse index spacing, as powers of two. Small blocks keep
+// the files of small tables spread over many blocks.
+const WRITE_BLOCK_BITS : u8 = 6;
+const WRITE_SPAN_BITS : u8 = 9;
+// Pairs made at most, and least uses of a pair worth a symbol
+const MAX_PAIRS : usize = 200;
+const MIN_PAIR_COUNT : usize = 4;
+// Symbols of a table, as the right half of a value symbol is 0xFFF
+const MAX_SYMBOLS : usize = 0xFFF;
+// Most values in a block, keeping sparse index offsets in 16 bits
+const MAX_BLOCK_VALUES : usize = 1 << 15;
+
+/// One table of a file compressed, as its sizes and Huffman code, sparse
+/// index, block lengths and blocks
+struct CompressedTable {
+    sizes : Vec<u8>,
+    sparse_index : Vec<u8>,
+    block_lengths : Vec<u8>,
+    blocks : Vec<u8>
+}
+
+/// Length in bits of the Huffman code of each symbol used, by how often it
+/// is used
+fn huffman_lengths(counts: &[usize]) -> Vec<u8> {
+    use std::cmp::Reverse;
+    use std::collections::BinaryHeap;
+
+    let mut lengths : Vec<u8> = vec![0; counts.len()];
+    // Leaves are the symbols, each merge a node holding the symbols below it
+    let mut nodes : Vec<Vec<usize>> = vec![];
+    let mut heap : BinaryHeap<Reverse<(usize, usize)>> = BinaryHeap::new();
+    for (symbol, &count) in counts.iter().enumerate() {
+        if count > 0 {
+            heap.push(Reverse((count, nodes.len())));
+            nodes.push(vec![symbol]);
+        }
+    }
+    if heap.len() == 1 {
+        lengths[nodes[0][0]] = 1;
+    }
+    while heap.len() > 1 {
+        let (Some(Reverse((first_count, first))), Some(Reverse((second_count, second)))) = (heap.pop(), heap.pop()) else { break };
+        let mut symbols = mem::take(&mut nodes[first]);
+        symbols.append(&mut nodes[second]);
+        for &symbol in &symbols {
+            lengths[symbol] += 1;
+        }
+        heap.push(Reverse((first_count + second_count, nodes.len())));
+        nodes.push(symbols);
+    }
+    lengths
+}
+
+/// Compresses the values of one table, with `flags` as its flags
+fn compress_table(values: &[u16], flags: u8) -> Result<CompressedTable, String> {
+    let mut literals : Vec<u16> = values.to_vec();
+    literals.sort_unstable();
+    literals.dedup();
+    if literals.len() == 1 && literals[0] < 256 {
+        return Ok(CompressedTable {
+            sizes : vec![flags | FLAG_SINGLE_VALUE, literals[0] as u8],
+            sparse_index : vec![],
+            block_lengths : vec![],
+            blocks : vec![]
+        });
+    }
+
+    // Symbols as their two halves, the right half 0xFFF for values, and the
+    // number of values each stands for
+    let mut symbols : Vec<(usize, usize)> = literals.iter().map(|&value| (value as usize, MAX_SYMBOLS)).collect();
+    let mut symbol_values : Vec<usize> = vec![1; symbols.len()];
+    let mut sequence : Vec<usize> = values.iter().map(|value| literals.binary_search(value).unwrap_or(0)).collect();
+    for _ in 0..MAX_PAIRS {
+        if symbols.len() >= MAX_SYMBOLS {
+            break;
+        }
+        let mut pair_counts : HashMap<(usize, usize), usize> = HashMap::new();
+        for pair in sequence.windows(2) {
+            if symbol_values[pair[0]] + symbol_values[pair[1]] <= 256 {
+                *pair_counts.entry((pair[0], pair[1])).or_insert(0) += 1;
+            }
+        }
+        let best = pair_counts.into_iter().max_by_key(|&(pair, count)| (count, std::cmp::Reverse(pair)));
+        let (pair, _) = match best {
+            Some((pair, count)) if count >= MIN_PAIR_COUNT => (pair, count),
+            _ => break
+        };
+        let symbol = symbols.len();
+        symbols.push(pair);
+        symbol_values.push(symbol_values[pair.0] + symbol_values[pair.1]);
+        let mut paired : Vec<usize> = Vec::with_capacity(sequence.len());
+        let mut i = 0;
+        while i < sequence.len() {
+            if i + 1 < sequence.len() && (sequence[i], sequence[i + 1]) == pair {
+                paired.push(symbol);
+                i += 2;
+            } else {
+                paired.push(sequence[i]);
+                i += 1;
+            }
+        }
+        sequence = paired;
+    }
+
+    // Renumber the symbols, the used ones by code length from the longest
+    // and the rest after them
+    let mut counts : Vec<usize> = vec![0; symbols.len()];
+    for &symbol in &sequence {
+        counts[symbol] += 1;
+    }
+    let lengths = huffman_lengths(&counts);
+    let max_length = *lengths.iter().max().unwrap_or(&0);
+    let min_length = lengths.iter().copied().filter(|&length| length > 0).min().unwrap_or(0);
+    if max_length > 32 {
+        return Err(String::from("a Huffman code is longer than 32 bits"));
+    }
+    let mut order : Vec<usize> = (0..symbols.len()).collect();
+    order.sort_by_key(|&symbol| (lengths[symbol] == 0, std::cmp::Reverse(lengths[symbol]), symbol));
+    let mut number : Vec<usize> = vec![0; symbols.len()];
+    for (new, &old) in order.iter().enumerate() {
+        number[old] = new;
+    }
+
+    // Canonical codes: the longest codes count up from 0, and each shorter
+    // length starts after the codes below it, so longer codes are lower
+    let length_count = (max_length - min_length + 1) as usize;
+    let mut per_length : Vec<usize> = vec![0; length_count];
+    for &length in lengths.iter().filter(|&&length| length > 0) {
+        per_length[(length - min_length) as usize] += 1;
+    }
+    let mut lowest_symbol : Vec<usize> = vec![0; length_count];
+    let mut base : Vec<u64> = vec![0; length_count];
+    for i in (0..length_count.saturating_sub(1)).rev() {
+        lowest_symbol[i] = lowest_symbol[i + 1] + per_length[i + 1];
+        base[i] = (base[i + 1] + per_length[i + 1] as u64) / 2;
+    }
+    let code = |symbol: usize| -> (u64, u8) {
+        let length = lengths[symbol];
+        let i = (length - min_length) as usize;
+        (base[i] + (number[symbol] - lowest_symbol[i]) as u64, length)
+    };
+
+    // Pack whole symbols into blocks, leaving room for the decoder to read
+    // 64 bits ahead
+    let block_size = 1usize << WRITE_BLOCK_BITS;
+    let capacity = block_size * 8 - 64;
+    let mut blocks : Vec<u8> = vec![];
+    let mut block_values : Vec<usize> = vec![];
+    let mut block : Vec<u8> = vec![];
+    let (mut bits, mut values_in_block) = (0, 0);
+    for &symbol in &sequence {
+        let (value, length) = code(symbol);
+        if bits + length as usize > capacity || values_in_block + symbol_values[symbol] > MAX_BLOCK_VALUES {
+            block.resize(block_size, 0);
+            blocks.append(&mut block);
+            block_values.push(values_in_block);
+            bits = 0;
+            values_in_block = 0;
+        }
+        for bit in (0..length).rev() {
+            if bits % 8 == 0 {
+                block.push(0);
+            }
+            if (value >> bit) & 1 == 1 {
+                *block.last_mut().unwrap_or(&mut 0) |= 0x80 >> (bits % 8);
+            }
+            bits += 1;
+        }
+        values_in_block += symbol_values[symbol];
+    }
+    block.resize(block_size, 0);
+    blocks.append(&mut block);
+    block_values.push(values_in_block);
+
+    // Every span'th entry gives the block and offset of the value in the
+    // middle of its span
+    let span = 1usize << WRITE_SPAN_BITS;
+    let mut sparse_index : Vec<u8> = vec![];
+    let mut block = 0;
+    let mut block_start = 0;
+    for entry in 0..values.len().div_ceil(span) {
+        let middle = entry * span + span / 2;
+        while block + 1 < block_values.len() && middle >= block_start + block_values[block] {
+            block_start += block_values[block];
+            block += 1;
+        }
+        sparse_index.extend_from_slice(&(block as u32).to_le_bytes());
+        sparse_index.extend_from_slice(&((middle - block_start) as u16).to_le_bytes());
+    }
+    let block_lengths : Vec<u8> = block_values.iter().flat_map(|&count| ((count - 1) as u16).to_le_bytes()).collect();
+
+    let mut sizes : Vec<u8> = vec![flags, WRITE_BLOCK_BITS, WRITE_SPAN_BITS, 0];
+    sizes.extend_from_slice(&(block_values.len() as u32).to_le_bytes());
+    sizes.push(max_length);
+    sizes.push(min_length);
+    for &lowest in &lowest_symbol {
+        sizes.extend_from_slice(&(lowest as u16).to_le_bytes());
+    }
+    sizes.extend_from_slice(&(symbols.len() as u16).to_le_bytes());
+    for &old in &order {
+        let (left, right) = symbols[old];
+        let (left, right) = if right == MAX_SYMBOLS { (left, right) } else { (number[left], number[right]) };
+        sizes.push(left as u8);
+        sizes.push(((left >> 8) & 0xF) as u8 | ((right & 0xF) << 4) as u8);
+        sizes.push((right >> 4) as u8);
+    }
+    if symbols.len() % 2 == 1 {
+        sizes.push(0);
+    }
+
+    Ok(CompressedTable {
+        sizes,
+        sparse_index,
+        block_lengths,
+        blocks
+    })
+}
+
+/// Code of each piece of a table in the order the files encode them: the
+/// leading pawns and the other pawns, or the kings and for tables with a
+/// unique piece one of those, then the rest with identical pieces together
+fn write_piece_order(white: &str, black: &str, info: &TableInfo) -> Vec<u8> {
+    let codes = |side: &str, color: u8| -> Vec<u8> {
+        side.chars().map(|letter| 6 - "KQRBNP".find(letter).unwrap_or(0) as u8 + color).collect()
+    };
+    let mut pieces : Vec<u8> = codes(white, 0);
+    pieces.extend(codes(black, BLACK));
+    let count = |piece: u8| pieces.iter().filter(|&&other| other == piece).count();
+    let white_leads = count(1 + BLACK) == 0 || (count(1) > 0 && count(1 + BLACK) >= count(1));
+    let lead = if white_leads { 1 } else { 1 + BLACK };
+    let unique = pieces.iter().copied().filter(|&piece| piece & 7 != 6 && count(piece) == 1).min();
+    let rank = |piece: u8| -> u8 {
+        if info.has_pawns {
+            if piece == lead { 0 } else if piece & 7 == 1 { 1 } else { 2 }
+        } else if piece == 6 {
+            0
+        } else if piece == 6 + BLACK {
+            1
+        } else if info.has_unique_pieces && Some(piece) == unique {
+            2
+        } else {
+            3
+        }
+    };
+    pieces.sort_by_key(|&piece| (rank(piece), piece));
+    pieces
+}
+
+/// The bytes after the magic number and flags up to the table sizes: the
+/// order of the groups and of the pieces for each file, for both sides
+fn write_layout(piece_order: &[u8], info: &TableInfo) -> Vec<u8> {
+    let mut layout : Vec<u8> = vec![];
+    for _ in 0..if info.has_pawns { 4 } else { 1 } {
+        layout.push(0x00);
+        if info.has_pawns && info.pawn_count[1] > 0 {
+            layout.push(0x11);
+        }
+        layout.extend(piece_order.iter().map(|&piece| piece | piece << 4));
+    }
+    // The sizes start on an even offset
+    if layout.len().is_multiple_of(2) {
+        layout.push(0);
+    }
+    layout
+}
+
+/// Writes a table as a WDL or DTZ file, each table of `tables` being the
+/// flags and values of one file and side to move
+fn write_file(path: &PathBuf, dtz: bool, layout: &[u8], has_pawns: bool, tables: &[(u8, Vec<u16>)], maps: &[Vec<Vec<u8>>]) -> Result<(), String> {
+    let files = if has_pawns { 4 } else { 1 };
+    let sides = tables.len() / files;
+    let mut data : Vec<u8> = if dtz { DTZ_MAGIC.to_vec() } else { WDL_MAGIC.to_vec() };
+    data.push((sides == 2) as u8 | (has_pawns as u8) << 1);
+    data.extend_from_slice(layout);
+
+    let compressed : Vec<CompressedTable> = tables.iter().map(|(flags, values)| compress_table(values, *flags)).collect::<Result<_, _>>()?;
+    for table in &compressed {
+        data.extend_from_slice(&table.sizes);
+    }
+    for map in maps {
+        for values in map {
+            data.push(values.len() as u8);
+            data.extend_from_slice(values);
+        }
+    }
+    if data.len() % 2 == 1 {
+        data.push(0);
+    }
+    for table in &compressed {
+        data.extend_from_slice(&table.sparse_index);
+    }
+    for table in &compressed {
+        data.extend_from_slice(&table.block_lengths);
+    }
+    for table in &compressed {
+        if !table.blocks.is_empty() {
+            data.resize(data.len().next_multiple_of(64), 0);
+            data.extend_from_slice(&table.blocks);
+        }
+    }
+    fs::write(path, data).map_err(|error| format!("can not write {}: {}", path.display(), error))
+}
+
+/// Generates the table of a material with at most five pieces and writes it
+/// as Syzygy WDL and DTZ files into `directory`. The DTZ file holds the
+/// stronger side to move.
+pub fn write_tables(material: &str, directory: &str) -> Result<(), String> {
+    let name = tablebase::signature_of(material).ok_or_else(|| format!("{} is not a material signature", material))?;
+    let mut generated : HashMap<String, Tablebase> = HashMap::new();
+    tablebase::generate(&name, &mut generated)?;
+    let table = &generated[&name];
+    let info = TableInfo::from_name(&name).ok_or_else(|| format!("{} is not a material signature", name))?;
+    let distances = table.zeroing_distances(&generated)?;
+    if distances.iter().any(|&distance| distance > 100) {
+        return Err(format!("{} has wins the fifty move rule turns into draws, which can not be written", name));
+    }
+
+    let (white, black) = name.split_once('v').ok_or_else(|| format!("{} is not a material signature", name))?;
+    let layout = write_layout(&write_piece_order(white, black, &info), &info);
+    let header : Vec<u8> = [0; 5].iter().chain(&layout).copied().collect();
+    let read_layout = |dtz: bool| -> Result<Table, String> {
+        let (pairs, _) = read_piece_order(&header, &info, dtz).ok_or_else(|| format!("can not lay out {}", name))?;
+        Ok(Table { data : vec![], pairs, sides : if !dtz && !info.symmetric { 2 } else { 1 }, map : 0 })
+    };
+    let (wdl_layout, dtz_layout) = (read_layout(false)?, read_layout(true)?);
+    let files = if info.has_pawns { 4 } else { 1 };
+    let size = |layout: &Table, side: usize, file: usize| {
+        let pairs = layout.pairs(side, file);
+        let groups = pairs.group_length.iter().position(|&length| length == 0).unwrap_or(0);
+        pairs.group_index[groups] as usize
+    };
+
+    // Values of the positions, None for indexes no position has
+    let mut wdl : Vec<Vec<Option<u16>>> = (0..files * wdl_layout.sides).map(|i| vec![None; size(&wdl_layout, i % wdl_layout.sides, i / wdl_layout.sides)]).collect();
+    let mut dtz : Vec<Vec<Option<(bool, u16)>>> = (0..files).map(|file| vec![None; size(&dtz_layout, 0, file)]).collect();
+    for (index, board, turn, value) in table.positions() {
+        let result = match value {
+            tablebase::DRAW => 2,
+            plies if plies % 2 == 1 => 4,
+            _ => 0
+        };
+        let (side, file, position) = position_index(&board, turn, &info, &wdl_layout, false).ok_or_else(|| format!("can not index a position of {}", name))?;
+        wdl[file * wdl_layout.sides + side % wdl_layout.sides][position as usize] = Some(result);
+        let (side, file, position) = position_index(&board, turn, &info, &dtz_layout, false).ok_or_else(|| format!("can not index a position of {}", name))?;
+        if side == 0 && result != 2 {
+            dtz[file][position as usize] = Some((result == 4, distances[index] as u16 - 1));
+        }
+    }
+
+    // Indexes without a position repeat the value before them, which packs
+    // best
+    let fill = |values: Vec<Option<u16>>| -> Vec<u16> {
+        let mut last = values.iter().flatten().next().copied().unwrap_or(0);
+        values.into_iter().map(|value| {
+            last = value.unwrap_or(last);
+            last
+        }).collect()
+    };
+    let wdl_tables : Vec<(u8, Vec<u16>)> = wdl.into_iter().map(|values| (0, fill(values))).collect();
+
+    // DTZ values become their rank among the distances of wins, or of
+    // losses, by how common they are
+    let mut dtz_tables : Vec<(u8, Vec<u16>)> = vec![];
+    let mut maps : Vec<Vec<Vec<u8>>> = vec![];
+    for values in dtz {
+        let mut map : Vec<Vec<u8>> = vec![vec![]; 4];
+        for (list, won) in [(0, true), (1, false)] {
+            let mut counts : HashMap<u16, usize> = HashMap::new();
+            for &(_, distance) in values.iter().flatten().filter(|(win, _)| *win == won) {
+                *counts.entry(distance).or_insert(0) += 1;
+            }
+            let mut common : Vec<(u16, usize)> = counts.into_iter().collect();
+            common.sort_by_key(|&(distance, count)| (std::cmp::Reverse(count), distance));
+            map[list] = common.into_iter().map(|(distance, _)| distance as u8).collect();
+        }
+        let ranked : Vec<Option<u16>> = values.iter().map(|value| value.map(|(won, distance)| {
+            map[if won { 0 } else { 1 }].iter().position(|&other| other as u16 == distance).unwrap_or(0) as u16
+        })).collect();
+        dtz_tables.push((FLAG_MAPPED | FLAG_WIN_PLIES | FLAG_LOSS_PLIES, fill(ranked)));
+        maps.push(map);
+    }
+
+    let path = PathBuf::from(directory).join(&name);
+    write_file(&path.with_extension("rtbw"), false, &layout, info.has_pawns, &wdl_tables, &[])?;
+    write_file(&path.with_extension("rtbz"), true, &layout, info.has_pawns, &dtz_tables, &maps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{initialise_board, write_fen_placement};
+    use crate::position::Position;
+    use crate::tablebase::DRAW;
+
+    const FIXTURES : &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/syzygy");
+
+    fn fixtures() -> Tablebases {
+        let tablebases = Tablebases::open(FIXTURES);
+        assert_eq!(tablebases.len(), 5, "the KQvK, KRvK, KBvK, KNvK and KPvK tables belong in {}", FIXTURES);
+        tablebases
+    }
+
+    fn probe_fen(tablebases: &Tablebases, fen: &str) -> (Wdl, i32) {
+        let position = Position::from_fen(fen).unwrap();
+        let (board, turn) = (position.board(), position.turn());
+        (tablebases.probe_wdl(board, turn).unwrap(), tablebases.probe_dtz(board, turn).unwrap())
+    }
+
+    /// The board with the colours swapped and mirrored top to bottom
+    fn swap_colors(board: &[Tile; 64]) -> [Tile; 64] {
+        let mut swapped = initialise_board();
+        for (index, tile) in board.iter().enumerate() {
+            swapped[index ^ 0o70].piece = tile.piece;
+            swapped[index ^ 0o70].color = tile.color.map(|color| color.opposite());
+        }
+        swapped
+    }
+
+    /// Compares every position of a table with the table generated by
+    /// retrograde analysis, and with the colours swapped the WDL too
+    fn compare_with_generated(tablebases: &Tablebases, material: &str) {
+        let mut tables : HashMap<String, Tablebase> = HashMap::new();
+        tablebase::generate(material, &mut tables).unwrap();
+        let generated = &tables[material];
+        let distances = generated.zeroing_distances(&tables).unwrap();
+        for (index, board, turn, value) in generated.positions() {
+            let (wdl, dtz) = match value {
+                DRAW => (Wdl::Draw, 0),
+                plies if plies % 2 == 1 => (Wdl::Win, distances[index] as i32),
+                _ => (Wdl::Loss, -(distances[index] as i32))
+            };
+            let fen = format!("{} {:?}", write_fen_placement(&board), turn);
+            assert_eq!(tablebases.probe_wdl(&board, turn), Some(wdl), "{}", fen);
+            assert_eq!(tablebases.probe_dtz(&board, turn), Some(dtz), "{}", fen);
+            assert_eq!(tablebases.probe_wdl(&swap_colors(&board), turn.opposite()), Some(wdl), "{} swapped", fen);
+        }
+    }
+
+    #[test]
+    fn probes_known_positions() {
+        let tablebases = fixtures();
+        assert_eq!(probe_fen(&tablebases, "7k/8/6K1/8/8/8/8/1Q6 w - - 0 1"), (Wdl::Win, 1));
+        assert_eq!(probe_fen(&tablebases, "7k/5Q2/6K1/8/8/8/8/8 b - - 0 1"), (Wdl::Draw, 0));
+        // The lone king takes the queen
+        assert_eq!(probe_fen(&tablebases, "6Qk/8/8/8/8/8/8/K7 b - - 0 1"), (Wdl::Draw, 0));
+        // In check, with only Kg8 before Qa8 mates
+        assert_eq!(probe_fen(&tablebases, "7k/8/6K1/8/8/8/8/Q7 b - - 0 1"), (Wdl::Loss, -2));
+        // Mate in 7, and without pawns the mate is the next zeroing move
+        assert_eq!(probe_fen(&tablebases, "8/8/8/8/8/8/R7/K6k w - - 0 1"), (Wdl::Win, 13));
+        // The pawn runs, so its move is the next zeroing move
+        assert_eq!(probe_fen(&tablebases, "8/8/8/8/8/8/P7/K6k w - - 0 1"), (Wdl::Win, 1));
+        assert_eq!(probe_fen(&tablebases, "8/8/8/8/8/8/P7/K6k b - - 0 1"), (Wdl::Loss, -2));
+        assert_eq!(probe_fen(&tablebases, "8/8/4k3/8/4K3/4P3/8/8 w - - 0 1"), (Wdl::Draw, 0));
+        assert_eq!(probe_fen(&tablebases, "8/8/8/8/8/8/8/4K2k w - - 0 1"), (Wdl::Draw, 0));
+        assert_eq!(probe_fen(&tablebases, "8/8/8/8/8/8/2B5/K6k b - - 0 1"), (Wdl::Draw, 0));
+    }
+
+    #[test]
+    fn agrees_with_the_generated_kqk_table() {
+        compare_with_generated(&fixtures(), "KQvK");
+    }
+
+    #[test]
+    fn agrees_with_the_generated_krk_table() {
+        compare_with_generated(&fixtures(), "KRvK");
+    }
+
+    #[test]
+    fn agrees_with_the_generated_kpk_table() {
+        compare_with_generated(&fixtures(), "KPvK");
+    }
+
+    #[test]
+    fn ignores_missing_tables() {
+        let tablebases = Tablebases::open(concat!(env!("CARGO_MANIFEST_DIR"), "/src"));
+        assert!(tablebases.is_empty());
+        let position = Position::from_fen("7k/8/6K1/8/8/8/8/1Q6 w - - 0 1").unwrap();
+        assert_eq!(tablebases.probe_wdl(position.board(), position.turn()), None);
+    }
+}
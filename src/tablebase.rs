@@ -0,0 +1,687 @@
+// Tablebase generation by retrograde analysis
+//
+// Works out the distance to mate of every position of a material signature
+// such as KRvK, the white pieces before the v and the black after it, so
+// endgames can be studied without the Syzygy files.
+//
+// Positions are numbered by the tile of the white king and then the tiles of
+// every other piece, with the side to move last. Boards are mirrored so the
+// white king is on the left half of the board, and for tables without pawns
+// also the upper half, so each position is stored once. Identical pieces are
+// kept in ascending tile order, and every other number is left illegal.
+//
+// Generation first plays every legal move of every position once. Mates
+// are known straight away, as are captures and promotions, which are looked
+// up in the tables of the material they lead to, generated first. Each
+// position also counts its other moves. Then, one distance at a time,
+// positions are walked backwards by taking moves back: a position with a
+// move to a lost position is won one ply later, and a position whose count
+// of moves that are not yet known to lose runs out is lost. Whatever is left
+// once no more can be decided is drawn.
+//
+// Every position takes one byte: the number of plies to mate, odd when the
+// side to move mates and even when it is mated, or DRAW or ILLEGAL. Castling
+// and en passant are left out.
+
+use std::collections::HashMap;
+use std::fs;
+use std::mem;
+
+use crate::position::Position;
+use crate::{generate_legal_moves, initialise_board, is_in_check, make_move, Colors, Pieces, Tile};
+
+const MAGIC : &[u8; 4] = b"CETB";
+pub const MAX_PIECES : usize = 5;
+pub const DRAW : u8 = 254;
+pub const ILLEGAL : u8 = 255;
+// Longest mate that fits in a byte
+const MAX_PLIES : usize = 253;
+// Marks positions that can hold a draw or better whatever their quiet moves
+// turn out to be
+const CAN_NOT_LOSE : u8 = 255;
+
+const KING_STEPS : [(i32, i32); 8] = [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)];
+const KNIGHT_JUMPS : [(i32, i32); 8] = [(-2, -1), (-2, 1), (-1, -2), (-1, 2), (1, -2), (1, 2), (2, -1), (2, 1)];
+const ROOK_LINES : [(i32, i32); 4] = [(0, -1), (-1, 0), (1, 0), (0, 1)];
+const BISHOP_LINES : [(i32, i32); 4] = [(-1, -1), (1, -1), (-1, 1), (1, 1)];
+
+/// A piece of the signature, as its colour and letter
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Slot {
+    color : Colors,
+    letter : char
+}
+
+/// Letter of a piece, as in the material signatures
+fn piece_letter(piece: Pieces) -> char {
+    match piece {
+        Pieces::King { .. } => 'K',
+        Pieces::Queen => 'Q',
+        Pieces::Rook { .. } => 'R',
+        Pieces::Bishop => 'B',
+        Pieces::Knight => 'N',
+        Pieces::Pawn { .. } => 'P'
+    }
+}
+
+/// Signature of a list of pieces, each side ordered from king to pawns
+fn signature(slots: &[Slot]) -> String {
+    let side = |color: Colors| -> String {
+        let mut letters : Vec<char> = slots.iter().filter(|slot| slot.color == color).map(|slot| slot.letter).collect();
+        letters.sort_by_key(|&letter| "KQRBNP".find(letter));
+        letters.into_iter().collect()
+    };
+    format!("{}v{}", side(Colors::White), side(Colors::Black))
+}
+
+/// Signature of the pieces on a board
+fn board_signature(board: &[Tile; 64]) -> String {
+    let slots : Vec<Slot> = board.iter()
+        .filter_map(|tile| Some(Slot { color : tile.color?, letter : piece_letter(tile.piece?) }))
+        .collect();
+    signature(&slots)
+}
+
+/// Reads a signature such as KRvKN into its pieces, the two kings first and
+/// identical pieces next to each other
+fn parse_signature(material: &str) -> Option<Vec<Slot>> {
+    let (white, black) = material.split_once('v')?;
+    let mut slots : Vec<Slot> = vec![Slot { color : Colors::White, letter : 'K' }, Slot { color : Colors::Black, letter : 'K' }];
+    for (side, color) in [(white, Colors::White), (black, Colors::Black)] {
+        let mut letters = side.chars();
+        if letters.next() != Some('K') {
+            return None;
+        }
+        let mut rest : Vec<char> = letters.collect();
+        if !rest.iter().all(|&letter| "QRBNP".contains(letter)) {
+            return None;
+        }
+        rest.sort_by_key(|&letter| "QRBNP".find(letter));
+        slots.extend(rest.into_iter().map(|letter| Slot { color, letter }));
+    }
+    if slots.len() > MAX_PIECES {
+        return None;
+    }
+    Some(slots)
+}
+
+/// Signatures reachable from a table by one capture or promotion
+fn successor_signatures(slots: &[Slot]) -> Vec<String> {
+    let mut successors : Vec<String> = vec![];
+    for (i, slot) in slots.iter().enumerate().skip(2) {
+        let mut captured = slots.to_vec();
+        captured.remove(i);
+        successors.push(signature(&captured));
+        if slot.letter == 'P' {
+            for letter in ['Q', 'R', 'B', 'N'] {
+                let mut promoted = slots.to_vec();
+                promoted[i].letter = letter;
+                successors.push(signature(&promoted));
+            }
+        }
+    }
+    successors.sort();
+    successors.dedup();
+    successors
+}
+
+#[inline]
+fn coords(tile: usize) -> (i32, i32) {
+    ((tile & 7) as i32, (tile >> 3) as i32)
+}
+
+/// Tile at an offset from another, if it is on the board
+#[inline]
+fn offset(tile: usize, (dx, dy): (i32, i32)) -> Option<usize> {
+    let (x, y) = coords(tile);
+    let (x, y) = (x + dx, y + dy);
+    if (0..8).contains(&x) && (0..8).contains(&y) {
+        Some((y * 8 + x) as usize)
+    } else {
+        None
+    }
+}
+
+/// Tiles a piece now on `tile` could have come from by a move that was
+/// neither a capture nor a promotion
+fn origins(slot: Slot, tile: usize, occupied: &[bool; 64]) -> Vec<usize> {
+    let mut tiles : Vec<usize> = vec![];
+    let steps = |offsets: &[(i32, i32)], tiles: &mut Vec<usize>| {
+        tiles.extend(offsets.iter().filter_map(|&step| offset(tile, step)).filter(|&from| !occupied[from]));
+    };
+    let lines = |directions: &[(i32, i32)], tiles: &mut Vec<usize>| {
+        for &(dx, dy) in directions {
+            let mut distance = 1;
+            while let Some(from) = offset(tile, (dx * distance, dy * distance)) {
+                if occupied[from] {
+                    break;
+                }
+                tiles.push(from);
+                distance += 1;
+            }
+        }
+    };
+    match slot.letter {
+        'K' => steps(&KING_STEPS, &mut tiles),
+        'N' => steps(&KNIGHT_JUMPS, &mut tiles),
+        'R' => lines(&ROOK_LINES, &mut tiles),
+        'B' => lines(&BISHOP_LINES, &mut tiles),
+        'Q' => {
+            lines(&ROOK_LINES, &mut tiles);
+            lines(&BISHOP_LINES, &mut tiles);
+        },
+        _ => {
+            // Row 0 is the eighth rank, so White's pawns move towards it
+            let (back, start_row, double_row) = if slot.color == Colors::White { (1, 6, 4) } else { (-1, 1, 3) };
+            if let Some(from) = offset(tile, (0, back)).filter(|&from| !occupied[from]) {
+                if (1..7).contains(&(from >> 3)) {
+                    tiles.push(from);
+                }
+                if (tile >> 3) == double_row {
+                    if let Some(start) = offset(tile, (0, 2 * back)).filter(|&start| !occupied[start] && start >> 3 == start_row) {
+                        tiles.push(start);
+                    }
+                }
+            }
+        }
+    }
+    tiles
+}
+
+/// Distance to mate of every position of one material signature
+pub struct Tablebase {
+    material : String,
+    slots : Vec<Slot>,
+    has_pawns : bool,
+    values : Vec<u8>
+}
+impl Tablebase {
+    fn new(material: &str) -> Option<Tablebase> {
+        let slots = parse_signature(material)?;
+        let has_pawns = slots.iter().any(|slot| slot.letter == 'P');
+        let mut tablebase = Tablebase {
+            material : signature(&slots),
+            slots,
+            has_pawns,
+            values : vec![]
+        };
+        tablebase.values = vec![DRAW; tablebase.size()];
+        Some(tablebase)
+    }
+
+    /// Number of positions, legal or not
+    fn size(&self) -> usize {
+        let king_tiles = if self.has_pawns { 32 } else { 16 };
+        king_tiles * 64usize.pow(self.slots.len() as u32 - 1) * 2
+    }
+
+    /// Number of a position from the tile of each piece, mirroring the
+    /// tiles in place
+    fn index(&self, tiles: &mut [usize], turn: Colors) -> usize {
+        if tiles[0] & 7 >= 4 {
+            tiles.iter_mut().for_each(|tile| *tile ^= 7);
+        }
+        if !self.has_pawns && tiles[0] >> 3 >= 4 {
+            tiles.iter_mut().for_each(|tile| *tile ^= 0o70);
+        }
+        // Identical pieces are kept in ascending order
+        let mut start = 0;
+        for i in 1..=tiles.len() {
+            if i == tiles.len() || self.slots[i] != self.slots[start] {
+                tiles[start..i].sort_unstable();
+                start = i;
+            }
+        }
+        let king = (tiles[0] >> 3) * 4 + (tiles[0] & 7);
+        let index = tiles[1..].iter().fold(king, |index, &tile| index * 64 + tile);
+        index * 2 + turn as usize
+    }
+
+    /// Tile of each piece and the side to move of a position number
+    fn position(&self, mut index: usize) -> (Vec<usize>, Colors) {
+        let turn = if index & 1 == 0 { Colors::White } else { Colors::Black };
+        index >>= 1;
+        let mut tiles : Vec<usize> = vec![0; self.slots.len()];
+        for tile in tiles[1..].iter_mut().rev() {
+            *tile = index % 64;
+            index /= 64;
+        }
+        tiles[0] = (index / 4) * 8 + index % 4;
+        (tiles, turn)
+    }
+
+    /// Whether the tiles are a position of the table, leaving out checks
+    fn is_valid(&self, tiles: &[usize]) -> bool {
+        for i in 0..tiles.len() {
+            if tiles[..i].contains(&tiles[i]) {
+                return false;
+            }
+            if i > 0 && self.slots[i] == self.slots[i - 1] && tiles[i] < tiles[i - 1] {
+                return false;
+            }
+            if self.slots[i].letter == 'P' && !(1..7).contains(&(tiles[i] >> 3)) {
+                return false;
+            }
+        }
+        let ((king_x, king_y), (other_x, other_y)) = (coords(tiles[0]), coords(tiles[1]));
+        king_x.abs_diff(other_x).max(king_y.abs_diff(other_y)) > 1
+    }
+
+    fn board(&self, tiles: &[usize]) -> [Tile; 64] {
+        let mut board = initialise_board();
+        for (slot, &tile) in self.slots.iter().zip(tiles) {
+            let start_row = if slot.color == Colors::White { 6 } else { 1 };
+            board[tile].color = Some(slot.color);
+            board[tile].piece = Some(match slot.letter {
+                'K' => Pieces::King { has_moved : true },
+                'Q' => Pieces::Queen,
+                'R' => Pieces::Rook { has_moved : true },
+                'B' => Pieces::Bishop,
+                'N' => Pieces::Knight,
+                _ => Pieces::Pawn { has_moved : tile >> 3 != start_row, en_passantable : false }
+            });
+        }
+        board
+    }
+
+    /// Tile of each piece on the board, or None when the material differs
+    fn tiles_of_board(&self, board: &[Tile; 64]) -> Option<Vec<usize>> {
+        let mut tiles : Vec<Option<usize>> = vec![None; self.slots.len()];
+        for (index, tile) in board.iter().enumerate() {
+            let (color, piece) = match (tile.color, tile.piece) {
+                (Some(color), Some(piece)) => (color, piece),
+                _ => continue
+            };
+            let slot = Slot { color, letter : piece_letter(piece) };
+            let free = (0..self.slots.len()).find(|&i| self.slots[i] == slot && tiles[i].is_none())?;
+            tiles[free] = Some(index);
+        }
+        tiles.into_iter().collect()
+    }
+
+    /// Numbers of the positions that lead to the given one by a move other
+    /// than a capture or promotion, and a pawn move only with `pawn_moves`
+    fn predecessors(&self, index: usize, pawn_moves: bool) -> Vec<usize> {
+        let (tiles, turn) = self.position(index);
+        let mover = turn.opposite();
+        let mut occupied = [false; 64];
+        for &tile in &tiles {
+            occupied[tile] = true;
+        }
+        let mut predecessors : Vec<usize> = vec![];
+        for (i, &slot) in self.slots.iter().enumerate() {
+            if slot.color != mover || (slot.letter == 'P' && !pawn_moves) {
+                continue;
+            }
+            for from in origins(slot, tiles[i], &occupied) {
+                let mut before = tiles.clone();
+                before[i] = from;
+                predecessors.push(self.index(&mut before, mover));
+            }
+        }
+        predecessors
+    }
+
+    /// Looks up a board of this table's material, giving the plies to mate
+    /// for the side to move, DRAW or ILLEGAL
+    pub fn probe(&self, board: &[Tile; 64], turn: Colors) -> Option<u8> {
+        let mut tiles = self.tiles_of_board(board)?;
+        let index = self.index(&mut tiles, turn);
+        self.values.get(index).copied()
+    }
+
+    pub fn material(&self) -> &str {
+        &self.material
+    }
+
+    /// Every legal position with its number, board, side to move and value
+    pub fn positions(&self) -> impl Iterator<Item = (usize, [Tile; 64], Colors, u8)> + '_ {
+        (0..self.values.len()).filter(|&index| self.values[index] != ILLEGAL).map(|index| {
+            let (tiles, turn) = self.position(index);
+            (index, self.board(&tiles), turn, self.values[index])
+        })
+    }
+
+    /// Plies to the next capture, pawn move or mate of every position won or
+    /// lost, with the winner hurrying and the loser holding out, as Syzygy DTZ
+    /// tables count them. A mated position, or a lost one whose every move
+    /// is a capture or pawn move, counts 1, and drawn or illegal ones 0.
+    /// `tables` must hold the tables the captures and promotions lead to.
+    pub fn zeroing_distances(&self, tables: &HashMap<String, Tablebase>) -> Result<Vec<u8>, String> {
+        let size = self.values.len();
+        let too_long = || format!("{} has distances to zeroing longer than {} plies", self.material, MAX_PLIES);
+        let mut distances : Vec<u8> = vec![0; size];
+        // Quiet moves of each lost position whose distance is not yet known
+        let mut remaining : Vec<u8> = vec![0; size];
+        let mut pending : Vec<Vec<u32>> = vec![vec![]; MAX_PLIES + 1];
+
+        for (index, board, turn, value) in self.positions() {
+            if value == DRAW {
+                continue;
+            }
+            let won = value % 2 == 1;
+            // Mated positions count 1, as do wins with a mate or a capture
+            // or pawn move that keeps the win
+            let mut counts_one = value == 0;
+            let mut quiet_moves = 0;
+            for mv in generate_legal_moves(&board, turn) {
+                let zeroing = board[mv.to].piece.is_some() || matches!(board[mv.from].piece, Some(Pieces::Pawn { .. }));
+                let mut after = board;
+                make_move(&mut after, mv);
+                let after_value = if board_signature(&after) == self.material {
+                    self.probe(&after, turn.opposite())
+                } else {
+                    probe_tables(tables, &after, turn.opposite())
+                }.ok_or_else(|| format!("no table for {}", board_signature(&after)))?;
+                if won && (after_value == 0 || (zeroing && after_value != DRAW && after_value % 2 == 0)) {
+                    counts_one = true;
+                }
+                if !zeroing {
+                    quiet_moves += 1;
+                }
+            }
+            if counts_one || (!won && quiet_moves == 0) {
+                pending[1].push(index as u32);
+            } else if !won {
+                remaining[index] = quiet_moves;
+            }
+        }
+
+        for plies in 1..MAX_PLIES {
+            for index in mem::take(&mut pending[plies]) {
+                let index = index as usize;
+                if distances[index] != 0 {
+                    continue;
+                }
+                distances[index] = plies as u8;
+                let lost = self.values[index].is_multiple_of(2);
+                for predecessor in self.predecessors(index, false) {
+                    let value = self.values[predecessor];
+                    if distances[predecessor] != 0 || value == DRAW || value == ILLEGAL {
+                        continue;
+                    }
+                    if value % 2 == 1 {
+                        // A quiet move to a lost position
+                        if !lost {
+                            continue;
+                        }
+                    } else {
+                        // Lost once every quiet move is known, by the longest
+                        if lost || remaining[predecessor] == 0 {
+                            continue;
+                        }
+                        remaining[predecessor] -= 1;
+                        if remaining[predecessor] > 0 {
+                            continue;
+                        }
+                    }
+                    pending.get_mut(plies + 1).ok_or_else(too_long)?.push(predecessor as u32);
+                }
+            }
+        }
+        Ok(distances)
+    }
+
+    /// Writes the table as the magic number, the signature and one byte per
+    /// position
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let mut data : Vec<u8> = MAGIC.to_vec();
+        data.push(self.material.len() as u8);
+        data.extend_from_slice(self.material.as_bytes());
+        data.extend_from_slice(&self.values);
+        fs::write(path, data).map_err(|error| format!("can not write {}: {}", path, error))
+    }
+
+    pub fn load(path: &str) -> Result<Tablebase, String> {
+        let data = fs::read(path).map_err(|error| format!("can not read {}: {}", path, error))?;
+        let corrupted = || format!("{} is not a tablebase", path);
+        if data.get(..4) != Some(MAGIC.as_slice()) {
+            return Err(corrupted());
+        }
+        let length = *data.get(4).ok_or_else(corrupted)? as usize;
+        let material = data.get(5..5 + length).and_then(|name| std::str::from_utf8(name).ok()).ok_or_else(corrupted)?;
+        let mut tablebase = Tablebase::new(material).ok_or_else(corrupted)?;
+        let values = &data[5 + length..];
+        if values.len() != tablebase.size() {
+            return Err(corrupted());
+        }
+        tablebase.values = values.to_vec();
+        Ok(tablebase)
+    }
+}
+
+/// The signature of a material as tables are named, such as KRvK for RKvK
+pub fn signature_of(material: &str) -> Option<String> {
+    parse_signature(material).map(|slots| signature(&slots))
+}
+
+/// Looks a board up in whichever table holds its material
+fn probe_tables(tables: &HashMap<String, Tablebase>, board: &[Tile; 64], turn: Colors) -> Option<u8> {
+    tables.get(&board_signature(board))?.probe(board, turn)
+}
+
+/// Generates the table of a signature, and first every table its captures
+/// and promotions lead to, adding them all to `tables`
+pub fn generate(material: &str, tables: &mut HashMap<String, Tablebase>) -> Result<(), String> {
+    let mut tablebase = Tablebase::new(material)
+        .ok_or_else(|| format!("invalid material {}, expected something like KRvK with at most {} pieces", material, MAX_PIECES))?;
+    if tables.contains_key(&tablebase.material) {
+        return Ok(());
+    }
+    for successor in successor_signatures(&tablebase.slots) {
+        generate(&successor, tables)?;
+    }
+
+    let size = tablebase.size();
+    let too_long = || format!("{} has mates longer than {} plies", tablebase.material, MAX_PLIES);
+    // Quiet moves of each position not yet known to lose, and the longest
+    // loss its captures and promotions lead to
+    let mut remaining : Vec<u8> = vec![CAN_NOT_LOSE; size];
+    let mut conversion_loss : Vec<u8> = vec![0; size];
+    // Positions waiting to be given each number of plies to mate
+    let mut pending : Vec<Vec<u32>> = vec![vec![]; MAX_PLIES + 1];
+
+    for index in 0..size {
+        let (tiles, turn) = tablebase.position(index);
+        if !tablebase.is_valid(&tiles) {
+            tablebase.values[index] = ILLEGAL;
+            continue;
+        }
+        let board = tablebase.board(&tiles);
+        if is_in_check(&board, turn.opposite()) {
+            tablebase.values[index] = ILLEGAL;
+            continue;
+        }
+        let moves = generate_legal_moves(&board, turn);
+        if moves.is_empty() {
+            // Stalemates stay drawn
+            if is_in_check(&board, turn) {
+                pending[0].push(index as u32);
+            }
+            continue;
+        }
+
+        let mut quiet_moves = 0;
+        let mut fastest_win : Option<usize> = None;
+        let mut can_draw = false;
+        let mut longest_loss = 0;
+        for mv in moves {
+            if board[mv.to].piece.is_none() && mv.promotion.is_none() {
+                quiet_moves += 1;
+                continue;
+            }
+            let mut after = board;
+            make_move(&mut after, mv);
+            match probe_tables(tables, &after, turn.opposite()) {
+                Some(DRAW) => can_draw = true,
+                Some(plies) if plies % 2 == 0 => fastest_win = Some(fastest_win.map_or(plies as usize + 1, |win| win.min(plies as usize + 1))),
+                Some(plies) => longest_loss = longest_loss.max(plies as usize + 1),
+                None => return Err(format!("no table for {}", board_signature(&after)))
+            }
+        }
+
+        if let Some(win) = fastest_win {
+            pending.get_mut(win).ok_or_else(too_long)?.push(index as u32);
+        } else if !can_draw {
+            if quiet_moves == 0 {
+                pending.get_mut(longest_loss).ok_or_else(too_long)?.push(index as u32);
+            } else {
+                remaining[index] = quiet_moves;
+                conversion_loss[index] = longest_loss as u8;
+            }
+        }
+    }
+
+    for plies in 0..=MAX_PLIES {
+        for index in mem::take(&mut pending[plies]) {
+            let index = index as usize;
+            if tablebase.values[index] != DRAW {
+                continue;
+            }
+            tablebase.values[index] = plies as u8;
+
+            for predecessor in tablebase.predecessors(index, true) {
+                if tablebase.values[predecessor] != DRAW {
+                    continue;
+                }
+                let next = if plies % 2 == 0 {
+                    // A move to a lost position wins
+                    plies + 1
+                } else if remaining[predecessor] != CAN_NOT_LOSE {
+                    // Lost once every move is known to lose, by the longest
+                    remaining[predecessor] -= 1;
+                    if remaining[predecessor] > 0 {
+                        continue;
+                    }
+                    (plies + 1).max(conversion_loss[predecessor] as usize)
+                } else {
+                    continue;
+                };
+                pending.get_mut(next).ok_or_else(too_long)?.push(predecessor as u32);
+            }
+        }
+    }
+
+    tables.insert(tablebase.material.clone(), tablebase);
+    Ok(())
+}
+
+/// Describes a stored value for the side to move
+pub fn describe(value: u8) -> String {
+    match value {
+        DRAW => String::from("draw"),
+        ILLEGAL => String::from("illegal position"),
+        0 => String::from("checkmated"),
+        plies if plies % 2 == 1 => format!("mate in {}", plies.div_ceil(2)),
+        plies => format!("mated in {}", plies / 2)
+    }
+}
+
+/// Generates the table of a signature and writes it to `output`, printing
+/// how its positions turn out
+pub fn run_generate(material: &str, output: &str) -> Result<(), String> {
+    let mut tables : HashMap<String, Tablebase> = HashMap::new();
+    generate(material, &mut tables)?;
+    let tablebase = parse_signature(material).and_then(|slots| tables.remove(&signature(&slots)))
+        .ok_or_else(|| format!("{} is not a material signature", material))?;
+
+    for (turn, name) in [(0, "White"), (1, "Black")] {
+        let values = tablebase.values.iter().skip(turn).step_by(2);
+        let (mut wins, mut draws, mut losses, mut longest) = (0, 0, 0, 0);
+        for &value in values {
+            match value {
+                ILLEGAL => (),
+                DRAW => draws += 1,
+                plies if plies % 2 == 1 => {
+                    wins += 1;
+                    longest = longest.max(plies);
+                },
+                _ => losses += 1
+            }
+        }
+        print!("{} {} to move: {} won, {} drawn, {} lost", tablebase.material, name, wins, draws, losses);
+        if wins > 0 {
+            print!(", longest {}", describe(longest));
+        }
+        println!();
+    }
+    tablebase.save(output)
+}
+
+/// Prints the value of a position from a table file
+pub fn run_probe(path: &str, fen: &str) -> Result<(), String> {
+    let tablebase = Tablebase::load(path)?;
+    let position = Position::from_fen(fen).map_err(|error| format!("\"{}\" is not a position: {}", fen, error))?;
+    let value = tablebase.probe(position.board(), position.turn()).ok_or_else(|| format!("the position is not {}", tablebase.material()))?;
+    println!("{}", describe(value));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::Position;
+
+    /// The longest mate of the side to move, in moves, of a generated table
+    fn longest_mate(material: &str) -> usize {
+        let mut tables : HashMap<String, Tablebase> = HashMap::new();
+        generate(material, &mut tables).unwrap();
+        let longest = tables[material].values.iter().filter(|&&value| value < DRAW && value % 2 == 1).max().copied().unwrap_or(0);
+        (longest as usize).div_ceil(2)
+    }
+
+    fn probe_fen(tablebase: &Tablebase, fen: &str) -> u8 {
+        let position = Position::from_fen(fen).unwrap();
+        tablebase.probe(position.board(), position.turn()).unwrap()
+    }
+
+    #[test]
+    fn finds_the_longest_mates() {
+        assert_eq!(longest_mate("KQvK"), 10);
+        assert_eq!(longest_mate("KRvK"), 16);
+        assert_eq!(longest_mate("KPvK"), 28);
+    }
+
+    #[test]
+    fn counts_plies_to_zeroing() {
+        let mut tables : HashMap<String, Tablebase> = HashMap::new();
+        generate("KPvK", &mut tables).unwrap();
+        // Without pawns the next zeroing move is the mate
+        let kqk = &tables["KQvK"];
+        let distances = kqk.zeroing_distances(&tables).unwrap();
+        for (index, _, _, value) in kqk.positions() {
+            let expected = match value {
+                DRAW => 0,
+                0 => 1,
+                plies => plies
+            };
+            assert_eq!(distances[index], expected);
+        }
+
+        let kpk = &tables["KPvK"];
+        let distances = kpk.zeroing_distances(&tables).unwrap();
+        let distance = |fen: &str| {
+            let position = Position::from_fen(fen).unwrap();
+            let mut tiles = kpk.tiles_of_board(position.board()).unwrap();
+            distances[kpk.index(&mut tiles, position.turn())]
+        };
+        // The pawn runs at once, or after the lone king's move
+        assert_eq!(distance("8/8/8/8/8/8/P7/K6k w - - 0 1"), 1);
+        assert_eq!(distance("8/8/8/8/8/8/P7/K6k b - - 0 1"), 2);
+        // The king blocks its pawn, so has to step aside first
+        assert!(distance("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1") >= 3);
+        assert_eq!(distance("8/8/4k3/8/4K3/4P3/8/8 w - - 0 1"), 0);
+    }
+
+    #[test]
+    fn probes_mates_and_stalemates() {
+        let mut tables : HashMap<String, Tablebase> = HashMap::new();
+        generate("KQvK", &mut tables).unwrap();
+        let kqk = &tables["KQvK"];
+        assert_eq!(describe(probe_fen(kqk, "7k/8/6K1/8/8/8/8/1Q6 w - - 0 1")), "mate in 1");
+        assert_eq!(describe(probe_fen(kqk, "Q6k/8/6K1/8/8/8/8/8 b - - 0 1")), "checkmated");
+        assert_eq!(probe_fen(kqk, "7k/5Q2/6K1/8/8/8/8/8 b - - 0 1"), DRAW);
+        // The lone king takes the queen
+        assert_eq!(probe_fen(kqk, "6Qk/8/8/8/8/8/8/K7 b - - 0 1"), DRAW);
+    }
+}
@@ -0,0 +1,116 @@
+// Terminal play mode
+//
+// Plays a game on the terminal without opening a window, for SSH sessions
+// and headless machines. The board is drawn as text after every move, and
+// moves are typed in SAN, such as "Nf3" or "exd5", or in coordinates, such
+// as "g1f3" or "e7e8q". A few commands work on the game:
+//
+//     undo   takes back the last move
+//     fen    prints the FEN of the board
+//     flip   turns the board round
+//     hint   searches for the best move and prints it
+//     help   lists the commands
+//     quit   leaves
+//
+// The clocks of the FEN are kept as the game is played, and the end of the
+// game, by mate or stalemate, is announced.
+
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+
+use crate::san::{move_to_san, parse_san};
+use crate::search::{format_score, search, SearchLimits};
+use crate::square::parse_uci_move;
+use crate::text_board::{board_to_text, TextBoardStyle};
+use crate::time_management::{TimeControl, TimeManager};
+use crate::transposition_table::TranspositionTable;
+use crate::position::Position;
+use crate::{Colors, Move};
+
+const HINT_TIME : Duration = Duration::from_secs(2);
+const HASH_MEGABYTES : usize = 16;
+const HELP : &str = "Type a move such as Nf3 or g1f3, or one of: undo, fen, flip, hint, help, quit";
+
+/// Reads a move in coordinates or SAN, coordinates first as they can not
+/// be mistaken for SAN
+fn read_move(position: &Position, input: &str) -> Result<Move, String> {
+    parse_uci_move(position, input).or_else(|error| {
+        parse_san(position, input).map_err(|san_error| {
+            // The coordinate error says more when the input looked like coordinates
+            if input.len() >= 4 && input.as_bytes()[1].is_ascii_digit() { error } else { san_error }
+        })
+    })
+}
+
+/// Plays a game on the terminal from the position given, coloring the board
+/// unless asked not to or when the output is not a terminal
+pub fn run(start: Position, unicode: bool, colors: bool) {
+    // Every position of the game with the move that reached it
+    let mut history : Vec<(Position, Option<Move>)> = vec![(start, None)];
+    let mut style = TextBoardStyle { unicode, flipped: false, colors: colors && io::stdout().is_terminal() };
+    let table = TranspositionTable::new(HASH_MEGABYTES);
+
+    println!("{}", HELP);
+    let mut redraw = true;
+    let mut lines = io::stdin().lock().lines();
+    loop {
+        let (position, last_move) = history[history.len() - 1];
+        if redraw {
+            println!();
+            print!("{}", board_to_text(position.board(), style, last_move));
+            let side = if position.turn() == Colors::White { "White" } else { "Black" };
+            match (position.legal_moves().is_empty(), position.is_in_check()) {
+                (true, true) => println!("Checkmate, {} loses", side),
+                (true, false) => println!("Stalemate"),
+                (false, true) => println!("{} to move, in check", side),
+                (false, false) => println!("{} to move", side)
+            }
+        }
+        redraw = false;
+
+        print!("> ");
+        let _ = io::stdout().flush();
+        let line = match lines.next() {
+            Some(Ok(line)) => line,
+            _ => break
+        };
+        match line.trim() {
+            "" => (),
+            "quit" | "exit" => break,
+            "help" => println!("{}", HELP),
+            "fen" => println!("{}", position.to_fen()),
+            "flip" => {
+                style.flipped = !style.flipped;
+                redraw = true;
+            },
+            "undo" => {
+                if history.len() > 1 {
+                    history.pop();
+                    redraw = true;
+                } else {
+                    println!("There is no move to take back");
+                }
+            },
+            "hint" => {
+                let mut limits = SearchLimits::infinite();
+                limits.time = TimeManager::new(&TimeControl { move_time: Some(HINT_TIME), ..TimeControl::default() });
+                let result = search(&position, limits, &table, &AtomicBool::new(false), |_| {});
+                match (result.best_move, result.lines.first()) {
+                    (Some(mv), Some(line)) => println!("Try {} ({})", move_to_san(&position, mv), format_score(line.score)),
+                    _ => println!("There is no move to play")
+                }
+            },
+            input => match read_move(&position, input) {
+                Ok(mv) => {
+                    println!("{}", move_to_san(&position, mv));
+                    let mut next = position;
+                    next.play_move(mv);
+                    history.push((next, Some(mv)));
+                    redraw = true;
+                },
+                Err(error) => println!("{}", error)
+            }
+        }
+    }
+}
@@ -0,0 +1,151 @@
+// Text board diagrams
+//
+// Draws the board as eight rows of text with the ranks and files labelled
+// around it, for terminals and logs:
+//
+//        a  b  c  d  e  f  g  h
+//     8  r  n  b  q  k  b  n  r  8
+//     7  p  p  p  p  .  p  p  p  7
+//     ...
+//
+// Pieces are FEN letters, upper case for White, or Unicode chess glyphs.
+// The board can be drawn from Black's side, and the tiles of the last move
+// can be marked with ANSI colours on terminals that show them.
+
+use crate::{get_array_index, Colors, Move, Pieces, Tile};
+
+// Black text on a yellow background, and back to normal
+const HIGHLIGHT : &str = "\x1b[30;43m";
+const RESET : &str = "\x1b[0m";
+
+/// How a board is drawn as text
+#[derive(Debug, Copy, Clone, Default)]
+pub struct TextBoardStyle {
+    // Chess glyphs rather than FEN letters
+    pub unicode : bool,
+    // Black at the bottom
+    pub flipped : bool,
+    // The last move marked with ANSI colours
+    pub colors : bool
+}
+
+/// The character a tile is drawn with
+fn tile_character(tile: &Tile, unicode: bool) -> char {
+    let (piece, color) = match (tile.piece, tile.color) {
+        (Some(piece), Some(color)) => (piece, color),
+        _ => return if unicode { '·' } else { '.' }
+    };
+    let kind = match piece {
+        Pieces::King { .. } => 0,
+        Pieces::Queen => 1,
+        Pieces::Rook { .. } => 2,
+        Pieces::Bishop => 3,
+        Pieces::Knight => 4,
+        Pieces::Pawn { .. } => 5
+    };
+    let characters : [char; 6] = match (unicode, color) {
+        (false, Colors::White) => ['K', 'Q', 'R', 'B', 'N', 'P'],
+        (false, Colors::Black) => ['k', 'q', 'r', 'b', 'n', 'p'],
+        (true, Colors::White) => ['♔', '♕', '♖', '♗', '♘', '♙'],
+        (true, Colors::Black) => ['♚', '♛', '♜', '♝', '♞', '♟']
+    };
+    characters[kind]
+}
+
+/// Draws the board as text, marking the tiles of the last move when the
+/// style has colours
+pub fn board_to_text(board: &[Tile; 64], style: TextBoardStyle, last_move: Option<Move>) -> String {
+    // Columns and rows in the order they are drawn, left to right and top
+    // to bottom
+    let order = |index: usize| if style.flipped { 7 - index } else { index };
+    let files : String = (0..8).map(|x| format!(" {} ", (b'a' + order(x) as u8) as char)).collect();
+
+    let mut text = format!("  {}\n", files.trim_end());
+    for y in (0..8).map(order) {
+        let rank = 8 - y;
+        text.push_str(&format!("{} ", rank));
+        for x in (0..8).map(order) {
+            let index = get_array_index(x, y);
+            let cell = format!(" {} ", tile_character(&board[index], style.unicode));
+            let moved = last_move.is_some_and(|mv| mv.from == index || mv.to == index);
+            if style.colors && moved {
+                text.push_str(HIGHLIGHT);
+                text.push_str(&cell);
+                text.push_str(RESET);
+            } else {
+                text.push_str(&cell);
+            }
+        }
+        text.push_str(&format!(" {}\n", rank));
+    }
+    text.push_str(&format!("  {}\n", files.trim_end()));
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::Position;
+
+    fn start_position(style: TextBoardStyle) -> String {
+        board_to_text(Position::default().board(), style, None)
+    }
+
+    #[test]
+    fn draws_the_start_position_in_letters() {
+        assert_eq!(start_position(TextBoardStyle::default()), "   a  b  c  d  e  f  g  h
+8  r  n  b  q  k  b  n  r  8
+7  p  p  p  p  p  p  p  p  7
+6  .  .  .  .  .  .  .  .  6
+5  .  .  .  .  .  .  .  .  5
+4  .  .  .  .  .  .  .  .  4
+3  .  .  .  .  .  .  .  .  3
+2  P  P  P  P  P  P  P  P  2
+1  R  N  B  Q  K  B  N  R  1
+   a  b  c  d  e  f  g  h
+");
+    }
+
+    #[test]
+    fn draws_the_start_position_in_glyphs() {
+        assert_eq!(start_position(TextBoardStyle { unicode: true, ..TextBoardStyle::default() }), "   a  b  c  d  e  f  g  h
+8  ♜  ♞  ♝  ♛  ♚  ♝  ♞  ♜  8
+7  ♟  ♟  ♟  ♟  ♟  ♟  ♟  ♟  7
+6  ·  ·  ·  ·  ·  ·  ·  ·  6
+5  ·  ·  ·  ·  ·  ·  ·  ·  5
+4  ·  ·  ·  ·  ·  ·  ·  ·  4
+3  ·  ·  ·  ·  ·  ·  ·  ·  3
+2  ♙  ♙  ♙  ♙  ♙  ♙  ♙  ♙  2
+1  ♖  ♘  ♗  ♕  ♔  ♗  ♘  ♖  1
+   a  b  c  d  e  f  g  h
+");
+    }
+
+    #[test]
+    fn draws_the_start_position_from_blacks_side() {
+        assert_eq!(start_position(TextBoardStyle { flipped: true, ..TextBoardStyle::default() }), "   h  g  f  e  d  c  b  a
+1  R  N  B  K  Q  B  N  R  1
+2  P  P  P  P  P  P  P  P  2
+3  .  .  .  .  .  .  .  .  3
+4  .  .  .  .  .  .  .  .  4
+5  .  .  .  .  .  .  .  .  5
+6  .  .  .  .  .  .  .  .  6
+7  p  p  p  p  p  p  p  p  7
+8  r  n  b  k  q  b  n  r  8
+   h  g  f  e  d  c  b  a
+");
+    }
+
+    #[test]
+    fn marks_the_last_move_only_in_colour() {
+        let mut position = Position::default();
+        let mv = crate::square::parse_uci_move(&position, "e2e4").unwrap();
+        position.play_move(mv);
+        let plain = board_to_text(position.board(), TextBoardStyle::default(), Some(mv));
+        assert!(!plain.contains('\x1b'));
+        let colored = board_to_text(position.board(), TextBoardStyle { colors: true, ..TextBoardStyle::default() }, Some(mv));
+        assert_eq!(colored.matches(HIGHLIGHT).count(), 2);
+        assert!(colored.contains(&format!("4  .  .  .  . {} P {} .", HIGHLIGHT, RESET)), "{}", colored);
+        assert!(colored.contains(&format!("2  P  P  P  P {} . {} P", HIGHLIGHT, RESET)), "{}", colored);
+    }
+}
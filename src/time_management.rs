@@ -0,0 +1,205 @@
+// Time management for clock based play
+//
+// Given the clock state sent with a UCI `go` command, the time manager picks
+// a soft limit (the time we would like to spend on the move) and a hard limit
+// (the time the search must never go past). Iterative deepening checks the
+// soft limit between iterations and the search checks the hard limit while
+// it runs.
+
+use std::time::{Duration, Instant};
+
+// Time kept back for communication with the GUI
+const MOVE_OVERHEAD : Duration = Duration::from_millis(30);
+// Number of moves assumed to be left when the clock has no moves to go
+const DEFAULT_MOVES_TO_GO : u32 = 30;
+// How many times longer the next iteration is expected to take than the last
+const BRANCHING_FACTOR : u32 = 2;
+// Extra fraction of the soft limit granted each time the best move changes,
+// in percent, and the most that can be granted in total
+const INSTABILITY_EXTENSION : u32 = 40;
+const MAX_INSTABILITY_EXTENSION : u32 = 200;
+
+/// Clock state for one move, as given by `go wtime btime winc binc movestogo`
+/// or `go movetime`
+#[derive(Debug, Copy, Clone, Default)]
+pub struct TimeControl {
+    pub time_left : Option<Duration>,
+    pub increment : Duration,
+    pub moves_to_go : Option<u32>,
+    pub move_time : Option<Duration>
+}
+
+#[derive(Debug, Clone)]
+pub struct TimeManager {
+    start : Instant,
+    soft_limit : Option<Duration>,
+    hard_limit : Option<Duration>,
+    // Percentage added to the soft limit while the best move is unstable
+    extension : u32
+}
+impl TimeManager {
+    /// Creates a time manager with no limits, the search runs until stopped
+    pub fn infinite() -> TimeManager {
+        TimeManager {
+            start : Instant::now(),
+            soft_limit : None,
+            hard_limit : None,
+            extension : 0
+        }
+    }
+
+    pub fn new(control: &TimeControl) -> TimeManager {
+        let mut manager = TimeManager::infinite();
+
+        if let Some(move_time) = control.move_time {
+            // A fixed time per move is used in full
+            let limit = move_time.saturating_sub(MOVE_OVERHEAD);
+            manager.soft_limit = Some(limit);
+            manager.hard_limit = Some(limit);
+        } else if let Some(time_left) = control.time_left {
+            let available = time_left.saturating_sub(MOVE_OVERHEAD);
+            let moves_to_go = control.moves_to_go.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+
+            // Spread the remaining time over the moves left, spending most of
+            // the increment as it comes back after the move
+            let soft_limit = available / moves_to_go + control.increment * 3 / 4;
+            // The hard limit allows overrunning the soft limit on difficult
+            // moves, but never uses more than a third of the remaining time,
+            // or all of it when this is the last move before the time control
+            let hard_limit = if moves_to_go == 1 {
+                available
+            } else {
+                (soft_limit * 4).min(available / 3).max(soft_limit)
+            };
+
+            manager.soft_limit = Some(soft_limit.min(available));
+            manager.hard_limit = Some(hard_limit.min(available));
+        }
+
+        manager
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Whether the search has run past the hard limit and must stop now
+    pub fn hard_limit_reached(&self) -> bool {
+        match self.hard_limit {
+            Some(limit) => self.elapsed() >= limit,
+            None => false
+        }
+    }
+
+    /// The soft limit including any extension for an unstable best move
+    fn extended_soft_limit(&self) -> Option<Duration> {
+        let limit = self.soft_limit? * (100 + self.extension) / 100;
+        Some(match self.hard_limit {
+            Some(hard_limit) => limit.min(hard_limit),
+            None => limit
+        })
+    }
+
+    /// Decides whether another iteration of iterative deepening is worth
+    /// starting, given how long the last one took. If the next iteration is
+    /// unlikely to finish before the soft limit it would only be thrown away.
+    pub fn should_start_iteration(&self, last_iteration : Duration) -> bool {
+        match self.extended_soft_limit() {
+            Some(limit) => self.elapsed() + last_iteration * BRANCHING_FACTOR < limit,
+            None => true
+        }
+    }
+
+    /// Records whether the best move changed in the last iteration. A
+    /// changing best move means the position is not yet understood, so more
+    /// time is allowed; a stable one slowly gives the extra time back.
+    pub fn update_best_move_stability(&mut self, best_move_changed : bool) {
+        if best_move_changed {
+            self.extension = (self.extension + INSTABILITY_EXTENSION).min(MAX_INSTABILITY_EXTENSION);
+        } else {
+            self.extension /= 2;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clock(time_left: u64, increment: u64, moves_to_go: Option<u32>) -> TimeManager {
+        TimeManager::new(&TimeControl {
+            time_left : Some(Duration::from_millis(time_left)),
+            increment : Duration::from_millis(increment),
+            moves_to_go,
+            move_time : None
+        })
+    }
+
+    fn limits(manager: &TimeManager) -> (Option<Duration>, Option<Duration>) {
+        (manager.soft_limit, manager.hard_limit)
+    }
+
+    fn millis(millis: u64) -> Option<Duration> {
+        Some(Duration::from_millis(millis))
+    }
+
+    #[test]
+    fn splits_the_clock_into_soft_and_hard_limits() {
+        // 59970ms over 30 moves, with the hard limit four times the soft one
+        assert_eq!(limits(&clock(60000, 0, None)), (millis(1999), millis(7996)));
+        // Three quarters of the increment is spent on top
+        assert_eq!(limits(&clock(60000, 1000, None)), (millis(2749), millis(10996)));
+        assert_eq!(limits(&clock(60000, 0, Some(10))), (millis(5997), millis(19990)));
+        let move_time = TimeManager::new(&TimeControl { move_time : millis(500), ..TimeControl::default() });
+        assert_eq!(limits(&move_time), (millis(470), millis(470)));
+        assert_eq!(limits(&TimeManager::new(&TimeControl::default())), (None, None));
+    }
+
+    #[test]
+    fn spends_everything_on_the_last_move_before_the_control() {
+        assert_eq!(limits(&clock(10000, 0, Some(1))), (millis(9970), millis(9970)));
+        // No moves to go is taken as one
+        assert_eq!(limits(&clock(10000, 0, Some(0))), (millis(9970), millis(9970)));
+    }
+
+    #[test]
+    fn caps_the_hard_limit() {
+        // Four times the soft limit would be more than a third of the clock
+        assert_eq!(limits(&clock(60000, 0, Some(5))), (millis(11994), millis(19990)));
+        // A large increment on a nearly empty clock can not spend time that
+        // is not there
+        assert_eq!(limits(&clock(1000, 5000, None)), (millis(970), millis(970)));
+    }
+
+    #[test]
+    fn extends_the_soft_limit_while_the_best_move_changes() {
+        let mut manager = clock(60000, 0, None);
+        assert_eq!(manager.extended_soft_limit(), millis(1999));
+        manager.update_best_move_stability(true);
+        assert_eq!(manager.extended_soft_limit(), Some(Duration::from_micros(2798600)));
+        for _ in 0..10 {
+            manager.update_best_move_stability(true);
+        }
+        assert_eq!(manager.extended_soft_limit(), millis(5997));
+        // A stable best move gives half of the extension back each time
+        manager.update_best_move_stability(false);
+        assert_eq!(manager.extended_soft_limit(), millis(3998));
+        manager.update_best_move_stability(false);
+        assert_eq!(manager.extended_soft_limit(), Some(Duration::from_micros(2998500)));
+
+        // The extension never goes past the hard limit
+        let mut move_time = TimeManager::new(&TimeControl { move_time : millis(500), ..TimeControl::default() });
+        move_time.update_best_move_stability(true);
+        assert_eq!(move_time.extended_soft_limit(), millis(470));
+    }
+
+    #[test]
+    fn starts_iterations_expected_to_finish_in_time() {
+        let manager = clock(60000, 0, None);
+        assert!(manager.should_start_iteration(Duration::from_millis(900)));
+        assert!(!manager.should_start_iteration(Duration::from_millis(1000)));
+        let infinite = TimeManager::infinite();
+        assert!(infinite.should_start_iteration(Duration::from_secs(3600)));
+        assert!(!infinite.hard_limit_reached());
+    }
+}
@@ -0,0 +1,142 @@
+// Lock-free transposition table
+//
+// Results of earlier searches are stored by Zobrist hash so they can be
+// reused when a position is reached again, by another move order or by
+// another search thread. Each entry is two atomics, the packed data and the
+// hash exclusive or'd with the data. A reader only trusts an entry when the
+// two agree, so an entry torn by two threads writing at once is ignored
+// rather than misread, and no locks are needed.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{Move, Pieces};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Bound {
+    // The score is exact
+    Exact,
+    // The score is at least this, the search failed high
+    Lower,
+    // The score is at most this, the search failed low
+    Upper
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct TableEntry {
+    pub best_move : Option<Move>,
+    pub score : i32,
+    pub depth : u32,
+    pub bound : Bound
+}
+
+#[derive(Default)]
+struct Slot {
+    // Hash exclusive or'd with the data
+    check : AtomicU64,
+    data : AtomicU64
+}
+
+pub struct TranspositionTable {
+    slots : Vec<Slot>,
+    mask : usize
+}
+
+/// Packs a move into 16 bits, six for each tile and three for the promotion.
+/// Zero is never a real move as a piece can not move to its own tile.
+fn encode_move(mv: Option<Move>) -> u64 {
+    let mv = match mv {
+        Some(mv) => mv,
+        None => return 0
+    };
+    let promotion = match mv.promotion {
+        Some(Pieces::Queen) => 1,
+        Some(Pieces::Rook { .. }) => 2,
+        Some(Pieces::Bishop) => 3,
+        Some(Pieces::Knight) => 4,
+        _ => 0
+    };
+    mv.from as u64 | (mv.to as u64) << 6 | promotion << 12
+}
+
+fn decode_move(bits: u64) -> Option<Move> {
+    if bits == 0 {
+        return None;
+    }
+    let promotion = match bits >> 12 & 0b111 {
+        1 => Some(Pieces::Queen),
+        2 => Some(Pieces::Rook { has_moved: true }),
+        3 => Some(Pieces::Bishop),
+        4 => Some(Pieces::Knight),
+        _ => None
+    };
+    Some(Move {
+        from : (bits & 0o77) as usize,
+        to : (bits >> 6 & 0o77) as usize,
+        promotion
+    })
+}
+
+impl TranspositionTable {
+    /// Creates a table using about the given number of megabytes, rounded
+    /// down to a power of two number of entries
+    pub fn new(megabytes: usize) -> TranspositionTable {
+        let bytes = megabytes.max(1) * 1024 * 1024;
+        let fits = (bytes / std::mem::size_of::<Slot>()).max(1);
+        let count = 1 << (usize::BITS - 1 - fits.leading_zeros());
+        TranspositionTable {
+            slots : (0..count).map(|_| Slot::default()).collect(),
+            mask : count - 1
+        }
+    }
+
+    pub fn clear(&self) {
+        for slot in &self.slots {
+            slot.check.store(0, Ordering::Relaxed);
+            slot.data.store(0, Ordering::Relaxed);
+        }
+    }
+
+    pub fn probe(&self, hash: u64) -> Option<TableEntry> {
+        let slot = &self.slots[hash as usize & self.mask];
+        let data = slot.data.load(Ordering::Relaxed);
+        if data == 0 || slot.check.load(Ordering::Relaxed) ^ data != hash {
+            return None;
+        }
+        let bound = match data >> 40 & 0b11 {
+            0 => Bound::Exact,
+            1 => Bound::Lower,
+            _ => Bound::Upper
+        };
+        Some(TableEntry {
+            best_move : decode_move(data & 0xFFFF),
+            score : (data >> 16 & 0xFFFF) as u16 as i16 as i32,
+            depth : (data >> 32 & 0xFF) as u32,
+            bound
+        })
+    }
+
+    pub fn store(&self, hash: u64, entry: TableEntry) {
+        let slot = &self.slots[hash as usize & self.mask];
+
+        // Keep deeper results for the same position unless this one is exact
+        if let Some(existing) = self.probe(hash) {
+            if existing.depth > entry.depth && entry.bound != Bound::Exact {
+                return;
+            }
+        }
+
+        let bound : u64 = match entry.bound {
+            Bound::Exact => 0,
+            Bound::Lower => 1,
+            Bound::Upper => 2
+        };
+        let data = encode_move(entry.best_move)
+            | (entry.score as i16 as u16 as u64) << 16
+            | (entry.depth.min(0xFF) as u64) << 32
+            | bound << 40
+            // Marks the slot as filled even when every other field is zero
+            | 1 << 42;
+        slot.check.store(hash ^ data, Ordering::Relaxed);
+        slot.data.store(data, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,309 @@
+// Texel tuning of the evaluation weights
+//
+// Reads a file of quiet positions labelled with the result of the game they
+// were taken from, one per line as a FEN followed by the result:
+//
+//     rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1 [0.5]
+//
+// Results can be written as 1-0, 0-1 or 1/2-1/2, or as the score for White
+// in brackets. The `<FEN> | <score> | <result>` lines written by `datagen`
+// are read too, ignoring the score. The static evaluation is turned into an
+// expected score by a logistic curve, and the loss is the mean squared
+// difference between that and the real results. Every weight is then nudged
+// up and down by a step in turn, keeping changes that lower the loss, with
+// the step halved once no weight can be improved. The tuned weights are
+// written out as the Rust source of `eval_weights`.
+//
+// The loss of each nudge is worked out by a set of threads started once for
+// the whole run, each keeping its own share of the positions.
+
+use std::fs;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::{self, Scope};
+use std::time::Instant;
+
+
+use crate::eval_params::EvalParams;
+use crate::eval_weights::DEFAULT_PARAMS;
+use crate::evaluation::evaluate_with_params;
+use crate::position::Position;
+use crate::{Colors, Tile};
+
+// Local search step sizes, starting large and halving down to one
+const INITIAL_STEP : i32 = 8;
+// Range and resolution of the scaling constant search
+const MAX_SCALING : f64 = 3.0;
+const SCALING_STEPS : [f64; 3] = [0.1, 0.01, 0.001];
+
+struct TuningPosition {
+    board : [Tile; 64],
+    // Score of the game for White, 1 for a win and 0 for a loss
+    result : f64
+}
+
+/// Reads the result at the end of a line, as the score for White
+fn parse_result(text: &str) -> Option<f64> {
+    let text = text.trim().trim_end_matches(';').trim_matches('"');
+    match text {
+        "1-0" | "[1.0]" | "[1]" | "1.0" => Some(1.0),
+        "0-1" | "[0.0]" | "[0]" | "0.0" => Some(0.0),
+        "1/2-1/2" | "[0.5]" | "0.5" => Some(0.5),
+        _ => None
+    }
+}
+
+/// Reads the labelled positions from a file, skipping blank lines
+fn read_positions(path: &str) -> Result<Vec<TuningPosition>, String> {
+    let contents = fs::read_to_string(path).map_err(|error| format!("can not read {}: {}", path, error))?;
+    let mut positions : Vec<TuningPosition> = vec![];
+
+    for (line_number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        // The result is the last word, or after `c9` in EPD style lines, or
+        // the last field of `datagen` lines
+        let (fen, result) = if line.contains('|') {
+            let fields : Vec<&str> = line.split('|').collect();
+            (fields[0], fields[fields.len() - 1])
+        } else if let Some((fen, result)) = line.split_once(" c9 ") {
+            (fen, result)
+        } else {
+            line.rsplit_once(char::is_whitespace).unwrap_or((line, ""))
+        };
+        let result = parse_result(result)
+            .ok_or_else(|| format!("{}:{}: missing game result", path, line_number + 1))?;
+        let position = Position::from_fen(fen)
+            .map_err(|error| format!("{}:{}: {}", path, line_number + 1, error))?;
+        positions.push(TuningPosition { board: *position.board(), result });
+    }
+
+    Ok(positions)
+}
+
+/// Expected score for White of a position evaluated at `score` centipawns
+#[inline]
+fn sigmoid(score: f64, scaling: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-scaling * score / 400.0))
+}
+
+/// Static evaluation of every position from White's point of view
+fn evaluate_all(positions: &[TuningPosition], params: &EvalParams) -> Vec<f64> {
+    positions.iter()
+        .map(|position| evaluate_with_params(&position.board, Colors::White, params, None) as f64)
+        .collect()
+}
+
+/// Sum of the squared differences between the results and the expected scores
+fn squared_error(positions: &[TuningPosition], scores: &[f64], scaling: f64) -> f64 {
+    positions.iter().zip(scores)
+        .map(|(position, &score)| (position.result - sigmoid(score, scaling)).powi(2))
+        .sum()
+}
+
+/// Mean squared error between the results and the expected scores
+fn loss(positions: &[TuningPosition], scores: &[f64], scaling: f64) -> f64 {
+    squared_error(positions, scores, scaling) / positions.len() as f64
+}
+
+/// Threads that each evaluate a share of the positions, for working out the
+/// loss of many sets of weights without starting threads for each one. The
+/// threads finish once this is dropped.
+struct LossWorkers {
+    requests : Vec<Sender<Arc<EvalParams>>>,
+    // Squared error of each thread's share
+    errors : Receiver<f64>,
+    position_count : usize
+}
+impl LossWorkers {
+    fn start<'scope>(scope: &'scope Scope<'scope, '_>, positions: &'scope [TuningPosition], scaling: f64, threads: usize) -> LossWorkers {
+        let chunk_size = positions.len().div_ceil(threads.max(1)).max(1);
+        let (error_sender, errors) = mpsc::channel::<f64>();
+        let requests = positions.chunks(chunk_size).map(|chunk| {
+            let (request_sender, params_received) = mpsc::channel::<Arc<EvalParams>>();
+            let error_sender = error_sender.clone();
+            scope.spawn(move || {
+                for params in params_received {
+                    let error = squared_error(chunk, &evaluate_all(chunk, &params), scaling);
+                    if error_sender.send(error).is_err() {
+                        break;
+                    }
+                }
+            });
+            request_sender
+        }).collect();
+        LossWorkers {
+            requests,
+            errors,
+            position_count : positions.len()
+        }
+    }
+
+    /// Mean squared error of the weights over all the positions
+    fn loss(&self, params: &EvalParams) -> f64 {
+        let params = Arc::new(params.clone());
+        for request in self.requests.iter() {
+            request.send(Arc::clone(&params)).expect("tuning threads run until dropped");
+        }
+        let total : f64 = self.errors.iter().take(self.requests.len()).sum();
+        total / self.position_count as f64
+    }
+}
+
+/// Finds the scaling constant that best fits the results to the current
+/// weights, so that tuning changes the weights rather than their scale
+fn fit_scaling(positions: &[TuningPosition], params: &EvalParams) -> f64 {
+    let scores = evaluate_all(positions, params);
+    let mut best = 1.0;
+    let mut low = 0.0;
+    let mut high = MAX_SCALING;
+
+    for step in SCALING_STEPS {
+        let mut best_loss = f64::MAX;
+        let mut scaling = low;
+        while scaling <= high {
+            let error = loss(positions, &scores, scaling);
+            if error < best_loss {
+                best_loss = error;
+                best = scaling;
+            }
+            scaling += step;
+        }
+        low = (best - step).max(0.0);
+        high = (best + step).min(MAX_SCALING);
+    }
+    best
+}
+
+/// Tunes the weights by local search, returning the best weights found
+/// with the loss worked out on threads kept for the whole run
+fn tune(positions: &[TuningPosition], initial: &EvalParams, scaling: f64, output: &str) -> EvalParams {
+    let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+    thread::scope(|scope| {
+        let workers = LossWorkers::start(scope, positions, scaling, threads);
+        local_search(&workers, initial, output)
+    })
+}
+
+/// Nudges every weight in turn, keeping the changes that lower the loss,
+/// until no weight can be improved by a single step
+fn local_search(workers: &LossWorkers, initial: &EvalParams, output: &str) -> EvalParams {
+    let mut params = initial.clone();
+    let mut best_loss = workers.loss(&params);
+    let weight_count = params.weights_mut().len();
+    let start = Instant::now();
+    println!("{} weights, initial loss {:.6}", weight_count, best_loss);
+
+    let mut step = INITIAL_STEP;
+    let mut pass = 0;
+    while step >= 1 {
+        pass += 1;
+        let mut improved = false;
+
+        for weight in 0..weight_count {
+            for delta in [step, -step] {
+                *params.weights_mut()[weight] += delta;
+                let error = workers.loss(&params);
+                if error < best_loss {
+                    best_loss = error;
+                    improved = true;
+                    break;
+                }
+                *params.weights_mut()[weight] -= delta;
+            }
+        }
+
+        println!("pass {} step {} loss {:.6} time {}s", pass, step, best_loss, start.elapsed().as_secs());
+        // Save after every pass, tuning can take hours
+        if let Err(error) = fs::write(output, params.to_rust_source()) {
+            eprintln!("can not write {}: {}", output, error);
+        }
+        if !improved {
+            step /= 2;
+        }
+    }
+
+    params
+}
+
+/// Runs the tuner on a file of labelled positions, writing the weights to
+/// `output`
+pub fn run(positions_path: &str, output: &str) -> Result<(), String> {
+    let positions = read_positions(positions_path)?;
+    if positions.is_empty() {
+        return Err(format!("no positions in {}", positions_path));
+    }
+    println!("{} positions", positions.len());
+
+    let scaling = fit_scaling(&positions, &DEFAULT_PARAMS);
+    println!("scaling constant {:.3}", scaling);
+
+    tune(&positions, &DEFAULT_PARAMS, scaling, output);
+    println!("weights written to {}", output);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Positions with a spread of evaluations, each labelled as the result
+    /// `label` gives for its evaluation
+    fn labelled_positions(label: impl Fn(f64) -> f64) -> Vec<TuningPosition> {
+        [
+            "4k3/8/8/8/8/8/8/3QK3 w - - 0 1",
+            "4k3/8/8/8/8/8/PPP5/4K3 w - - 0 1",
+            "4k3/pp6/8/8/8/8/8/4K3 w - - 0 1",
+            "3rk3/8/8/8/8/8/8/2N1K3 w - - 0 1",
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1",
+            "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3"
+        ].iter().map(|fen| {
+            let board = *Position::from_fen(fen).unwrap().board();
+            let score = evaluate_with_params(&board, Colors::White, &DEFAULT_PARAMS, None) as f64;
+            TuningPosition { board, result : label(score) }
+        }).collect()
+    }
+
+    #[test]
+    fn maps_scores_to_expected_results() {
+        assert_eq!(sigmoid(0.0, 1.0), 0.5);
+        assert!((sigmoid(400.0, 1.0) - 10.0 / 11.0).abs() < 1e-12);
+        assert!((sigmoid(-250.0, 1.3) - (1.0 - sigmoid(250.0, 1.3))).abs() < 1e-12);
+        // A larger scaling constant makes the same score more decisive
+        assert!(sigmoid(100.0, 2.0) > sigmoid(100.0, 1.0));
+    }
+
+    #[test]
+    fn measures_the_mean_squared_error() {
+        let positions = labelled_positions(|_| 1.0);
+        let scores = vec![0.0; positions.len()];
+        assert_eq!(loss(&positions, &scores, 1.0), 0.25);
+        let exact = labelled_positions(|score| sigmoid(score, 1.0));
+        assert!(loss(&exact, &evaluate_all(&exact, &DEFAULT_PARAMS), 1.0) < 1e-12);
+    }
+
+    #[test]
+    fn fits_the_scaling_constant_of_the_results() {
+        for scaling in [0.7, 1.3, 2.45] {
+            let positions = labelled_positions(|score| sigmoid(score, scaling));
+            let fitted = fit_scaling(&positions, &DEFAULT_PARAMS);
+            assert!((fitted - scaling).abs() < 0.002, "{} fitted as {}", scaling, fitted);
+        }
+    }
+
+    #[test]
+    fn workers_agree_with_a_single_thread() {
+        let positions = labelled_positions(|score| if score > 0.0 { 1.0 } else { 0.5 });
+        let expected = loss(&positions, &evaluate_all(&positions, &DEFAULT_PARAMS), 1.1);
+        let mut params = DEFAULT_PARAMS;
+        params.piece_values[1][0] += 100;
+        let nudged = loss(&positions, &evaluate_all(&positions, &params), 1.1);
+        thread::scope(|scope| {
+            let workers = LossWorkers::start(scope, &positions, 1.1, 4);
+            assert!((workers.loss(&DEFAULT_PARAMS) - expected).abs() < 1e-12);
+            assert!((workers.loss(&params) - nudged).abs() < 1e-12);
+        });
+    }
+}
@@ -0,0 +1,325 @@
+// Universal Chess Interface
+//
+// Reads commands from standard input and answers on standard output, so the
+// engine can be used from any UCI compatible GUI. Searches run on their own
+// thread so that `stop` and `isready` are answered while thinking.
+
+use std::io::{self, BufRead};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::mate_search::{find_mate, MateSearchResult};
+use crate::nnue::Network;
+use crate::polyglot::Book;
+use crate::search::{is_mate_score, search, SearchLimits, SearchResult, MATE_SCORE};
+use crate::position::Position;
+use crate::square::{move_to_uci, play_uci_move};
+use crate::syzygy::Tablebases;
+use crate::text_board::{board_to_text, TextBoardStyle};
+use crate::time_management::{TimeControl, TimeManager};
+use crate::transposition_table::TranspositionTable;
+use crate::{Colors, Move};
+
+const DEFAULT_HASH_MEGABYTES : usize = 16;
+const MAX_HASH_MEGABYTES : usize = 4096;
+const MAX_THREADS : usize = 256;
+const MAX_MULTI_PV : usize = 64;
+
+/// Engine settings changed with `setoption`
+struct Options {
+    hash_megabytes : usize,
+    threads : usize,
+    multi_pv : usize,
+    // Whether to evaluate with the network loaded from the EvalFile option
+    use_nnue : bool,
+    network : Option<Arc<Network>>,
+    // Tables found in the SyzygyPath directories
+    tablebases : Option<Arc<Tablebases>>,
+    // Opening book from the BookFile option, and whether to always play its
+    // heaviest move rather than a random one
+    book : Option<Book>,
+    book_best_move : bool
+}
+
+/// Formats a score as UCI expects, in centipawns or moves to mate
+fn score_to_string(score: i32) -> String {
+    if is_mate_score(score) {
+        let plies = MATE_SCORE - score.abs();
+        let moves = (plies + 1) / 2;
+        format!("mate {}", if score > 0 { moves } else { -moves })
+    } else {
+        format!("cp {}", score)
+    }
+}
+
+/// Prints one info line for each of the ranked lines of an iteration
+fn print_info(result: &SearchResult) {
+    let millis = result.elapsed.as_millis().max(1);
+    for (rank, line) in result.lines.iter().enumerate() {
+        let pv : Vec<String> = line.pv.iter().map(|&mv| move_to_uci(mv)).collect();
+        println!(
+            "info depth {} multipv {} score {} nodes {} nps {} time {} pv {}",
+            result.depth,
+            rank + 1,
+            score_to_string(line.score),
+            result.nodes,
+            result.nodes as u128 * 1000 / millis,
+            millis,
+            pv.join(" ")
+        );
+    }
+}
+
+/// Handles `position [startpos | fen <fen>] [moves <move>...]`, returning
+/// the position along with the hashes of the positions before it
+fn parse_position<'a>(mut tokens: impl Iterator<Item = &'a str>) -> Result<(Position, Vec<u64>), String> {
+    let mut position = match tokens.next().unwrap_or("") {
+        "startpos" => Position::default(),
+        "fen" => {
+            let fen : Vec<&str> = tokens.by_ref().take_while(|&token| token != "moves").collect();
+            Position::from_fen(&fen.join(" "))?
+        },
+        _ => return Err("invalid position".to_string())
+    };
+    let mut history : Vec<u64> = vec![];
+    for token in tokens.filter(|&token| token != "moves") {
+        history.push(position.hash());
+        play_uci_move(&mut position, token)?;
+    }
+    Ok((position, history))
+}
+
+/// Handles `setoption name <name> value <value>`
+fn parse_setoption<'a>(tokens: impl Iterator<Item = &'a str>, options: &mut Options) {
+    let tokens : Vec<&str> = tokens.collect();
+    let value_index = tokens.iter().position(|&token| token == "value").unwrap_or(tokens.len());
+    let name = tokens[..value_index].iter().skip_while(|&&token| token == "name").copied().collect::<Vec<&str>>().join(" ");
+    let value = tokens.get(value_index + 1..).unwrap_or_default().join(" ");
+
+    match name.to_lowercase().as_str() {
+        "hash" => match value.parse::<usize>() {
+            Ok(megabytes) => options.hash_megabytes = megabytes.clamp(1, MAX_HASH_MEGABYTES),
+            Err(_) => println!("info string invalid Hash value {}", value)
+        },
+        "threads" => match value.parse::<usize>() {
+            Ok(threads) => options.threads = threads.clamp(1, MAX_THREADS),
+            Err(_) => println!("info string invalid Threads value {}", value)
+        },
+        "multipv" => match value.parse::<usize>() {
+            Ok(multi_pv) => options.multi_pv = multi_pv.clamp(1, MAX_MULTI_PV),
+            Err(_) => println!("info string invalid MultiPV value {}", value)
+        },
+        "usennue" => match value.to_lowercase().as_str() {
+            "true" => options.use_nnue = true,
+            "false" => options.use_nnue = false,
+            _ => println!("info string invalid UseNNUE value {}", value)
+        },
+        "evalfile" => match Network::load(&value) {
+            Ok(network) => {
+                options.network = Some(Arc::new(network));
+                println!("info string loaded network {}", value);
+            },
+            Err(error) => println!("info string {}", error)
+        },
+        "bookfile" => {
+            if value.is_empty() || value == "<empty>" {
+                options.book = None;
+                return;
+            }
+            match Book::load(&value) {
+                Ok(book) => {
+                    println!("info string loaded book {} with {} entries", value, book.len());
+                    options.book = Some(book);
+                },
+                Err(error) => println!("info string {}", error)
+            }
+        },
+        "bookbestmove" => match value.to_lowercase().as_str() {
+            "true" => options.book_best_move = true,
+            "false" => options.book_best_move = false,
+            _ => println!("info string invalid BookBestMove value {}", value)
+        },
+        "syzygypath" => {
+            if value.is_empty() || value == "<empty>" {
+                options.tablebases = None;
+                return;
+            }
+            let tablebases = Tablebases::open(&value);
+            println!("info string found {} tablebases with up to {} pieces", tablebases.len(), tablebases.max_pieces);
+            options.tablebases = if !tablebases.is_empty() { Some(Arc::new(tablebases)) } else { None };
+        },
+        _ => println!("info string unknown option {}", name)
+    }
+}
+
+/// Handles `go` and its limits for the side to move, along with the number
+/// of moves to look for a mate in when given `go mate <moves>` and whether
+/// the search is infinite
+fn parse_go<'a>(mut tokens: impl Iterator<Item = &'a str>, turn: Colors, options: &Options) -> (SearchLimits, Option<u32>, bool) {
+    let mut limits = SearchLimits::infinite();
+    limits.threads = options.threads;
+    limits.multi_pv = options.multi_pv;
+    limits.tablebases = options.tablebases.clone();
+    if options.use_nnue {
+        limits.network = options.network.clone();
+        if limits.network.is_none() {
+            println!("info string no network loaded, using the handcrafted evaluation");
+        }
+    }
+    let mut control = TimeControl::default();
+    let mut infinite = false;
+    let mut mate : Option<u32> = None;
+    let millis = |value: Option<&str>| value.and_then(|value| value.parse::<u64>().ok()).map(Duration::from_millis);
+
+    while let Some(token) = tokens.next() {
+        match (token, turn) {
+            ("wtime", Colors::White) | ("btime", Colors::Black) => control.time_left = millis(tokens.next()),
+            ("winc", Colors::White) | ("binc", Colors::Black) => control.increment = millis(tokens.next()).unwrap_or_default(),
+            ("wtime" | "btime" | "winc" | "binc", _) => { tokens.next(); },
+            ("movestogo", _) => control.moves_to_go = tokens.next().and_then(|value| value.parse().ok()),
+            ("movetime", _) => control.move_time = millis(tokens.next()),
+            ("depth", _) => limits.depth = tokens.next().and_then(|value| value.parse().ok()),
+            ("nodes", _) => limits.nodes = tokens.next().and_then(|value| value.parse().ok()),
+            ("mate", _) => mate = tokens.next().and_then(|value| value.parse().ok()),
+            ("infinite", _) => infinite = true,
+            _ => ()
+        }
+    }
+
+    if !infinite {
+        limits.time = TimeManager::new(&control);
+    }
+    (limits, mate, infinite)
+}
+
+/// Runs the mate solver, printing the mating line if one is found
+fn search_mate(position: &Position, moves: u32, stop: &AtomicBool) -> Option<Move> {
+    let result = find_mate(position, moves, stop, |moves, nodes| {
+        println!("info depth {} nodes {} string no mate in {}", moves * 2 - 1, nodes, moves);
+    });
+    match result {
+        MateSearchResult::Mate(line) => {
+            let pv : Vec<String> = line.iter().map(|&mv| move_to_uci(mv)).collect();
+            println!("info depth {} score mate {} pv {}", line.len(), line.len().div_ceil(2), pv.join(" "));
+            line.first().copied()
+        },
+        MateSearchResult::NoMate => {
+            println!("info string no mate in {} found", moves);
+            None
+        },
+        MateSearchResult::Stopped => None
+    }
+}
+
+/// Stops any running search and waits for it to print its best move
+fn stop_search(stop: &AtomicBool, search_thread: &mut Option<JoinHandle<()>>) {
+    if let Some(handle) = search_thread.take() {
+        stop.store(true, Ordering::Relaxed);
+        let _ = handle.join();
+    }
+}
+
+pub fn run() {
+    let mut position = Position::default();
+    // Hashes of the positions played before the current one
+    let mut history : Vec<u64> = vec![];
+    let mut options = Options {
+        hash_megabytes : DEFAULT_HASH_MEGABYTES,
+        threads : 1,
+        multi_pv : 1,
+        use_nnue : false,
+        network : None,
+        tablebases : None,
+        book : None,
+        book_best_move : false
+    };
+    let mut table : Arc<TranspositionTable> = Arc::new(TranspositionTable::new(options.hash_megabytes));
+    let stop : Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+    let mut search_thread : Option<JoinHandle<()>> = None;
+
+    for line in io::stdin().lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break
+        };
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some("uci") => {
+                println!("id name Chess_Engine");
+                println!("id author SamKn1ght");
+                println!("option name Hash type spin default {} min 1 max {}", DEFAULT_HASH_MEGABYTES, MAX_HASH_MEGABYTES);
+                println!("option name Threads type spin default 1 min 1 max {}", MAX_THREADS);
+                println!("option name MultiPV type spin default 1 min 1 max {}", MAX_MULTI_PV);
+                println!("option name UseNNUE type check default false");
+                println!("option name EvalFile type string default <empty>");
+                println!("option name SyzygyPath type string default <empty>");
+                println!("option name BookFile type string default <empty>");
+                println!("option name BookBestMove type check default false");
+                println!("uciok");
+            },
+            Some("isready") => println!("readyok"),
+            Some("ucinewgame") => {
+                stop_search(&stop, &mut search_thread);
+                position = Position::default();
+                history.clear();
+                table.clear();
+            },
+            Some("setoption") => {
+                stop_search(&stop, &mut search_thread);
+                let hash_megabytes = options.hash_megabytes;
+                parse_setoption(tokens, &mut options);
+                if options.hash_megabytes != hash_megabytes {
+                    table = Arc::new(TranspositionTable::new(options.hash_megabytes));
+                }
+            },
+            // Not part of UCI, but handy when talking to the engine by hand
+            Some("d") => {
+                print!("{}", board_to_text(position.board(), TextBoardStyle::default(), None));
+                println!("Fen: {}", position.to_fen());
+            },
+            Some("position") => {
+                stop_search(&stop, &mut search_thread);
+                match parse_position(tokens) {
+                    Ok((parsed, parsed_history)) => (position, history) = (parsed, parsed_history),
+                    Err(error) => println!("info string {}", error)
+                }
+            },
+            Some("go") => {
+                stop_search(&stop, &mut search_thread);
+                let (mut limits, mate, infinite) = parse_go(tokens, position.turn(), &options);
+                limits.history = history.clone();
+                // Book moves are played without searching
+                let book_move = match (&mut options.book, mate) {
+                    (Some(book), None) => book.choose(&position, options.book_best_move),
+                    _ => None
+                };
+                stop.store(false, Ordering::Relaxed);
+                let stop = Arc::clone(&stop);
+                let table = Arc::clone(&table);
+                search_thread = Some(thread::spawn(move || {
+                    // Without a forced mate fall back to a normal search
+                    let best_move = book_move
+                        .or_else(|| mate.and_then(|moves| search_mate(&position, moves, &stop)))
+                        .or_else(|| search(&position, limits, &table, &stop, print_info).best_move);
+                    // An infinite search only gives its move when told to stop
+                    while infinite && !stop.load(Ordering::Relaxed) {
+                        thread::sleep(Duration::from_millis(1));
+                    }
+                    match best_move {
+                        Some(mv) => println!("bestmove {}", move_to_uci(mv)),
+                        None => println!("bestmove 0000")
+                    }
+                }));
+            },
+            Some("stop") => stop_search(&stop, &mut search_thread),
+            Some("quit") => {
+                stop_search(&stop, &mut search_thread);
+                break;
+            },
+            _ => ()
+        }
+    }
+    stop_search(&stop, &mut search_thread);
+}
@@ -0,0 +1,125 @@
+// Zobrist hashing
+//
+// Each (piece, colour, tile) combination, the side to move, every castling
+// right and every en passant file is given a random 64 bit key. The hash of
+// a board is the exclusive or of the keys of everything on it, so equal
+// positions always share a hash and different ones almost never do.
+// Positions keep their hash up to date as moves are played by taking the
+// keys of what a move changes out and putting the new ones in, see
+// `position`, rather than hashing the whole board again.
+
+use crate::{Colors, Pieces, Tile};
+
+const PIECE_KEYS : usize = 12 * 64;
+const SIDE_KEY : usize = PIECE_KEYS;
+const CASTLING_KEYS : usize = SIDE_KEY + 1;
+const EN_PASSANT_KEYS : usize = CASTLING_KEYS + 4;
+const KEY_COUNT : usize = EN_PASSANT_KEYS + 8;
+
+/// Fills the key table from a fixed seed with xorshift, so hashes are the
+/// same on every run
+const fn generate_keys() -> [u64; KEY_COUNT] {
+    let mut keys : [u64; KEY_COUNT] = [0; KEY_COUNT];
+    let mut state : u64 = 0x2545_F491_4F6C_DD1D;
+    let mut i = 0;
+    while i < KEY_COUNT {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        keys[i] = state;
+        i += 1;
+    }
+    keys
+}
+
+static KEYS : [u64; KEY_COUNT] = generate_keys();
+
+/// Index of a piece among the twelve coloured piece types
+fn piece_key_index(piece: Pieces, color: Colors) -> usize {
+    let piece_index = match piece {
+        Pieces::King { .. } => 0,
+        Pieces::Queen => 1,
+        Pieces::Rook { .. } => 2,
+        Pieces::Knight => 3,
+        Pieces::Bishop => 4,
+        Pieces::Pawn { .. } => 5
+    };
+    match color {
+        Colors::White => piece_index,
+        Colors::Black => piece_index + 6
+    }
+}
+
+/// Key of a piece of the given colour on a tile
+#[inline]
+pub fn piece_key(piece: Pieces, color: Colors, index: usize) -> u64 {
+    KEYS[piece_key_index(piece, color) * 64 + index]
+}
+
+/// Key hashed in when Black is to move
+#[inline]
+pub fn side_key() -> u64 {
+    KEYS[SIDE_KEY]
+}
+
+/// Key of a castling right, numbered in the order of FEN: K, Q, k and q
+#[inline]
+pub fn castling_key(right: usize) -> u64 {
+    KEYS[CASTLING_KEYS + right]
+}
+
+/// Key of the file of the en passant square
+#[inline]
+pub fn en_passant_key(file: usize) -> u64 {
+    KEYS[EN_PASSANT_KEYS + file]
+}
+
+/// Whether the king and rook on the given tiles can still castle
+fn has_castling_right(board: &[Tile; 64], color: Colors, king: usize, rook: usize) -> bool {
+    board[king].color == Some(color)
+        && board[king].piece == Some(Pieces::King { has_moved: false })
+        && board[rook].color == Some(color)
+        && board[rook].piece == Some(Pieces::Rook { has_moved: false })
+}
+
+pub fn hash_board(board: &[Tile; 64], turn: Colors) -> u64 {
+    let mut hash : u64 = 0;
+
+    for (index, tile) in board.iter().enumerate() {
+        if let (Some(piece), Some(color)) = (tile.piece, tile.color) {
+            hash ^= piece_key(piece, color, index);
+            if let Pieces::Pawn { en_passantable: true, .. } = piece {
+                hash ^= en_passant_key(index % 8);
+            }
+        }
+    }
+
+    if turn == Colors::Black {
+        hash ^= side_key();
+    }
+
+    let rights : [(Colors, usize, usize); 4] = [
+        (Colors::White, 0o74, 0o77),
+        (Colors::White, 0o74, 0o70),
+        (Colors::Black, 0o04, 0o07),
+        (Colors::Black, 0o04, 0o00)
+    ];
+    for (i, (color, king, rook)) in rights.into_iter().enumerate() {
+        if has_castling_right(board, color, king, rook) {
+            hash ^= castling_key(i);
+        }
+    }
+
+    hash
+}
+
+/// Hashes only the pawns, for caching pawn structure which rarely changes
+pub fn hash_pawns(board: &[Tile; 64]) -> u64 {
+    let mut hash : u64 = 0;
+    for (index, tile) in board.iter().enumerate() {
+        if let (Some(piece @ Pieces::Pawn { .. }), Some(color)) = (tile.piece, tile.color) {
+            hash ^= piece_key(piece, color, index);
+        }
+    }
+    hash
+}